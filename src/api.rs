@@ -0,0 +1,530 @@
+use crate::file_utils::SessionRoots;
+use crate::types::{AppState, FileNode, SelectionState};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+/// Largest request body accepted, selections are lists of paths.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Local API settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiConfig {
+  /// Address to listen on, `127.0.0.1:PORT` or `unix:/path/to/socket` (off if not set)
+  #[serde(default)]
+  pub listen: Option<String>,
+}
+
+/// Where the API listens, only loopback addresses and unix sockets are allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiAddress {
+  /// Loopback TCP address
+  Tcp(SocketAddr),
+  /// Unix socket path
+  Unix(PathBuf),
+}
+
+impl ApiAddress {
+  /// Parses `127.0.0.1:PORT`, `localhost:PORT`, `[::1]:PORT` or `unix:/path`.
+  pub fn parse(text: &str) -> Result<Self> {
+    if let Some(path) = text.strip_prefix("unix:") {
+      if !cfg!(unix) {
+        anyhow::bail!("Unix sockets are not supported on this platform");
+      }
+      if path.is_empty() {
+        anyhow::bail!("Missing socket path in '{}'", text);
+      }
+      return Ok(ApiAddress::Unix(PathBuf::from(path)));
+    }
+
+    let text = match text.strip_prefix("localhost:") {
+      Some(port) => format!("127.0.0.1:{}", port),
+      None => text.to_string(),
+    };
+    let address: SocketAddr = text.parse().with_context(|| format!("Invalid API address '{}', use 127.0.0.1:PORT or unix:/path", text))?;
+    // the API has no authentication, it must not be reachable from other machines
+    if !address.ip().is_loopback() {
+      anyhow::bail!("API address {} is not a loopback address", address);
+    }
+    Ok(ApiAddress::Tcp(address))
+  }
+}
+
+impl std::fmt::Display for ApiAddress {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ApiAddress::Tcp(address) => write!(f, "http://{}", address),
+      ApiAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+    }
+  }
+}
+
+/// Request from an API client, handled by the app in its main loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiRequest {
+  /// Every node in the tree with its selection
+  GetTree,
+  /// Selected files and the token total
+  GetSelection,
+  /// Replace the selection with the paths
+  SetSelection(Vec<String>),
+  /// Add the paths to the selection
+  AddToSelection(Vec<String>),
+  /// Remove the paths from the selection
+  RemoveFromSelection(Vec<String>),
+  /// Token total and per-file counts of the selection
+  GetTokens,
+  /// Run the active backend on the selection
+  Run,
+  /// Result and packed content of the last finished run
+  GetOutput,
+}
+
+/// Status code and JSON body sent back to the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+  /// HTTP status code
+  pub status: u16,
+  /// JSON body
+  pub body: Value,
+}
+
+impl ApiResponse {
+  /// Creates a 200 response.
+  pub fn ok(body: Value) -> Self {
+    Self { status: 200, body }
+  }
+
+  /// Creates an error response with a message.
+  pub fn error(status: u16, message: impl Into<String>) -> Self {
+    Self {
+      status,
+      body: json!({ "error": message.into() }),
+    }
+  }
+}
+
+/// API request waiting for the app, with the channel for the reply.
+#[derive(Debug)]
+pub struct ApiEvent {
+  /// What the client asked for
+  pub request: ApiRequest,
+  /// Where the response goes
+  pub reply: oneshot::Sender<ApiResponse>,
+}
+
+/// Running API server, removes its unix socket or token file when dropped.
+pub struct ApiServer {
+  /// Address the server listens on
+  pub address: ApiAddress,
+  /// File with the token TCP clients must send (none for unix sockets)
+  pub token_file: Option<PathBuf>,
+}
+
+impl Drop for ApiServer {
+  fn drop(&mut self) {
+    if let ApiAddress::Unix(path) = &self.address {
+      let _ = std::fs::remove_file(path);
+    }
+    if let Some(path) = &self.token_file {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+/// What a TCP client has to send, unix socket clients are limited by the socket permissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TcpAccess {
+  /// Accepted Host headers, anything else may be a DNS rebinding page
+  hosts: Vec<String>,
+  /// Session token expected as `Authorization: Bearer <token>`
+  token: String,
+}
+
+impl TcpAccess {
+  /// Creates the access rules for a port with a new random token.
+  fn new(port: u16) -> Self {
+    Self {
+      hosts: vec![format!("127.0.0.1:{}", port), format!("localhost:{}", port), format!("[::1]:{}", port)],
+      token: session_token(),
+    }
+  }
+}
+
+/// Returns a random 128 bit token in hex.
+fn session_token() -> String {
+  use std::hash::{BuildHasher, Hasher};
+  // the std hasher is keyed with random bits from the OS, which avoids a dependency for this
+  let state = std::collections::hash_map::RandomState::new();
+  (0..2u8)
+    .map(|part| {
+      let mut hasher = state.build_hasher();
+      hasher.write_u8(part);
+      format!("{:016x}", hasher.finish())
+    })
+    .collect()
+}
+
+/// Returns where the token of the API on a port is written.
+fn token_file_path(port: u16) -> PathBuf {
+  dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join(format!("api-{}.token", port))
+}
+
+/// Writes the token to a file only the user can read.
+fn write_token_file(path: &Path, token: &str) -> Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+  }
+  let _ = std::fs::remove_file(path);
+  let mut options = std::fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+  let mut file = options.open(path).with_context(|| format!("Failed to write API token to {}", path.display()))?;
+  std::io::Write::write_all(&mut file, token.as_bytes()).with_context(|| format!("Failed to write API token to {}", path.display()))
+}
+
+/// Binds the address and serves requests in the background, forwarding them to the app through the sender.
+/// Binding happens before this returns, so a taken port is reported at startup.
+/// TCP clients need the session token, which is written to `token_file_path`.
+pub async fn start(address: ApiAddress, sender: mpsc::UnboundedSender<ApiEvent>) -> Result<ApiServer> {
+  let mut token_file = None;
+  match &address {
+    ApiAddress::Tcp(socket_address) => {
+      let listener = tokio::net::TcpListener::bind(socket_address).await.with_context(|| format!("Failed to listen on {}", socket_address))?;
+      // any local user can reach the port, and pages can through DNS rebinding
+      let port = listener.local_addr().map(|address| address.port()).unwrap_or(socket_address.port());
+      let access = std::sync::Arc::new(TcpAccess::new(port));
+      let path = token_file_path(port);
+      write_token_file(&path, &access.token)?;
+      token_file = Some(path);
+      tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+          let sender = sender.clone();
+          let access = access.clone();
+          tokio::spawn(async move {
+            let _ = serve_connection(stream, sender, Some(&access)).await;
+          });
+        }
+      });
+    }
+    #[cfg(unix)]
+    ApiAddress::Unix(path) => {
+      use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+      // a socket left behind by a siff that didn't exit cleanly is replaced, anything else is kept
+      if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+          anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+      }
+      let listener = tokio::net::UnixListener::bind(path).with_context(|| format!("Failed to listen on {}", path.display()))?;
+      // only the user can connect
+      std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).context("Failed to restrict socket permissions")?;
+      tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+          let sender = sender.clone();
+          tokio::spawn(async move {
+            let _ = serve_connection(stream, sender, None).await;
+          });
+        }
+      });
+    }
+    #[cfg(not(unix))]
+    ApiAddress::Unix(_) => anyhow::bail!("Unix sockets are not supported on this platform"),
+  }
+
+  Ok(ApiServer { address, token_file })
+}
+
+/// Returns a path relative to the session as sent to clients.
+pub fn relative_path(roots: &SessionRoots, path: &Path) -> String {
+  roots.relative(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Resolves paths from a client, relative to the root or absolute inside it.
+/// Fails with the unknown paths if any isn't in the tree, so nothing changes.
+pub fn resolve_paths(file_tree: &HashMap<PathBuf, FileNode>, roots: &SessionRoots, paths: &[String]) -> std::result::Result<Vec<PathBuf>, ApiResponse> {
+  let (resolved, unknown) = crate::file_utils::resolve_paths(file_tree, roots, paths);
+  if unknown.is_empty() {
+    Ok(resolved)
+  } else {
+    Err(ApiResponse {
+      status: 400,
+      body: json!({ "error": "Paths not in the tree (missing, ignored by siff, outside the root or not scanned yet)", "unknown": unknown }),
+    })
+  }
+}
+
+/// Returns every node below the root in tree order, with its selection.
+pub fn tree_json(state: &AppState) -> Value {
+  let mut entries = Vec::new();
+  let mut stack: Vec<PathBuf> = state.file_tree.get(&state.root_path).map(|root| root.children.iter().rev().cloned().collect()).unwrap_or_default();
+  while let Some(path) = stack.pop() {
+    let Some(node) = state.file_tree.get(&path) else {
+      continue;
+    };
+    let selection = match node.selection_state() {
+      SelectionState::All => "all",
+      SelectionState::Partial => "partial",
+      SelectionState::None => "none",
+    };
+    let mut entry = json!({ "path": relative_path(&state.roots, &path), "directory": node.is_directory, "selection": selection, "size": node.size });
+    if node.is_directory {
+      entry["files"] = node.total_files.into();
+      entry["selected_files"] = node.selected_files.into();
+      stack.extend(node.children.iter().rev().cloned());
+    } else if !node.content.is_text() {
+      entry["content"] = node.content.display_name().into();
+    }
+    entries.push(entry);
+  }
+  let mut tree = json!({ "scanning": state.is_scanning, "entries": entries });
+  match state.roots.len() {
+    1 => tree["root"] = json!(state.root_path),
+    _ => tree["roots"] = state.roots.iter().map(|(name, path)| json!({ "name": name, "path": path })).collect(),
+  }
+  tree
+}
+
+/// Reads one HTTP request, hands it to the app and writes the response (one request per connection).
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(stream: S, sender: mpsc::UnboundedSender<ApiEvent>, access: Option<&TcpAccess>) -> Result<()> {
+  let mut reader = BufReader::new(stream);
+  let response = match read_request(&mut reader).await {
+    Ok(request) => match check_access(&request, access).and_then(|_| parse_request(&request)) {
+      Ok(api_request) => {
+        let (reply, response) = oneshot::channel();
+        if sender.send(ApiEvent { request: api_request, reply }).is_err() {
+          ApiResponse::error(503, "siff is shutting down")
+        } else {
+          response.await.unwrap_or_else(|_| ApiResponse::error(503, "siff is shutting down"))
+        }
+      }
+      Err(response) => response,
+    },
+    Err(e) => ApiResponse::error(400, format!("{:#}", e)),
+  };
+
+  let body = serde_json::to_string(&response.body)?;
+  let head = format!(
+    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+    response.status,
+    reason_phrase(response.status),
+    body.len()
+  );
+  let stream = reader.get_mut();
+  stream.write_all(head.as_bytes()).await?;
+  stream.write_all(body.as_bytes()).await?;
+  stream.shutdown().await?;
+  Ok(())
+}
+
+/// HTTP request as read from the connection.
+#[derive(Debug, Default)]
+struct HttpRequest {
+  /// Request method
+  method: String,
+  /// Request path without the query
+  path: String,
+  /// Lowercased header names with their values
+  headers: Vec<(String, String)>,
+  /// Request body
+  body: Vec<u8>,
+}
+
+impl HttpRequest {
+  /// Returns the value of a header.
+  fn header(&self, name: &str) -> Option<&str> {
+    self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+  }
+}
+
+/// Reads the request line, headers and body.
+async fn read_request<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<HttpRequest> {
+  let mut line = String::new();
+  reader.read_line(&mut line).await.context("Failed to read request")?;
+  let mut parts = line.split_whitespace();
+  let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+    anyhow::bail!("Malformed request line");
+  };
+  let mut request = HttpRequest {
+    method: method.to_string(),
+    path: target.split('?').next().unwrap_or_default().to_string(),
+    ..Default::default()
+  };
+
+  loop {
+    line.clear();
+    if reader.read_line(&mut line).await.context("Failed to read headers")? == 0 {
+      anyhow::bail!("Connection closed in headers");
+    }
+    let header = line.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      request.headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+    if request.headers.len() > 100 {
+      anyhow::bail!("Too many headers");
+    }
+  }
+
+  let length: usize = request.header("content-length").map(|length| length.parse()).transpose().context("Invalid Content-Length")?.unwrap_or(0);
+  if length > MAX_BODY_SIZE {
+    anyhow::bail!("Request body too large");
+  }
+  request.body = vec![0; length];
+  reader.read_exact(&mut request.body).await.context("Failed to read request body")?;
+  Ok(request)
+}
+
+/// Body of the selection endpoints.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathsBody {
+  /// Paths relative to the root, or absolute paths inside it
+  paths: Vec<String>,
+}
+
+/// Checks the Host header and session token of TCP requests.
+fn check_access(request: &HttpRequest, access: Option<&TcpAccess>) -> std::result::Result<(), ApiResponse> {
+  let Some(access) = access else {
+    return Ok(());
+  };
+  // a rebound DNS name reaches the port with its own name as the host
+  if !request.header("host").is_some_and(|host| access.hosts.iter().any(|allowed| host.eq_ignore_ascii_case(allowed))) {
+    return Err(ApiResponse::error(403, "Host must be a loopback address with the API port"));
+  }
+  if request.header("authorization").and_then(|value| value.strip_prefix("Bearer ")) != Some(access.token.as_str()) {
+    return Err(ApiResponse::error(401, "Missing or wrong token, send Authorization: Bearer <token from the token file>"));
+  }
+  Ok(())
+}
+
+/// Maps an HTTP request to an API request.
+/// Browsers send an Origin header and can't send JSON without a preflight, so pages can't drive siff.
+fn parse_request(request: &HttpRequest) -> std::result::Result<ApiRequest, ApiResponse> {
+  if request.header("origin").is_some() {
+    return Err(ApiResponse::error(403, "Requests from browsers are not allowed"));
+  }
+  let writes = matches!(request.method.as_str(), "POST" | "PUT");
+  if writes && !request.header("content-type").is_some_and(|content_type| content_type.starts_with("application/json")) {
+    return Err(ApiResponse::error(415, "Content-Type must be application/json"));
+  }
+  let paths = || -> std::result::Result<Vec<String>, ApiResponse> {
+    serde_json::from_slice::<PathsBody>(&request.body)
+      .map(|body| body.paths)
+      .map_err(|e| ApiResponse::error(400, format!("Expected {{\"paths\": [...]}}: {}", e)))
+  };
+
+  match (request.method.as_str(), request.path.trim_end_matches('/')) {
+    ("GET", "/tree") => Ok(ApiRequest::GetTree),
+    ("GET", "/selection") => Ok(ApiRequest::GetSelection),
+    ("PUT", "/selection") => Ok(ApiRequest::SetSelection(paths()?)),
+    ("POST", "/selection/add") => Ok(ApiRequest::AddToSelection(paths()?)),
+    ("POST", "/selection/remove") => Ok(ApiRequest::RemoveFromSelection(paths()?)),
+    ("GET", "/tokens") => Ok(ApiRequest::GetTokens),
+    ("POST", "/run") => Ok(ApiRequest::Run),
+    ("GET", "/output") => Ok(ApiRequest::GetOutput),
+    (_, "/tree" | "/selection" | "/selection/add" | "/selection/remove" | "/tokens" | "/run" | "/output") => Err(ApiResponse::error(405, "Method not allowed")),
+    _ => Err(ApiResponse::error(404, "Not found")),
+  }
+}
+
+/// Returns the reason phrase for the status codes the API uses.
+fn reason_phrase(status: u16) -> &'static str {
+  match status {
+    200 => "OK",
+    400 => "Bad Request",
+    401 => "Unauthorized",
+    403 => "Forbidden",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    409 => "Conflict",
+    415 => "Unsupported Media Type",
+    503 => "Service Unavailable",
+    _ => "Error",
+  }
+}
+
+// test for api request parsing
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_api_address() {
+    assert_eq!(ApiAddress::parse("localhost:7777").unwrap(), ApiAddress::Tcp("127.0.0.1:7777".parse().unwrap()));
+    assert!(ApiAddress::parse("[::1]:7777").is_ok());
+    assert!(ApiAddress::parse("0.0.0.0:7777").is_err());
+    assert!(ApiAddress::parse("192.168.1.5:7777").is_err());
+    assert!(ApiAddress::parse("unix:").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_api_requests() {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<ApiEvent>();
+    // answers like the app would, echoing the request
+    tokio::spawn(async move {
+      while let Some(event) = receiver.recv().await {
+        let _ = event.reply.send(ApiResponse::ok(json!({ "request": format!("{:?}", event.request) })));
+      }
+    });
+
+    let send = |raw: &'static str| {
+      let sender = sender.clone();
+      async move {
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move { serve_connection(server, sender, None).await });
+        let (mut read_half, mut write_half) = tokio::io::split(client);
+        write_half.write_all(raw.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        read_half.read_to_string(&mut response).await.unwrap();
+        response
+      }
+    };
+
+    let response = send("POST /selection/add HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 23\r\n\r\n{\"paths\": [\"src/a.rs\"]}").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(r#"{"request":"AddToSelection([\"src/a.rs\"])"}"#));
+
+    assert!(send("GET /tree?depth=1 HTTP/1.1\r\n\r\n").await.contains("GetTree"));
+    assert!(send("POST /run HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 415"));
+    assert!(send("POST /run HTTP/1.1\r\nContent-Type: application/json\r\nOrigin: https://example.com\r\n\r\n").await.starts_with("HTTP/1.1 403"));
+    assert!(send("DELETE /tree HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 405"));
+    assert!(send("GET /nope HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 404"));
+    assert!(send("PUT /selection HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}").await.starts_with("HTTP/1.1 400"));
+  }
+
+  #[test]
+  fn test_tcp_access() {
+    let access = TcpAccess::new(7777);
+    assert_eq!(access.token.len(), 32);
+    assert_ne!(access.token, TcpAccess::new(7777).token);
+    let request = |host: &str, authorization: &str| HttpRequest {
+      method: "GET".to_string(),
+      path: "/output".to_string(),
+      headers: vec![("host".to_string(), host.to_string()), ("authorization".to_string(), authorization.to_string())],
+      body: Vec::new(),
+    };
+    let bearer = format!("Bearer {}", access.token);
+
+    assert!(check_access(&request("localhost:7777", &bearer), Some(&access)).is_ok());
+    assert!(check_access(&request("[::1]:7777", &bearer), Some(&access)).is_ok());
+    // DNS rebinding keeps the attacker's host name
+    assert_eq!(check_access(&request("evil.example:7777", &bearer), Some(&access)).unwrap_err().status, 403);
+    assert_eq!(check_access(&request("127.0.0.1:8888", &bearer), Some(&access)).unwrap_err().status, 403);
+    assert_eq!(check_access(&request("127.0.0.1:7777", "Bearer nope"), Some(&access)).unwrap_err().status, 401);
+    assert_eq!(check_access(&HttpRequest::default(), Some(&access)).unwrap_err().status, 403);
+    // unix socket clients are checked by the socket permissions
+    assert!(check_access(&HttpRequest::default(), None).is_ok());
+  }
+}
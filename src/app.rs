@@ -1,10 +1,18 @@
+use crate::api::{ApiAddress, ApiEvent, ApiRequest, ApiResponse, ApiServer};
+use crate::command_palette::CommandPalette;
 use crate::config::SifConfig;
 use crate::file_utils;
-use crate::repomix_integration::Repomix;
+use crate::keymap::{Action, Keymap};
+use crate::notifications::{NotificationLog, NotificationSource};
+use crate::process_runner::RunMonitor;
+use crate::repomix_integration::{Repomix, RepomixConfig};
+use crate::selection_history::SelectionHistory;
+use crate::selection_patterns::PatternPrompt;
+use crate::theme::{Theme, ThemeName};
 use crate::token_counter::TokenCounter;
-use crate::types::{AppState, Backend, BackendRequest, BackendResult, RepomixOptions};
+use crate::types::{AppState, Backend, BackendEvent, BackendRequest, BackendResult, DownloadStatus, FileNode, ScanEvent, SortMode, StatusLevel};
 use crate::ui::{handle_input, render_app, update_ui_state, UIState};
-use crate::yek_integration::Yek;
+use crate::yek_integration::YekProvisioner;
 use anyhow::{Context, Result};
 use crossterm::{
   event::{self, Event, KeyCode, MouseEvent},
@@ -17,7 +25,7 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio_util::sync::CancellationToken;
 
 /// Main app struct that manages the entire siff app.
@@ -31,10 +39,6 @@ pub struct App {
   pub should_quit: bool,
   /// Last time performed an update (for periodic tasks)
   pub last_update: Instant,
-  /// Token counter for calculating file sizes
-  pub status_message: String,
-  /// When the status message was last updated
-  pub status_updated_at: Instant,
   /// Whether backend is currently running
   pub is_processing: bool,
   /// Current token count for selected files
@@ -43,14 +47,22 @@ pub struct App {
   pub config: SifConfig,
   /// Repomix manager for isolated repomix execution (lazy)
   pub repomix: Option<Repomix>,
+  /// Finds or installs the yek binary in the background (lazy)
+  pub yek: Option<YekProvisioner>,
+  /// Dependency check for switching to repomix, runs in the background
+  repomix_check: Option<oneshot::Receiver<Result<()>>>,
+  /// Starts the repomix dependency check (replaced in tests)
+  start_repomix_check: fn(RepomixConfig) -> oneshot::Receiver<Result<()>>,
   /// Sender for token calculation requests
   token_request_sender: mpsc::UnboundedSender<PathBuf>,
   /// Receiver for token calculation results
   token_result_receiver: mpsc::UnboundedReceiver<(PathBuf, usize)>,
   /// Sender for backend execution requests
   backend_request_sender: mpsc::UnboundedSender<BackendRequest>,
-  /// Receiver for backend execution results
-  backend_result_receiver: mpsc::UnboundedReceiver<BackendResult>,
+  /// Receiver for backend progress and results
+  backend_event_receiver: mpsc::UnboundedReceiver<BackendEvent>,
+  /// Receiver for nodes streamed by the background directory scan
+  scan_event_receiver: mpsc::UnboundedReceiver<ScanEvent>,
   /// Counter for generating unique request IDs
   next_request_id: u64,
   /// Current active request ID (for cancellation)
@@ -67,58 +79,101 @@ pub struct App {
   is_bulk_token_calculation: bool,
   /// Suppress status messages during nav
   suppress_status_messages: bool,
+  /// Files queued for token calculation outside the selection (pattern previews, token sort)
+  extra_token_requests: std::collections::HashSet<PathBuf>,
+  /// Receiver for requests from the local API (only when it's enabled)
+  api_event_receiver: Option<mpsc::UnboundedReceiver<ApiEvent>>,
+  /// Local API server, stops listening when dropped
+  _api_server: Option<ApiServer>,
+  /// Last finished run, served by the API
+  last_result: Option<BackendResult>,
+  /// Paths from --select-from, selected once the scan finishes
+  initial_selection: Vec<String>,
+  /// Dirs that were expanded before a rescan, expanded again as they stream in
+  rescan_expanded: std::collections::HashSet<PathBuf>,
+  /// Files that were selected before a rescan, selected again once it finishes
+  rescan_selection: Vec<PathBuf>,
 }
 
 impl App {
   /// Creates a new app instance.
-  /// Scans the given directory and initializes all state.
-  pub async fn new(root_path: &Path, backend: Backend) -> Result<Self> {
+  /// Scans the given directories and initializes all state, and starts the local API if an address is given.
+  pub async fn new(roots: &[PathBuf], backend: Backend, api_address: Option<ApiAddress>) -> Result<Self> {
     // load user config
     let config = SifConfig::load().context("Failed to load configuration")?;
+    Self::with_config(roots, backend, api_address, config).await
+  }
+
+  /// Creates a new app instance with an already loaded configuration.
+  pub async fn with_config(roots: &[PathBuf], backend: Backend, api_address: Option<ApiAddress>, config: SifConfig) -> Result<Self> {
+    // several roots share one tree, each a top-level node
+    let roots = file_utils::SessionRoots::new(roots)?;
+    let root_path = roots.root_path();
 
     // if a specific backend was requested via command line, use that
     // otherwise use the saved default backend
     let effective_backend = backend;
 
     // create repomix options from saved config
-    let repomix_options = RepomixOptions {
-      backend: effective_backend.clone(),
-      compress: config.compress,
-      remove_comments: config.remove_comments,
-      file_tree: config.include_file_tree,
-      output_format: config.output_format.clone(),
-      output_file: None, // output file is not persisted (for file tree)
-    };
+    let repomix_options = config.repomix_options(effective_backend.clone());
+
+    // start with just the root node, the rest of the tree streams in from the background scan
+    let mut file_tree = HashMap::new();
+    file_tree.insert(root_path.clone(), FileNode::new(root_path.clone(), true, 0));
 
-    // scan the directory to build the file tree (shows all files by default)
-    let file_tree = file_utils::scan_directory(root_path).context("Failed to scan directory")?;
+    // build keymap from config (unknown keys and conflicts are reported at startup)
+    let keymap = Keymap::from_config(&config.keymap).context("Invalid keymap in configuration")?;
 
-    // create initial visible files list (just the root directory)
-    let visible_paths = file_utils::flatten_visible_tree(&file_tree, root_path);
+    // create initial visible files list (empty until the scan delivers nodes)
+    let visible_paths = Vec::new();
 
     // create initial app state
     let state = AppState {
       file_tree,
-      root_path: root_path.to_path_buf(),
+      root_path,
+      roots: roots.clone(),
       visible_paths,
       selected_index: 0,
       repomix_options,
+      yek_options: config.yek_options.clone(),
+      custom_backends: config.custom_backends.clone(),
+      secret_files: HashMap::new(),
       individual_token_counts: HashMap::new(),
-      status_message: String::new(),
+      notifications: NotificationLog::default(),
+      show_log: false,
+      log_scroll: 0,
       is_processing: false,
       token_count: 0,
       focus: crate::types::Focus::FileTree,
+      selection_history: SelectionHistory::default(),
+      pattern_prompt: None,
+      file_token_cache: HashMap::new(),
+      sort_mode: config.sort_mode,
+      is_scanning: true,
+      show_hidden: config.scan.show_hidden,
+      keymap,
+      theme: Theme::from_config(config.theme),
+      show_help: false,
+      help_scroll: 0,
+      command_palette: None,
     };
 
     // initialize repomix only if using repomix backend
     let repomix = if matches!(effective_backend, Backend::Repomix) {
-      let mut r = Repomix::new()?;
+      let mut r = Repomix::new(&config.repomix)?;
       r.start_background_download().await;
       Some(r)
     } else {
       None
     };
 
+    // same for yek, which is provisioned without blocking the UI
+    let yek = matches!(effective_backend, Backend::Yek).then(|| {
+      let mut provisioner = YekProvisioner::new(&config.yek);
+      provisioner.start();
+      provisioner
+    });
+
     // setups for file tree
     // create channels for background token calculation
     let (token_request_sender, token_request_receiver) = mpsc::unbounded_channel::<PathBuf>();
@@ -126,7 +181,14 @@ impl App {
 
     // create channels for non-blocking backend execution
     let (backend_request_sender, backend_request_receiver) = mpsc::unbounded_channel::<BackendRequest>();
-    let (backend_result_sender, backend_result_receiver) = mpsc::unbounded_channel::<BackendResult>();
+    let (backend_event_sender, backend_event_receiver) = mpsc::unbounded_channel::<BackendEvent>();
+
+    // scan the directory in the background so the UI shows up immediately
+    let (scan_event_sender, scan_event_receiver) = mpsc::unbounded_channel::<ScanEvent>();
+    let scan_options = config.scan.clone();
+    tokio::task::spawn_blocking(move || {
+      file_utils::scan_roots_streaming(&roots, &scan_options, scan_event_sender);
+    });
 
     // spawn background token calculation task
     let token_counter_for_task = TokenCounter::new()?;
@@ -135,26 +197,48 @@ impl App {
     });
 
     // spawn background backend execution task with lazy init
-    let current_backend_for_task = effective_backend.clone();
+    let repomix_config_for_task = config.repomix.clone();
     tokio::spawn(async move {
-      Self::backend_execution_task_lazy(current_backend_for_task, backend_request_receiver, backend_result_sender).await;
+      Self::backend_execution_task_lazy(repomix_config_for_task, backend_request_receiver, backend_event_sender).await;
     });
 
-    Ok(Self {
+    // editor plugins talk to the running app through the local API, requests arrive in the main loop
+    let mut notifications = Vec::new();
+    let (mut api_server, mut api_event_receiver) = (None, None);
+    if let Some(address) = api_address {
+      let (api_event_sender, receiver) = mpsc::unbounded_channel::<ApiEvent>();
+      match crate::api::start(address, api_event_sender).await {
+        Ok(server) => {
+          let message = match &server.token_file {
+            Some(path) => format!("API listening on {} (token in {})", server.address, path.display()),
+            None => format!("API listening on {}", server.address),
+          };
+          notifications.push((StatusLevel::Info, message));
+          api_server = Some(server);
+          api_event_receiver = Some(receiver);
+        }
+        // another siff may have the port, the app works without the API
+        Err(e) => notifications.push((StatusLevel::Error, format!("Error: API not started, {:#}", e))),
+      }
+    }
+
+    let mut app = Self {
       state,
       ui_state: UIState::default(),
       should_quit: false,
       last_update: Instant::now(),
-      status_message: String::new(),
-      status_updated_at: Instant::now(),
       is_processing: false,
       token_count: 0,
       config,
       repomix,
+      yek,
+      repomix_check: None,
+      start_repomix_check: Self::check_repomix_dependencies,
       token_request_sender,
       token_result_receiver,
       backend_request_sender,
-      backend_result_receiver,
+      backend_event_receiver,
+      scan_event_receiver,
       next_request_id: 0,
       current_request_id: None,
       cancellation_token: CancellationToken::new(),
@@ -163,7 +247,18 @@ impl App {
       pending_token_calculations: std::collections::HashSet::new(),
       is_bulk_token_calculation: false,
       suppress_status_messages: false,
-    })
+      extra_token_requests: std::collections::HashSet::new(),
+      api_event_receiver,
+      _api_server: api_server,
+      last_result: None,
+      initial_selection: Vec::new(),
+      rescan_expanded: std::collections::HashSet::new(),
+      rescan_selection: Vec::new(),
+    };
+    for (level, message) in notifications {
+      app.notify(level, NotificationSource::Api, message);
+    }
+    Ok(app)
   }
 
   /// Updates the token count for currently selected files whenever file selection changes.
@@ -186,7 +281,7 @@ impl App {
     for path in self.state.individual_token_counts.keys() {
       if let Some(node) = self.state.file_tree.get(path) {
         // remove if file is not selected and not a directory with selected descendants
-        if !(node.is_selected || node.is_directory && self.has_selected_descendants(path)) {
+        if !node.has_selection() {
           paths_to_remove.push(path.clone());
         }
       } else {
@@ -201,11 +296,11 @@ impl App {
       self.pending_token_calculations.remove(path);
     }
 
-    // calculate total from already cached individual counts
+    // calculate total from already cached individual counts, files reached through several symlinks count once
     let mut total_from_cache = 0;
     let mut uncached_files = Vec::new();
 
-    for file_path in &selected_files {
+    for file_path in &file_utils::unique_files(&self.state.file_tree, selected_files.clone()) {
       if let Some(cached_count_opt) = self.state.individual_token_counts.get(file_path) {
         if let Some(cached_count) = cached_count_opt {
           total_from_cache += cached_count;
@@ -235,16 +330,6 @@ impl App {
     Ok(())
   }
 
-  /// Checks if a directory has any selected descendants
-  fn has_selected_descendants(&self, dir_path: &std::path::Path) -> bool {
-    for (path, node) in &self.state.file_tree {
-      if node.is_selected && path.starts_with(dir_path) && path != dir_path {
-        return true;
-      }
-    }
-    false
-  }
-
   /// Debouncing that only updates if enough time has passed.
   /// Prevents UI slow down from rapid selection changes.
   pub fn update_token_count_debounced(&mut self) -> Result<()> {
@@ -261,9 +346,6 @@ impl App {
 
   /// Recalculates directory token counts from scratch based on currently selected files.
   fn recalculate_directory_token_counts(&mut self) {
-    // build map of directories with selected descendants
-    let dir_descendants_map = self.build_directories_with_descendants_map();
-
     // clear all directory token counts first
     let directory_paths: Vec<PathBuf> = self.state.file_tree.iter().filter(|(_, node)| node.is_directory).map(|(path, _)| path.clone()).collect();
 
@@ -271,7 +353,7 @@ impl App {
     for dir_path in &directory_paths {
       // include directories that are selected or have selected descendants
       if let Some(dir_node) = self.state.file_tree.get(dir_path) {
-        if dir_node.is_selected || *dir_descendants_map.get(dir_path).unwrap_or(&false) {
+        if dir_node.has_selection() {
           self.state.individual_token_counts.insert(dir_path.clone(), Some(0));
         }
       }
@@ -284,7 +366,7 @@ impl App {
         if let Some(file_node) = self.state.file_tree.get(file_path) {
           if file_node.is_selected && !file_node.is_directory {
             // find if file is selected, add its tokens to all parent directories that should show counts
-            self.add_file_tokens_to_directories_with_selections(file_path, *token_count, &dir_descendants_map);
+            self.add_file_tokens_to_directories_with_selections(file_path, *token_count);
           }
         }
       }
@@ -293,70 +375,27 @@ impl App {
 
   /// Adds a file's token count to parent directories that should show token counts.
   /// Includes both selected directories and directories with selected descendants.
-  fn add_file_tokens_to_directories_with_selections(&mut self, file_path: &Path, file_tokens: usize, dir_descendants_map: &HashMap<PathBuf, bool>) {
-    let mut current_path = file_path.parent();
-    while let Some(parent_path) = current_path {
-      if let Some(parent_node) = self.state.file_tree.get(parent_path) {
-        if parent_node.is_directory {
-          // add tokens if directory is selected or has selected descendants
-          if parent_node.is_selected || *dir_descendants_map.get(parent_path).unwrap_or(&false) {
-            let current_dir_tokens = self.state.individual_token_counts.get(parent_path).and_then(|opt| *opt).unwrap_or(0);
-            self.state.individual_token_counts.insert(parent_path.to_path_buf(), Some(current_dir_tokens + file_tokens));
-          }
-        }
+  /// Walks the tree's parents, so the virtual root of several roots gets a total too.
+  fn add_file_tokens_to_directories_with_selections(&mut self, file_path: &Path, file_tokens: usize) {
+    for parent_path in file_utils::ancestor_dirs(&self.state.file_tree, &[file_path.to_path_buf()]) {
+      // add tokens if directory is selected or has selected descendants
+      if self.state.file_tree.get(&parent_path).is_some_and(|parent_node| parent_node.is_directory && parent_node.has_selection()) {
+        let current_dir_tokens = self.state.individual_token_counts.get(&parent_path).and_then(|opt| *opt).unwrap_or(0);
+        self.state.individual_token_counts.insert(parent_path, Some(current_dir_tokens + file_tokens));
       }
-      current_path = parent_path.parent();
     }
   }
 
-  /// Builds a map of directories that have selected descendants.
-  fn build_directories_with_descendants_map(&self) -> HashMap<PathBuf, bool> {
-    let mut dir_map = HashMap::new();
-
-    // initialize all directories as false
-    for (path, node) in &self.state.file_tree {
-      if node.is_directory {
-        dir_map.insert(path.clone(), false);
-      }
-    }
-
-    // mark directories that have selected descendants
-    for (path, node) in &self.state.file_tree {
-      if node.is_selected {
-        // mark all parent directories as having selected descendants
-        let mut current_path = path.parent();
-        while let Some(parent_path) = current_path {
-          if let Some(has_descendants) = dir_map.get_mut(parent_path) {
-            *has_descendants = true;
-          }
-          current_path = parent_path.parent();
-        }
-      }
-    }
-
-    dir_map
-  }
-
   /// Queues individual token calculations for background processing with batching.
   fn queue_individual_token_calculations(&mut self, files: Vec<PathBuf>) {
-    // build map of directories with selected descendants
-    let dir_descendants_map = self.build_directories_with_descendants_map();
-
     // clear old individual counts for files that are no longer selected
     // but keep directories that have selected descendants
     let mut new_token_counts = HashMap::new();
 
     for (path, count) in &self.state.individual_token_counts {
       if let Some(node) = self.state.file_tree.get(path) {
-        let should_keep = if node.is_directory {
-          // keep directories that are selected or have selected descendants
-          node.is_selected || *dir_descendants_map.get(path).unwrap_or(&false)
-        } else {
-          // keep files that are selected
-          node.is_selected
-        };
-
-        if should_keep {
+        // keep files that are selected and directories with selected descendants
+        if node.has_selection() {
           new_token_counts.insert(path.clone(), *count);
         }
       }
@@ -370,7 +409,7 @@ impl App {
     let files_to_process = if files.len() > MAX_FILES_FOR_TOKEN_CALC {
       // only show this message during bulk operations, not regular nav
       if self.is_bulk_token_calculation {
-        self.set_status_message(format!("Processing {} files (showing first 1000)...", files.len()));
+        self.notify(StatusLevel::Progress, NotificationSource::Tokens, format!("Processing {} files (showing first 1000)...", files.len()));
       }
       files.into_iter().take(MAX_FILES_FOR_TOKEN_CALC).collect()
     } else {
@@ -438,17 +477,8 @@ impl App {
 
   /// Queues directory token calculations for directories that should show counts.
   fn queue_directory_calculations(&mut self) {
-    // build map of directories with selected descendants
-    let dir_descendants_map = self.build_directories_with_descendants_map();
-
     // include both selected directories and directories with selected descendants
-    let relevant_dirs: Vec<PathBuf> = self
-      .state
-      .file_tree
-      .iter()
-      .filter(|(path, node)| node.is_directory && (node.is_selected || *dir_descendants_map.get(*path).unwrap_or(&false)))
-      .map(|(path, _)| path.clone())
-      .collect();
+    let relevant_dirs: Vec<PathBuf> = self.state.file_tree.iter().filter(|(_, node)| node.is_directory && node.has_selection()).map(|(path, _)| path.clone()).collect();
 
     for dir_path in relevant_dirs {
       self.state.individual_token_counts.entry(dir_path).or_insert(Some(0));
@@ -461,6 +491,16 @@ impl App {
 
     // receive all available results
     while let Ok((file_path, token_count)) = self.token_result_receiver.try_recv() {
+      // remember the count for previews, even if the file is not selected
+      self.state.file_token_cache.insert(file_path.clone(), token_count);
+      self.extra_token_requests.remove(&file_path);
+
+      // pattern preview results for unselected files don't affect the totals
+      if !self.pending_token_calculations.contains(&file_path) && !self.state.individual_token_counts.contains_key(&file_path) {
+        processed_any = true;
+        continue;
+      }
+
       // update individual token count
       self.state.individual_token_counts.insert(file_path.clone(), Some(token_count));
       // remove from pending calculations
@@ -468,6 +508,11 @@ impl App {
       processed_any = true;
     }
 
+    // token sorting is refreshed once all queued counts are in
+    if processed_any && self.state.sort_mode == SortMode::Tokens && self.pending_token_calculations.is_empty() && self.extra_token_requests.is_empty() {
+      self.resort_tree();
+    }
+
     // only recalculate totals if we processed results and no calculations are pending
     if processed_any {
       if self.pending_token_calculations.is_empty() {
@@ -478,7 +523,7 @@ impl App {
         if self.is_bulk_token_calculation {
           self.is_bulk_token_calculation = false;
           let selected_count = file_utils::get_selected_files(&self.state.file_tree).len();
-          self.set_status_message(format!("✓ Calculated tokens for {} files", selected_count));
+          self.notify(StatusLevel::Success, NotificationSource::Tokens, format!("✓ Calculated tokens for {} files", selected_count));
         }
       } else {
         // still have pending calculations, show progress
@@ -486,7 +531,7 @@ impl App {
         let total = completed + self.pending_token_calculations.len();
 
         if self.is_bulk_token_calculation {
-          self.set_status_message(format!("Calculating tokens... {}/{}", completed, total));
+          self.notify(StatusLevel::Progress, NotificationSource::Tokens, format!("Calculating tokens... {}/{}", completed, total));
         }
 
         // recalculate partial totals for feedback
@@ -500,7 +545,7 @@ impl App {
   /// Recalculates totals when all calculations are complete.
   fn recalculate_final_token_totals(&mut self) {
     // recalculate total token count
-    let selected_files = file_utils::get_selected_files(&self.state.file_tree);
+    let selected_files = file_utils::unique_files(&self.state.file_tree, file_utils::get_selected_files(&self.state.file_tree));
     let mut total_tokens = 0;
 
     for file_path in &selected_files {
@@ -518,7 +563,7 @@ impl App {
   /// Recalculates partial token totals for feedback during calculations.
   fn recalculate_partial_token_totals(&mut self) {
     // only count files that have completed calculations
-    let selected_files = file_utils::get_selected_files(&self.state.file_tree);
+    let selected_files = file_utils::unique_files(&self.state.file_tree, file_utils::get_selected_files(&self.state.file_tree));
     let mut total_tokens = 0;
 
     for file_path in &selected_files {
@@ -533,12 +578,24 @@ impl App {
     self.recalculate_directory_token_counts();
   }
 
-  /// Processes backend execution results from the background task (non-blocking).
+  /// Processes backend progress and results from the background task (non-blocking).
   fn process_backend_results(&mut self) -> bool {
     let mut processed_any = false;
 
-    // receive all available results
-    while let Ok(result) = self.backend_result_receiver.try_recv() {
+    // receive all available events
+    while let Ok(event) = self.backend_event_receiver.try_recv() {
+      let result = match event {
+        BackendEvent::Progress(progress) => {
+          // progress of cancelled requests is ignored
+          if self.current_request_id == Some(progress.request_id) {
+            self.notify(StatusLevel::Progress, NotificationSource::from(&progress.backend), progress.summary());
+            processed_any = true;
+          }
+          continue;
+        }
+        BackendEvent::Finished(result) => result,
+      };
+
       // check if result is from the current request (ignore cancelled requests)
       if let Some(current_id) = self.current_request_id {
         if result.request_id != current_id {
@@ -553,24 +610,54 @@ impl App {
       // update processing state
       self.is_processing = false;
       self.current_request_id = None;
+      self.last_result = Some(result.clone());
+
+      // handle the result, warnings and full output are kept in the message log
+      let source = NotificationSource::from(&result.backend);
+      let mut details: Vec<String> = result.warnings.iter().map(|warning| format!("Warning: {}", warning)).collect();
+      details.push(result.stderr.clone());
+
+      // flag files with secrets in the tree until the next run
+      self.state.secret_files = result.flagged.iter().map(|flagged| (flagged.path.clone(), flagged.summary.clone())).collect();
+      for flagged in &result.flagged {
+        let relative_path = self.state.roots.relative(&flagged.path);
+        details.insert(0, format!("Secrets in {}: {}", relative_path.display(), flagged.summary));
+      }
+      for redacted in &result.redacted {
+        let relative_path = self.state.roots.relative(&redacted.path);
+        details.push(format!("Redacted in {}: {}", relative_path.display(), redacted.summary()));
+      }
 
-      // handle the result
       if result.success {
         // successful execution
-        let message = if result.message.len() > 100 { format!("{}...", &result.message[..100]) } else { result.message.to_string() };
-        self.set_status_message(message);
+        let mut message = result.message.clone();
+        if let Some(output_file) = &result.output_file {
+          message = format!("{} | Output: {}", message, output_file.display());
+        }
 
-        // if an output file was created, print it
-        if let Some(output_file) = result.output_file {
-          self.set_status_message(format!("{} | Output: {}", result.message, output_file.display()));
+        if !result.flagged.is_empty() {
+          message.push_str(&format!(" • secrets in {} files ({})", result.flagged.len(), self.config.secrets.policy.display_name()));
         }
-      } else {
-        // failed execution
-        if let Some(error) = result.error {
-          self.set_status_message(error);
+        if !result.redacted.is_empty() {
+          let substitutions: usize = result.redacted.iter().map(|redacted| redacted.total()).sum();
+          message.push_str(&format!(" • {} redactions in {} files", substitutions, result.redacted.len()));
+        }
+
+        let level = if result.warnings.is_empty() && result.flagged.is_empty() {
+          StatusLevel::Success
         } else {
-          self.set_status_message("Error: Backend execution failed".to_string());
+          message.push_str(&format!(" • {} warnings", result.warnings.len()));
+          StatusLevel::Warning
+        };
+        self.notify_with_details(level, source, message, details.join("\n"));
+      } else {
+        // failed execution, the toast only shows the first line
+        let error = result.error.unwrap_or_else(|| "Error: Backend execution failed".to_string());
+        let summary = error.lines().next().unwrap_or_default().to_string();
+        if summary != error {
+          details.insert(0, error);
         }
+        self.notify_with_details(StatusLevel::Error, source, summary, details.join("\n"));
       }
 
       processed_any = true;
@@ -596,8 +683,9 @@ impl App {
       // update UI state to match app state
       update_ui_state(&self.state, &mut self.ui_state);
 
-      // handle events with timeout for periodic updates
-      if crossterm::event::poll(Duration::from_millis(100))? {
+      // handle events with timeout for periodic updates (shorter while the scan streams in)
+      let poll_timeout = if self.state.is_scanning { Duration::from_millis(16) } else { Duration::from_millis(100) };
+      if crossterm::event::poll(poll_timeout)? {
         match event::read()? {
           Event::Key(key) => {
             let should_continue = self.handle_key_event(key).await?;
@@ -618,16 +706,30 @@ impl App {
       // perform periodic updates
       self.periodic_update();
 
-      // update background repomix download if needed
-      if matches!(self.state.repomix_options.backend, Backend::Repomix) {
-        if let Ok(status_changed) = self.update_repomix_download().await {
-          if status_changed {
-            // status changed, update UI
-            continue;
-          }
+      // finish switching to repomix once its dependencies are checked
+      if self.update_repomix_check().await {
+        continue;
+      }
+
+      // update background repomix download (keeps going after switching backend)
+      if let Ok(status_changed) = self.update_repomix_download().await {
+        if status_changed {
+          // status changed, update UI
+          continue;
         }
       }
 
+      // pick up background yek provisioning progress
+      if self.update_yek_download() {
+        continue;
+      }
+
+      // merge nodes from the background scan
+      if self.process_scan_events() {
+        // if merged nodes, continue to update UI
+        continue;
+      }
+
       // process token calculation results
       if self.process_token_results() {
         // if processed tokens, continue to update UI
@@ -640,6 +742,11 @@ impl App {
         continue;
       }
 
+      // answer requests from the local API
+      if self.process_api_requests().await? {
+        continue;
+      }
+
       // check if should quit
       if self.should_quit {
         break;
@@ -654,40 +761,171 @@ impl App {
 
   /// Handles keyboard input events.
   async fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<bool> {
-    // handle global quit commands
-    match key.code {
-      KeyCode::Char('c') if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
+    // pattern prompt captures all input while it's open
+    if self.state.pattern_prompt.is_some() {
+      self.handle_pattern_prompt_key(key)?;
+      return Ok(true);
+    }
+
+    // command palette captures all input while it's open
+    if self.state.command_palette.is_some() {
+      return self.handle_command_palette_key(key).await;
+    }
+
+    // message log captures all input while it's open
+    if self.state.show_log {
+      self.handle_log_key(key);
+      return Ok(true);
+    }
+
+    // scroll keys move the help overlay, any other key closes it
+    if self.state.show_help {
+      self.handle_help_key(key);
+      return Ok(true);
+    }
+
+    // resolve the key through the active keymap
+    let Some(action) = self.state.keymap.action_for(&key) else {
+      return Ok(true);
+    };
+
+    self.execute_action(action).await
+  }
+
+  /// Handles key input while the message log is open (scrolling and closing).
+  fn handle_log_key(&mut self, key: crossterm::event::KeyEvent) {
+    let max_scroll = self.state.notifications.line_count().saturating_sub(1);
+    match (key.code, self.state.keymap.action_for(&key)) {
+      (KeyCode::Esc, _) | (_, Some(Action::ShowLog | Action::Quit)) => self.state.show_log = false,
+      (KeyCode::PageUp, _) => self.state.log_scroll = self.state.log_scroll.saturating_sub(10),
+      (KeyCode::PageDown, _) => self.state.log_scroll = (self.state.log_scroll + 10).min(max_scroll),
+      (KeyCode::Home, _) => self.state.log_scroll = 0,
+      (KeyCode::End, _) => self.state.log_scroll = max_scroll,
+      (_, Some(Action::MoveUp)) => self.state.log_scroll = self.state.log_scroll.saturating_sub(1),
+      (_, Some(Action::MoveDown)) => self.state.log_scroll = (self.state.log_scroll + 1).min(max_scroll),
+      _ => {}
+    }
+  }
+
+  /// Handles key input while the help overlay is open (scrolling, any other key closes it).
+  fn handle_help_key(&mut self, key: crossterm::event::KeyEvent) {
+    // don't scroll past the last line
+    let rows = crossterm::terminal::size().map(|(_, rows)| rows).unwrap_or(u16::MAX);
+    let max_scroll = crate::ui::overlay::help_max_scroll(rows);
+    match (key.code, self.state.keymap.action_for(&key)) {
+      (KeyCode::PageUp, _) => self.state.help_scroll = self.state.help_scroll.saturating_sub(10),
+      (KeyCode::PageDown, _) => self.state.help_scroll = (self.state.help_scroll + 10).min(max_scroll),
+      (KeyCode::Home, _) => self.state.help_scroll = 0,
+      (KeyCode::End, _) => self.state.help_scroll = max_scroll,
+      (_, Some(Action::MoveUp)) => self.state.help_scroll = self.state.help_scroll.saturating_sub(1),
+      (_, Some(Action::MoveDown)) => self.state.help_scroll = (self.state.help_scroll + 1).min(max_scroll),
+      _ => self.state.show_help = false,
+    }
+  }
+
+  /// Executes a keymap action (from a key press or the command palette).
+  /// Returns false if the app should quit.
+  async fn execute_action(&mut self, action: Action) -> Result<bool> {
+    // backend specific actions do nothing for other backends
+    if !action.is_available(&self.state.repomix_options.backend) {
+      return Ok(true);
+    }
+
+    // handle global actions
+    match action {
+      Action::Quit => {
+        // stop a running backend instead of leaving it behind
+        self.cancellation_token.cancel();
         return Ok(false);
       }
-      KeyCode::Char('q') | KeyCode::Esc => {
-        return Ok(false);
+      Action::ShowHelp => {
+        self.state.show_help = true;
+        self.state.help_scroll = 0;
+        return Ok(true);
+      }
+      Action::ShowLog => {
+        // newest messages are at the top
+        self.state.show_log = true;
+        self.state.log_scroll = 0;
+        self.state.notifications.dismiss_toast();
+        return Ok(true);
+      }
+      Action::CommandPalette => {
+        self.state.command_palette = Some(CommandPalette::new(&self.state.repomix_options.backend));
+        return Ok(true);
+      }
+      Action::CycleTheme => {
+        self.cycle_theme();
+        return Ok(true);
+      }
+      Action::ToggleHidden => {
+        self.toggle_hidden();
+        return Ok(true);
       }
-      KeyCode::Char('r') => {
+      Action::Redo => {
+        // redo last undone selection change
+        self.apply_selection_history(true)?;
+        return Ok(true);
+      }
+      Action::Run => {
         self.run_backend().await?;
         return Ok(true);
       }
+      Action::CancelRun => {
+        self.cancel_backend();
+        return Ok(true);
+      }
+      Action::CycleSort => {
+        // cycle sort mode
+        self.cycle_sort_mode();
+        return Ok(true);
+      }
+      Action::SwitchBackend => {
+        // switch backend for this session
+        self.switch_backend().await;
+        return Ok(true);
+      }
+      Action::SaveDefaultBackend => {
+        // make current backend the default
+        self.save_default_backend();
+        return Ok(true);
+      }
+      Action::SelectByPattern => {
+        // open pattern selection prompt
+        self.state.pattern_prompt = Some(PatternPrompt::default());
+        return Ok(true);
+      }
+      Action::Undo => {
+        // undo last selection change
+        self.apply_selection_history(false)?;
+        return Ok(true);
+      }
       // repomix configuration shortcuts
-      KeyCode::Char('c') if self.state.repomix_options.backend == crate::types::Backend::Repomix => {
+      Action::ToggleCompress => {
         // toggle compress
         self.state.repomix_options.compress = !self.state.repomix_options.compress;
         if let Err(e) = self.save_repomix_options() {
-          self.set_status_message(format!("Error: config save error {}", e));
+          self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
         } else {
-          self.set_status_message(format!("Compress: {}", if self.state.repomix_options.compress { "enabled" } else { "disabled" }));
+          self.notify(StatusLevel::Info, NotificationSource::App, format!("Compress: {}", if self.state.repomix_options.compress { "enabled" } else { "disabled" }));
         }
         return Ok(true);
       }
-      KeyCode::Char('m') if self.state.repomix_options.backend == crate::types::Backend::Repomix => {
+      Action::ToggleRemoveComments => {
         // toggle remove comments
         self.state.repomix_options.remove_comments = !self.state.repomix_options.remove_comments;
         if let Err(e) = self.save_repomix_options() {
-          self.set_status_message(format!("Error: config save error {}", e));
+          self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
         } else {
-          self.set_status_message(format!("Remove comments: {}", if self.state.repomix_options.remove_comments { "enabled" } else { "disabled" }));
+          self.notify(
+            StatusLevel::Info,
+            NotificationSource::App,
+            format!("Remove comments: {}", if self.state.repomix_options.remove_comments { "enabled" } else { "disabled" }),
+          );
         }
         return Ok(true);
       }
-      KeyCode::Char('f') if self.state.repomix_options.backend == crate::types::Backend::Repomix => {
+      Action::CycleOutputFormat => {
         // cycle output format (XML, Markdown, Plain Text)
         use crate::types::OutputFormat;
         self.state.repomix_options.output_format = match self.state.repomix_options.output_format {
@@ -696,31 +934,60 @@ impl App {
           OutputFormat::Xml => OutputFormat::PlainText,
         };
         if let Err(e) = self.save_repomix_options() {
-          self.set_status_message(format!("Error: config save error {}", e));
+          self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
         } else {
-          self.set_status_message(format!("Output format: {}", self.state.repomix_options.output_format.display_name()));
+          self.notify(StatusLevel::Info, NotificationSource::App, format!("Output format: {}", self.state.repomix_options.output_format.display_name()));
         }
         return Ok(true);
       }
-      KeyCode::Char('t') if self.state.repomix_options.backend == crate::types::Backend::Repomix => {
-        // toggle file tree
-        self.state.repomix_options.file_tree = !self.state.repomix_options.file_tree;
-        if let Err(e) = self.save_repomix_options() {
-          self.set_status_message(format!("Error: config save error {}", e));
+      Action::ToggleFileTree => {
+        // toggle file tree (yek's tree header when yek is active)
+        let enabled = match self.state.repomix_options.backend {
+          Backend::Repomix => {
+            self.state.repomix_options.file_tree = !self.state.repomix_options.file_tree;
+            self.state.repomix_options.file_tree
+          }
+          Backend::Yek => {
+            self.state.yek_options.tree_header = !self.state.yek_options.tree_header;
+            self.state.yek_options.tree_header
+          }
+          Backend::Custom(ref name) => {
+            let message = format!("File tree isn't available for {}", name);
+            self.notify(StatusLevel::Warning, NotificationSource::Custom, message);
+            return Ok(true);
+          }
+        };
+        let saved = match self.state.repomix_options.backend {
+          Backend::Yek => self.save_yek_options(),
+          _ => self.save_repomix_options(),
+        };
+        if let Err(e) = saved {
+          self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
         } else {
-          self.set_status_message(format!("File tree: {}", if self.state.repomix_options.file_tree { "enabled" } else { "disabled" }));
+          self.notify(StatusLevel::Info, NotificationSource::App, format!("File tree: {}", if enabled { "enabled" } else { "disabled" }));
+        }
+        return Ok(true);
+      }
+      // yek configuration shortcuts
+      Action::CycleSizeLimit => {
+        // cycle through the preset size and token limits
+        self.state.yek_options.limit = self.state.yek_options.limit.next();
+        if let Err(e) = self.save_yek_options() {
+          self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
+        } else {
+          self.notify(StatusLevel::Info, NotificationSource::App, format!("Size limit: {}", self.state.yek_options.limit.display_name()));
         }
         return Ok(true);
       }
       // global bulk operations (will work regardless of focus)
-      KeyCode::Char('E') => {
+      Action::ExpandAll => {
         // expand all directories
         crate::file_utils::expand_all_directories(&mut self.state.file_tree);
         self.state.visible_paths = crate::file_utils::flatten_visible_tree(&self.state.file_tree, &self.state.root_path);
-        self.set_status_message("Expanded all directories".to_string());
+        self.notify(StatusLevel::Info, NotificationSource::App, "Expanded all directories".to_string());
         return Ok(true);
       }
-      KeyCode::Char('C') => {
+      Action::CollapseAll => {
         // collapse all directories (keep root expanded)
         crate::file_utils::collapse_all_directories(&mut self.state.file_tree);
         // re-expand the root directory
@@ -728,13 +995,15 @@ impl App {
           root_node.is_expanded = true;
         }
         self.state.visible_paths = crate::file_utils::flatten_visible_tree(&self.state.file_tree, &self.state.root_path);
-        self.set_status_message("Collapsed all directories".to_string());
+        self.notify(StatusLevel::Info, NotificationSource::App, "Collapsed all directories".to_string());
         return Ok(true);
       }
-      KeyCode::Char('A') => {
+      Action::SelectAll => {
         // select all visible items (files and directories)
         match crate::file_utils::select_all_visible_files(&mut self.state.file_tree, &self.state.visible_paths) {
-          Ok(()) => {
+          Ok(changed_files) => {
+            self.refresh_selection_sort(&changed_files);
+            self.record_selection("Select all", changed_files);
             // clear token cache
             self.state.individual_token_counts.clear();
             self.pending_token_calculations.clear();
@@ -742,59 +1011,81 @@ impl App {
             self.is_bulk_token_calculation = true;
             // allow status messages
             self.suppress_status_messages = false;
-            self.set_status_message("Selected all items - calculating tokens...".to_string());
+            self.notify(StatusLevel::Progress, NotificationSource::Tokens, "Selected all items - calculating tokens...".to_string());
           }
           Err(e) => {
-            self.set_status_message(format!("Error selecting items: {}", e));
+            self.notify(StatusLevel::Error, NotificationSource::App, format!("Error selecting items: {}", e));
           }
         }
         // force token count update without debouncing
         if let Err(e) = self.update_token_count_non_blocking() {
-          self.set_status_message(format!("Error: token count error {}", e));
+          self.notify(StatusLevel::Error, NotificationSource::Tokens, format!("Error: token count error {}", e));
         }
         return Ok(true);
       }
-      KeyCode::Char('U') => {
+      Action::UnselectAll => {
         // unselect all items
-        crate::file_utils::unselect_all_items(&mut self.state.file_tree);
+        let changed_files = crate::file_utils::unselect_all_items(&mut self.state.file_tree);
+        self.refresh_selection_sort(&changed_files);
+        self.record_selection("Unselect all", changed_files);
         // clear token cache
         self.state.individual_token_counts.clear();
         self.pending_token_calculations.clear();
         self.token_count = 0;
         self.is_bulk_token_calculation = false;
         self.suppress_status_messages = false;
-        self.set_status_message("Unselected all items".to_string());
+        self.notify(StatusLevel::Info, NotificationSource::App, "Unselected all items".to_string());
         // no need to update token count since we know it's 0
         return Ok(true);
       }
+      Action::ToggleSelection => {
+        if let Some(path) = self.state.visible_paths.get(self.state.selected_index).cloned() {
+          // selecting skips binary files, say so before the toggle leaves them out
+          self.warn_if_skipped(&path);
+          self.toggle_selection_with_history(&path);
+          self.suppress_status_messages = false;
+          self.update_token_count_debounced()?;
+        }
+        return Ok(true);
+      }
+      Action::ForceSelection => {
+        if let Some(path) = self.state.visible_paths.get(self.state.selected_index).cloned() {
+          if let Ok(changed_files) = crate::file_utils::force_toggle_selection(&mut self.state.file_tree, &path) {
+            self.refresh_selection_sort(&changed_files);
+            self.record_selection("Toggle selection", changed_files);
+          }
+          self.suppress_status_messages = false;
+          self.update_token_count_debounced()?;
+        }
+        return Ok(true);
+      }
       _ => {}
     }
 
     // let the UI components handle the input
-    let input_handled = handle_input(&mut self.state, &mut self.ui_state, key);
+    let input_handled = handle_input(&mut self.state, &mut self.ui_state, action);
 
     // if input was handled and might have changed file selection, update token count
     if input_handled {
-      // check if key was a selection-changing key
-      match key.code {
-        KeyCode::Char(' ') => {
-          // space key toggles selection, so update token count
-          self.suppress_status_messages = false;
+      // check if action changed the selection
+      match action {
+        // collapse/expand can change which selections are visible
+        // but don't trigger token recalculation unless selections changed
+        // only update if in bulk calculation
+        Action::Collapse | Action::Expand if self.is_bulk_token_calculation => {
           self.update_token_count_debounced()?;
         }
-        KeyCode::Char('h') | KeyCode::Char('l') | KeyCode::Left | KeyCode::Right => {
-          // h/l and arrow keys can toggle directory expansion, which might affect visible selections
-          // but don't trigger token recalculation unless selections changed
-          // only update if in bulk calculation
-          if self.is_bulk_token_calculation {
-            self.update_token_count_debounced()?;
-          }
+        Action::Expand if self.state.sort_mode == SortMode::Tokens => {
+          // newly visible files need counts for token sorting
+          let visible_paths = self.state.visible_paths.clone();
+          self.queue_extra_token_calculations(&visible_paths);
         }
-        KeyCode::Up | KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('k') => {
+        Action::MoveUp | Action::MoveDown => {
           // navigation keys don't change selections, so don't update token count
           // clear any existing calculation messages and suppress new ones
-          if self.status_message.contains("Calculating tokens") && !self.is_bulk_token_calculation {
-            self.clear_status_message();
+          let showing_progress = self.state.notifications.toast(false).is_some_and(|toast| toast.level == StatusLevel::Progress);
+          if showing_progress && !self.is_bulk_token_calculation && !self.is_processing {
+            self.state.notifications.dismiss_toast();
           }
           self.suppress_status_messages = true;
         }
@@ -805,10 +1096,443 @@ impl App {
     Ok(true)
   }
 
+  /// Handles keyboard input while the pattern selection prompt is open.
+  fn handle_pattern_prompt_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+    let Some(prompt) = self.state.pattern_prompt.as_mut() else {
+      return Ok(());
+    };
+
+    match key.code {
+      KeyCode::Esc => {
+        self.state.pattern_prompt = None;
+      }
+      KeyCode::Char('c') if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
+        self.state.pattern_prompt = None;
+      }
+      KeyCode::Enter => {
+        // the shown preview can be out of date (debounced typing, streaming scan, rescan, API changes)
+        let mut prompt = std::mem::take(prompt);
+        self.state.pattern_prompt = None;
+        prompt.refresh(&self.state.file_tree, &self.state.roots);
+        if let Some(error) = prompt.error {
+          self.notify(StatusLevel::Error, NotificationSource::App, format!("Error: {}", error));
+          return Ok(());
+        }
+
+        if let Some(preview) = prompt.preview {
+          let changed_files = preview.changed_files();
+          file_utils::flip_file_selections(&mut self.state.file_tree, &changed_files);
+          self.refresh_selection_sort(&changed_files);
+          self.record_selection("Pattern selection", changed_files);
+          self.suppress_status_messages = false;
+          self.notify(StatusLevel::Info, NotificationSource::App, format!("Pattern selection: +{} -{} files", preview.to_select.len(), preview.to_deselect.len()));
+          self.update_token_count_non_blocking()?;
+        }
+      }
+      KeyCode::Backspace => {
+        prompt.input.pop();
+        prompt.mark_stale();
+      }
+      KeyCode::Char(c) => {
+        prompt.input.push(c);
+        prompt.mark_stale();
+      }
+      _ => {}
+    }
+
+    Ok(())
+  }
+
+  /// Handles keyboard input while the command palette is open.
+  /// Returns false if the chosen action quits the app.
+  async fn handle_command_palette_key(&mut self, key: crossterm::event::KeyEvent) -> Result<bool> {
+    let Some(palette) = self.state.command_palette.as_mut() else {
+      return Ok(true);
+    };
+    let control = key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL);
+
+    match key.code {
+      KeyCode::Esc => {
+        self.state.command_palette = None;
+      }
+      KeyCode::Char('c') if control => {
+        self.state.command_palette = None;
+      }
+      KeyCode::Enter => {
+        let action = palette.selected_action();
+        self.state.command_palette = None;
+        if let Some(action) = action {
+          return self.execute_action(action).await;
+        }
+      }
+      KeyCode::Up => palette.move_selection(false),
+      KeyCode::Char('p') if control => palette.move_selection(false),
+      KeyCode::Down => palette.move_selection(true),
+      KeyCode::Char('n') if control => palette.move_selection(true),
+      KeyCode::Backspace => {
+        palette.input.pop();
+        palette.refresh(&self.state.repomix_options.backend);
+      }
+      KeyCode::Char(c) => {
+        palette.input.push(c);
+        palette.refresh(&self.state.repomix_options.backend);
+      }
+      _ => {}
+    }
+
+    Ok(true)
+  }
+
+  /// Recomputes the pattern preview and queues token counts for files not counted yet.
+  fn refresh_pattern_prompt(&mut self) {
+    let Some(prompt) = self.state.pattern_prompt.as_mut() else {
+      return;
+    };
+
+    prompt.refresh(&self.state.file_tree, &self.state.roots);
+
+    if let Some(preview) = &prompt.preview {
+      let resulting_files = preview.resulting_files.clone();
+      self.queue_extra_token_calculations(&resulting_files);
+    }
+  }
+
+  /// Queues token calculations for files that are not selected (and not counted yet).
+  /// Limited so huge previews or trees don't flood the token task.
+  fn queue_extra_token_calculations(&mut self, paths: &[PathBuf]) {
+    const MAX_EXTRA_TOKEN_REQUESTS: usize = 1000;
+
+    for path in paths {
+      if self.extra_token_requests.len() >= MAX_EXTRA_TOKEN_REQUESTS {
+        break;
+      }
+
+      let is_file = self.state.file_tree.get(path).map(|node| !node.is_directory).unwrap_or(false);
+      if is_file && !self.state.file_token_cache.contains_key(path) && self.extra_token_requests.insert(path.clone()) && self.token_request_sender.send(path.clone()).is_err() {
+        break;
+      }
+    }
+  }
+
+  /// Cycles to the next sort mode, re-sorts the tree and saves the choice.
+  fn cycle_sort_mode(&mut self) {
+    self.state.sort_mode = self.state.sort_mode.next();
+    self.resort_tree();
+
+    // token sorting needs counts for files that aren't selected
+    if self.state.sort_mode == SortMode::Tokens {
+      let visible_paths = self.state.visible_paths.clone();
+      self.queue_extra_token_calculations(&visible_paths);
+    }
+
+    self.config.sort_mode = self.state.sort_mode;
+    if let Err(e) = self.config.save() {
+      self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
+    } else {
+      self.notify(StatusLevel::Info, NotificationSource::App, format!("Sort: {}", self.state.sort_mode.display_name()));
+    }
+  }
+
+  /// Switches to the next color theme and saves it to config.
+  fn cycle_theme(&mut self) {
+    self.config.theme = self.config.theme.next();
+    self.state.theme = Theme::from_config(self.config.theme);
+
+    if let Err(e) = self.config.save() {
+      self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
+    } else if self.state.theme.name != self.config.theme {
+      // NO_COLOR wins over the configured theme
+      self.notify(
+        StatusLevel::Info,
+        NotificationSource::App,
+        format!("Theme: {} (NO_COLOR is set, using {})", self.config.theme.display_name(), ThemeName::Monochrome.display_name()),
+      );
+    } else {
+      self.notify(StatusLevel::Info, NotificationSource::App, format!("Theme: {}", self.config.theme.display_name()));
+    }
+  }
+
+  /// Shows or hides dotfiles, saves the choice and rescans the tree.
+  fn toggle_hidden(&mut self) {
+    self.state.show_hidden = !self.state.show_hidden;
+    self.config.scan.show_hidden = self.state.show_hidden;
+
+    if let Err(e) = self.config.save() {
+      self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
+    } else {
+      self.notify(StatusLevel::Info, NotificationSource::App, format!("Hidden files: {}", if self.state.show_hidden { "shown" } else { "hidden" }));
+    }
+
+    self.rescan();
+  }
+
+  /// Scans the roots again with the current scan options.
+  /// Expanded dirs and selected files are kept if they're still in the tree.
+  fn rescan(&mut self) {
+    // a rescan during a rescan keeps what the first one saved, the tree is still partial
+    if !self.state.is_scanning || (self.rescan_expanded.is_empty() && self.rescan_selection.is_empty()) {
+      self.rescan_expanded = self.state.file_tree.values().filter(|node| node.is_directory && node.is_expanded).map(|node| node.path.clone()).collect();
+      self.rescan_selection = self.state.file_tree.values().filter(|node| !node.is_directory && node.is_selected).map(|node| node.path.clone()).collect();
+    }
+
+    let root_path = self.state.root_path.clone();
+    let mut root_node = FileNode::new(root_path.clone(), true, 0);
+    root_node.is_expanded = self.rescan_expanded.contains(&root_path);
+    self.state.file_tree = HashMap::from([(root_path.clone(), root_node)]);
+    self.state.is_scanning = true;
+    self.update_visible_files();
+    self.state.selected_index = 0;
+
+    // the previous scan stops sending once its receiver is dropped
+    let (scan_event_sender, scan_event_receiver) = mpsc::unbounded_channel::<ScanEvent>();
+    self.scan_event_receiver = scan_event_receiver;
+    let roots = self.state.roots.clone();
+    let scan_options = self.config.scan.clone();
+    tokio::task::spawn_blocking(move || {
+      file_utils::scan_roots_streaming(&roots, &scan_options, scan_event_sender);
+    });
+  }
+
+  /// Switches to the next backend, initializing repomix or yek the first time it's used.
+  /// Stays on the current backend if the next one can't be initialized.
+  async fn switch_backend(&mut self) {
+    let custom_names: Vec<String> = self.state.custom_backends.iter().map(|custom_backend| custom_backend.name.clone()).collect();
+    let mut next_backend = self.state.repomix_options.backend.next(&custom_names);
+
+    // switching again while repomix is being checked skips past it
+    if self.repomix_check.take().is_some() {
+      next_backend = Backend::Repomix.next(&custom_names);
+    }
+
+    // node and npm are checked in the background, the switch finishes in update_repomix_check
+    if next_backend == Backend::Repomix && self.repomix.is_none() {
+      self.repomix_check = Some((self.start_repomix_check)(self.config.repomix.clone()));
+      self.notify(StatusLevel::Progress, NotificationSource::Repomix, "Checking Repomix dependencies...".to_string());
+      return;
+    }
+
+    if next_backend == Backend::Yek && self.yek.is_none() {
+      if let Err(e) = YekProvisioner::check_available(&self.config.yek) {
+        self.notify(StatusLevel::Error, NotificationSource::Yek, format!("Error: can't switch to Yek, {}", e));
+        return;
+      }
+      let mut provisioner = YekProvisioner::new(&self.config.yek);
+      provisioner.start();
+      self.yek = Some(provisioner);
+    }
+
+    if let Backend::Custom(name) = &next_backend {
+      let available = crate::custom_backend::find(&self.state.custom_backends, name).map(|custom_backend| custom_backend.check_available());
+      if let Some(Err(e)) = available {
+        self.notify(StatusLevel::Error, NotificationSource::Custom, format!("Error: can't switch to {}, {}", name, e));
+        return;
+      }
+    }
+
+    self.set_backend(next_backend);
+  }
+
+  /// Checks that node and npm are there for repomix, in the background.
+  fn check_repomix_dependencies(repomix_config: RepomixConfig) -> oneshot::Receiver<Result<()>> {
+    let (sender, receiver) = oneshot::channel();
+    tokio::spawn(async move {
+      let _ = sender.send(Repomix::check_build_dependencies(&repomix_config).await);
+    });
+    receiver
+  }
+
+  /// Finishes switching to repomix once the background dependency check is done.
+  /// Returns true if the check finished.
+  async fn update_repomix_check(&mut self) -> bool {
+    let Some(receiver) = self.repomix_check.as_mut() else {
+      return false;
+    };
+    let checked = match receiver.try_recv() {
+      Ok(checked) => checked,
+      Err(oneshot::error::TryRecvError::Empty) => return false,
+      Err(oneshot::error::TryRecvError::Closed) => Err(anyhow::anyhow!("dependency check stopped")),
+    };
+    self.repomix_check = None;
+
+    match checked.and_then(|()| Repomix::new(&self.config.repomix)) {
+      Ok(mut repomix) => {
+        repomix.start_background_download().await;
+        self.repomix = Some(repomix);
+        self.set_backend(Backend::Repomix);
+      }
+      Err(e) => {
+        self.notify(StatusLevel::Error, NotificationSource::Repomix, format!("Error: can't switch to Repomix, {}", e));
+      }
+    }
+    true
+  }
+
+  /// Makes an initialized backend the active one for this session.
+  fn set_backend(&mut self, backend: Backend) {
+    self.notify(StatusLevel::Info, NotificationSource::App, format!("Backend: {} (d to make default)", backend.display_name()));
+    self.state.repomix_options.backend = backend;
+  }
+
+  /// Saves the current backend as the default for new sessions.
+  fn save_default_backend(&mut self) {
+    self.config.default_backend = self.state.repomix_options.backend.clone();
+    if let Err(e) = self.config.save() {
+      self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: config save error {}", e));
+    } else {
+      self.notify(StatusLevel::Info, NotificationSource::App, format!("Default backend: {}", self.config.default_backend.display_name()));
+    }
+  }
+
+  /// Re-sorts the tree with the active sort mode, keeping the cursor on the same path.
+  fn resort_tree(&mut self) {
+    file_utils::sort_children(&mut self.state.file_tree, self.state.sort_mode, &self.state.file_token_cache);
+    self.update_visible_files_keeping_cursor();
+  }
+
+  /// Rebuilds the visible list while keeping the cursor on the same path.
+  fn update_visible_files_keeping_cursor(&mut self) {
+    let current_path = self.state.visible_paths.get(self.state.selected_index).cloned();
+
+    self.update_visible_files();
+
+    if let Some(index) = current_path.and_then(|path| self.state.visible_paths.iter().position(|visible| *visible == path)) {
+      self.state.selected_index = index;
+    }
+  }
+
+  /// Merges nodes streamed by the background scan into the tree (non-blocking).
+  /// Works for a limited time per call so the UI stays responsive on huge trees.
+  fn process_scan_events(&mut self) -> bool {
+    const SCAN_MERGE_BUDGET: Duration = Duration::from_millis(50);
+
+    let started_at = Instant::now();
+    let mut touched_dirs = Vec::new();
+    let mut processed_any = false;
+
+    while started_at.elapsed() < SCAN_MERGE_BUDGET {
+      match self.scan_event_receiver.try_recv() {
+        Ok(ScanEvent::Nodes(mut nodes)) => {
+          if !self.rescan_expanded.is_empty() {
+            for node in nodes.iter_mut().filter(|node| node.is_directory) {
+              node.is_expanded = self.rescan_expanded.contains(&node.path);
+            }
+          }
+          touched_dirs.extend(file_utils::merge_scanned_nodes(&mut self.state.file_tree, nodes));
+          processed_any = true;
+        }
+        Ok(ScanEvent::Warning(message)) => {
+          self.notify(StatusLevel::Warning, NotificationSource::Scan, format!("Warning: {}", message));
+          processed_any = true;
+        }
+        Ok(ScanEvent::Finished { unreadable_dirs }) => {
+          self.state.is_scanning = false;
+          file_utils::mark_link_targets(&mut self.state.file_tree, &self.state.roots);
+          let total_files = self.state.file_tree.get(&self.state.root_path).map(|node| node.total_files).unwrap_or(0);
+          if unreadable_dirs > 0 {
+            self.notify(
+              StatusLevel::Warning,
+              NotificationSource::Scan,
+              format!("Warning: scanned {} files, {} directories could not be read", total_files, unreadable_dirs),
+            );
+          } else {
+            self.notify(StatusLevel::Success, NotificationSource::Scan, format!("✓ Scanned {} files", total_files));
+          }
+          processed_any = true;
+          break;
+        }
+        Err(_) => break,
+      }
+    }
+
+    if processed_any {
+      touched_dirs.sort();
+      touched_dirs.dedup();
+      file_utils::sort_children_of(&mut self.state.file_tree, &touched_dirs, self.state.sort_mode, &self.state.file_token_cache);
+      self.update_visible_files_keeping_cursor();
+      // an open pattern preview counts the new files too, at most once per debounce period
+      if let Some(prompt) = self.state.pattern_prompt.as_mut().filter(|prompt| prompt.edited_at.is_none()) {
+        prompt.mark_stale();
+      }
+    }
+
+    // the list is applied to the whole tree, so wait for the scan
+    if !self.state.is_scanning && !self.initial_selection.is_empty() {
+      self.apply_initial_selection();
+    }
+    if !self.state.is_scanning && (!self.rescan_selection.is_empty() || !self.rescan_expanded.is_empty()) {
+      self.restore_rescan_selection();
+    }
+
+    processed_any
+  }
+
+  /// Selects the files that were selected before a rescan (without recording history).
+  /// Files that are hidden now are dropped, and so are binary files (forced ones have to be forced again).
+  fn restore_rescan_selection(&mut self) {
+    self.rescan_expanded.clear();
+    let mut selection = std::mem::take(&mut self.rescan_selection);
+    selection.retain(|path| self.state.file_tree.contains_key(path));
+    // nothing is selected in the new tree, files that are binary now stay unselected
+    file_utils::flip_file_selections(&mut self.state.file_tree, &selection);
+    self.refresh_selection_sort(&selection);
+    if let Err(e) = self.update_token_count_non_blocking() {
+      self.notify(StatusLevel::Error, NotificationSource::Tokens, format!("Error: token count error {}", e));
+    }
+  }
+
+  /// Selects the paths given with --select-from and reports the ones not in the tree.
+  fn apply_initial_selection(&mut self) {
+    let paths = std::mem::take(&mut self.initial_selection);
+    let (resolved, unknown) = file_utils::resolve_paths(&self.state.file_tree, &self.state.roots, &paths);
+
+    let mut changed_files = Vec::new();
+    for path in &resolved {
+      match file_utils::set_selection(&mut self.state.file_tree, path, true) {
+        Ok(changed) => changed_files.extend(changed),
+        Err(e) => self.notify(StatusLevel::Error, NotificationSource::App, format!("Error selecting {}: {}", path.display(), e)),
+      }
+    }
+    let changed = changed_files.len();
+    if changed > 0 {
+      self.refresh_selection_sort(&changed_files);
+      self.record_selection("Select from list", changed_files);
+    }
+
+    if unknown.is_empty() {
+      self.notify(StatusLevel::Info, NotificationSource::App, format!("Selected {} files from list", changed));
+    } else {
+      self.notify_with_details(
+        StatusLevel::Warning,
+        NotificationSource::App,
+        format!("Warning: selected {} files from list, {} paths not found", changed, unknown.len()),
+        unknown.join("\n"),
+      );
+    }
+    if let Err(e) = self.update_token_count_non_blocking() {
+      self.notify(StatusLevel::Error, NotificationSource::Tokens, format!("Error: token count error {}", e));
+    }
+  }
+
+  /// Re-sorts after selection changes when sorting by selection.
+  /// Only the dirs above the changed files can change order, the rest of the tree is left alone.
+  fn refresh_selection_sort(&mut self, changed_files: &[PathBuf]) {
+    if self.state.sort_mode != SortMode::SelectedFirst || changed_files.is_empty() {
+      return;
+    }
+    let changed_dirs = file_utils::ancestor_dirs(&self.state.file_tree, changed_files);
+    file_utils::sort_children_of(&mut self.state.file_tree, &changed_dirs, self.state.sort_mode, &self.state.file_token_cache);
+    self.update_visible_files_keeping_cursor();
+  }
+
   /// Handles mouse input events.
   async fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<()> {
     use crossterm::event::MouseEventKind;
 
+    // the tree is covered while an overlay is open
+    if self.state.show_help || self.state.show_log || self.state.command_palette.is_some() {
+      return Ok(());
+    }
+
     match mouse.kind {
       MouseEventKind::Down(crossterm::event::MouseButton::Left) => {
         // handle left mouse click
@@ -875,15 +1599,12 @@ impl App {
                 }
               } else {
                 // clicked on directory name, then toggle selection
-                if crate::file_utils::toggle_selection_recursive(&mut self.state.file_tree, &clicked_path).is_err() {
-                  // silently handle errors
-                }
+                self.toggle_selection_with_history(&clicked_path);
               }
             } else {
               // for files, toggle selection
-              if crate::file_utils::toggle_selection_recursive(&mut self.state.file_tree, &clicked_path).is_err() {
-                // silently handle errors
-              }
+              self.warn_if_skipped(&clicked_path);
+              self.toggle_selection_with_history(&clicked_path);
             }
 
             // update token count
@@ -896,13 +1617,61 @@ impl App {
     Ok(())
   }
 
+  /// Toggles selection of a path and records the change for undo.
+  fn toggle_selection_with_history(&mut self, path: &Path) {
+    if let Ok(changed_files) = crate::file_utils::toggle_selection_recursive(&mut self.state.file_tree, path) {
+      self.refresh_selection_sort(&changed_files);
+      self.record_selection("Toggle selection", changed_files);
+    }
+  }
+
+  /// Records a selection change for undo, with a warning if it's too large to be undone.
+  fn record_selection(&mut self, label: &str, changed_files: Vec<PathBuf>) {
+    let count = changed_files.len();
+    if !self.state.selection_history.record(label, changed_files) {
+      self.notify(StatusLevel::Warning, NotificationSource::App, crate::selection_history::not_recorded_message(label, count));
+    }
+  }
+
+  /// Tells why a binary or unreadable file won't be selected, and how to select it anyway.
+  fn warn_if_skipped(&mut self, path: &Path) {
+    let Some(node) = self.state.file_tree.get(path).filter(|node| !node.is_directory && !node.is_selected && !node.content.is_text()) else {
+      return;
+    };
+    let message = match self.state.keymap.first_key(Action::ForceSelection) {
+      Some(key) => format!("Warning: {} is {}, {} selects it anyway", node.name, node.content.display_name(), key),
+      None => format!("Warning: {} is {}, not selected", node.name, node.content.display_name()),
+    };
+    self.notify(StatusLevel::Warning, NotificationSource::App, message);
+  }
+
+  /// Undoes or redoes the last selection change and recomputes token totals.
+  fn apply_selection_history(&mut self, redo: bool) -> Result<()> {
+    let replayed = if redo {
+      self.state.selection_history.redo(&mut self.state.file_tree)
+    } else {
+      self.state.selection_history.undo(&mut self.state.file_tree)
+    };
+
+    match replayed {
+      Some((label, flipped_files)) => {
+        self.refresh_selection_sort(&flipped_files);
+        self.suppress_status_messages = false;
+        self.notify(StatusLevel::Info, NotificationSource::App, format!("{}: {}", if redo { "Redo" } else { "Undo" }, label));
+        self.update_token_count_non_blocking()?;
+      }
+      None => {
+        self.notify(StatusLevel::Info, NotificationSource::App, format!("Nothing to {}", if redo { "redo" } else { "undo" }));
+      }
+    }
+
+    Ok(())
+  }
+
   /// Calculates which file index was clicked based on the row position.
   /// Returns none if the click was outside the file list area.
   fn calculate_clicked_file_index(&self, row: u16) -> Option<usize> {
-    let config_height = match self.state.repomix_options.backend {
-      Backend::Repomix => 3, // repomix config box height
-      Backend::Yek => 0,     // yek has no config
-    };
+    let config_height = 3; // config box height, both backends have one
 
     let file_list_start_row = config_height + 2; // +1 for border, +1 for directory info
 
@@ -932,28 +1701,43 @@ impl App {
 
   /// Runs the selected backend with the currently selected files and options.
   async fn run_backend(&mut self) -> Result<()> {
-    // get selected files
-    let selected_files = file_utils::get_selected_files(&self.state.file_tree);
+    // get selected files, each file once even if symlinks reach it twice
+    let selected_files = file_utils::unique_files(&self.state.file_tree, file_utils::get_selected_files(&self.state.file_tree));
 
     if selected_files.is_empty() {
-      self.set_status_message("No files selected for processing".to_string());
+      self.notify(StatusLevel::Warning, NotificationSource::App, "No files selected for processing".to_string());
       return Ok(());
     }
+    let backend_source = NotificationSource::from(&self.state.repomix_options.backend);
 
     // validate options based on backend
     let warnings = match self.state.repomix_options.backend {
       crate::types::Backend::Repomix => crate::repomix_integration::validate_isolated_repomix_options(&self.state.repomix_options, &selected_files),
-      crate::types::Backend::Yek => crate::yek_integration::validate_yek_options(&selected_files),
+      crate::types::Backend::Yek => match crate::yek_integration::validate_yek_options(&self.state.yek_options, &selected_files) {
+        Ok(warnings) => warnings,
+        Err(e) => {
+          // yek would reject these options, don't start the run
+          self.notify(StatusLevel::Error, backend_source, format!("Error: invalid yek options, {:#}", e));
+          return Ok(());
+        }
+      },
+      crate::types::Backend::Custom(_) => Vec::new(),
     };
 
     if !warnings.is_empty() {
-      self.set_status_message(format!("Warning: {}", warnings.join(", ")));
+      self.notify(StatusLevel::Warning, backend_source, format!("Warning: {}", warnings.join(", ")));
       // continue anyway, but show the warning
     }
 
+    // broken redaction rules would fail the run, report them before starting
+    if let Err(e) = crate::redaction::Redactor::new(&self.config.redaction_rules) {
+      self.notify(StatusLevel::Error, NotificationSource::Config, format!("Error: {:#}", e));
+      return Ok(());
+    }
+
     // check if already processing, then cancel and restart
     if self.is_processing {
-      self.set_status_message("Cancelling previous run and restarting...".to_string());
+      self.notify(StatusLevel::Progress, backend_source, "Cancelling previous run and restarting...".to_string());
       // cancel the current running process
       self.cancellation_token.cancel();
       self.is_processing = false;
@@ -965,30 +1749,77 @@ impl App {
 
     // for repomix, check download status first
     if matches!(self.state.repomix_options.backend, Backend::Repomix) {
-      let download_status = self.repomix.as_ref().unwrap().download_status().clone();
+      let Some(repomix) = self.repomix.as_mut() else {
+        self.notify(StatusLevel::Error, NotificationSource::Repomix, "Error: Repomix is not initialized".to_string());
+        return Ok(());
+      };
+      let download_status = repomix.download_status().clone();
       match download_status {
-        crate::repomix_integration::DownloadStatus::Downloading(msg) => {
-          self.set_status_message(format!("Downloading: {}", msg));
+        DownloadStatus::Downloading(msg) => {
+          self.notify(StatusLevel::Progress, NotificationSource::Repomix, format!("Downloading: {}", msg));
           return Ok(());
         }
-        crate::repomix_integration::DownloadStatus::Failed(err) => {
+        DownloadStatus::Failed(err) => {
           // try to restart download
-          self.repomix.as_mut().unwrap().start_background_download().await;
-          self.set_status_message(format!("Repomix download failed: {}", err));
+          repomix.start_background_download().await;
+          self.notify(StatusLevel::Error, NotificationSource::Repomix, format!("Repomix download failed: {}", err));
           return Ok(());
         }
-        crate::repomix_integration::DownloadStatus::NotStarted => {
+        DownloadStatus::NotStarted => {
           // start download
-          self.repomix.as_mut().unwrap().start_background_download().await;
-          self.set_status_message("Starting repomix download...".to_string());
+          repomix.start_background_download().await;
+          self.notify(StatusLevel::Progress, NotificationSource::Repomix, "Starting repomix download...".to_string());
           return Ok(());
         }
-        crate::repomix_integration::DownloadStatus::Ready => {
+        DownloadStatus::Ready => {
           // download is ready
         }
       }
     }
 
+    // for yek, wait until a verified binary was found or installed
+    let mut yek_binary = None;
+    if matches!(self.state.repomix_options.backend, Backend::Yek) {
+      let Some(yek) = self.yek.as_mut() else {
+        self.notify(StatusLevel::Error, NotificationSource::Yek, "Error: Yek is not initialized".to_string());
+        return Ok(());
+      };
+      match yek.download_status().clone() {
+        DownloadStatus::Downloading(msg) => {
+          self.notify(StatusLevel::Progress, NotificationSource::Yek, format!("Setting up yek: {}", msg));
+          return Ok(());
+        }
+        DownloadStatus::Failed(err) => {
+          // retry, the error may have been fixed in the meantime
+          yek.start();
+          self.notify(StatusLevel::Error, NotificationSource::Yek, format!("Yek setup failed, retrying: {}", err));
+          return Ok(());
+        }
+        DownloadStatus::NotStarted => {
+          yek.start();
+          self.notify(StatusLevel::Progress, NotificationSource::Yek, "Setting up yek...".to_string());
+          return Ok(());
+        }
+        DownloadStatus::Ready => yek_binary = yek.binary_path().map(Path::to_path_buf),
+      }
+    }
+
+    // for custom backends, the command comes from the config
+    let mut custom_backend = None;
+    if let Backend::Custom(name) = &self.state.repomix_options.backend {
+      match crate::custom_backend::find(&self.state.custom_backends, name).map(|config| (config, config.validate())) {
+        Some((config, Ok(()))) => custom_backend = Some(config.clone()),
+        Some((_, Err(e))) => {
+          self.notify(StatusLevel::Error, NotificationSource::Custom, format!("Error: invalid custom backend, {:#}", e));
+          return Ok(());
+        }
+        None => {
+          self.notify(StatusLevel::Error, NotificationSource::Custom, format!("Error: custom backend {} is not defined in the config", name));
+          return Ok(());
+        }
+      }
+    }
+
     // generate new request ID
     let request_id = self.next_request_id;
     self.next_request_id += 1;
@@ -996,17 +1827,23 @@ impl App {
 
     // set processing state
     self.is_processing = true;
-    let backend_name = self.state.repomix_options.backend.display_name();
+    let backend_name = self.state.repomix_options.backend.display_name().to_string();
 
-    self.set_status_message(format!("Running {} on {} files...", backend_name, selected_files.len()));
+    self.notify(StatusLevel::Progress, backend_source, format!("Running {} on {} files...", backend_name, selected_files.len()));
 
     // create backend request
     let request = BackendRequest {
       backend: self.state.repomix_options.backend.clone(),
       repomix_options: self.state.repomix_options.clone(),
+      yek_options: self.state.yek_options.clone(),
       selected_files,
-      root_path: self.state.root_path.clone(),
-      file_tree: self.state.file_tree.clone(),
+      roots: self.state.roots.clone(),
+      // only render the directory structure when it's going to be used
+      file_tree_text: self.state.repomix_options.file_tree.then(|| file_utils::generate_file_tree_text(&self.state.file_tree, &self.state.root_path)),
+      yek_binary,
+      custom_backend,
+      secrets: self.config.secrets.clone(),
+      redaction_rules: self.config.redaction_rules.clone(),
       request_id,
       cancellation_token: self.cancellation_token.clone(),
     };
@@ -1014,33 +1851,141 @@ impl App {
     // send request to background thread (non-blocking)
     if self.backend_request_sender.send(request).is_err() {
       self.is_processing = false;
-      self.set_status_message("Failed to start backend execution".to_string());
+      self.notify(StatusLevel::Error, backend_source, "Failed to start backend execution".to_string());
     }
 
     Ok(())
   }
 
+  /// Answers all pending API requests, returns true if any were handled.
+  async fn process_api_requests(&mut self) -> Result<bool> {
+    let mut processed_any = false;
+    while let Some(event) = self.api_event_receiver.as_mut().and_then(|receiver| receiver.try_recv().ok()) {
+      let response = self.handle_api_request(event.request).await?;
+      // the client may have hung up, nothing to do then
+      let _ = event.reply.send(response);
+      processed_any = true;
+    }
+    Ok(processed_any)
+  }
+
+  /// Handles one API request like the matching key press would.
+  async fn handle_api_request(&mut self, request: ApiRequest) -> Result<ApiResponse> {
+    let response = match request {
+      ApiRequest::GetTree => ApiResponse::ok(crate::api::tree_json(&self.state)),
+      ApiRequest::GetSelection => ApiResponse::ok(self.api_selection_json(0)),
+      ApiRequest::GetTokens => {
+        let files: serde_json::Map<String, serde_json::Value> = file_utils::get_selected_files(&self.state.file_tree)
+          .iter()
+          .filter_map(|path| match self.state.individual_token_counts.get(path) {
+            Some(Some(count)) => Some((crate::api::relative_path(&self.state.roots, path), (*count).into())),
+            _ => None,
+          })
+          .collect();
+        ApiResponse::ok(serde_json::json!({ "total": self.token_count, "pending": self.pending_token_calculations.len(), "files": files }))
+      }
+      ApiRequest::SetSelection(paths) => self.api_change_selection(&paths, None)?,
+      ApiRequest::AddToSelection(paths) => self.api_change_selection(&paths, Some(true))?,
+      ApiRequest::RemoveFromSelection(paths) => self.api_change_selection(&paths, Some(false))?,
+      ApiRequest::Run => {
+        self.run_backend().await?;
+        let message = self.state.notifications.entries().next_back().map(|notification| notification.message.clone()).unwrap_or_default();
+        let body = serde_json::json!({ "running": self.is_processing, "request_id": self.current_request_id, "message": message });
+        // nothing selected, or the backend is still being installed
+        ApiResponse {
+          status: if self.is_processing { 200 } else { 409 },
+          body,
+        }
+      }
+      ApiRequest::GetOutput => match &self.last_result {
+        Some(result) => ApiResponse::ok(serde_json::json!({
+          "request_id": result.request_id,
+          "backend": result.backend.display_name(),
+          "success": result.success,
+          "message": result.message,
+          "error": result.error,
+          "warnings": result.warnings,
+          "output_file": result.output_file,
+          "content": result.content,
+          "running": self.is_processing,
+        })),
+        None => ApiResponse::error(404, "No run has finished yet"),
+      },
+    };
+    Ok(response)
+  }
+
+  /// Replaces the selection with the paths (no state given), or selects or clears them.
+  fn api_change_selection(&mut self, paths: &[String], selected: Option<bool>) -> Result<ApiResponse> {
+    if paths.is_empty() && selected.is_some() {
+      return Ok(ApiResponse::error(400, "No paths given"));
+    }
+    let resolved = match crate::api::resolve_paths(&self.state.file_tree, &self.state.roots, paths) {
+      Ok(resolved) => resolved,
+      Err(response) => return Ok(response),
+    };
+    let targets: std::collections::HashSet<PathBuf> = resolved.iter().flat_map(|path| file_utils::files_under(&self.state.file_tree, path)).collect();
+
+    // the change is applied as flips, so it can be undone like any other selection change
+    let candidates = match selected {
+      Some(_) => targets.iter().cloned().collect(),
+      None => file_utils::files_under(&self.state.file_tree, &self.state.root_path),
+    };
+    let mut changed_files: Vec<PathBuf> = candidates
+      .into_iter()
+      .filter(|path| {
+        let wanted = selected.unwrap_or_else(|| targets.contains(path));
+        // binary and unreadable files are only selected with the force key
+        self.state.file_tree.get(path).is_some_and(|node| node.is_selected != wanted && (!wanted || node.content.is_text()))
+      })
+      .collect();
+    changed_files.sort();
+
+    let changed = changed_files.len();
+    if changed > 0 {
+      file_utils::flip_file_selections(&mut self.state.file_tree, &changed_files);
+      self.refresh_selection_sort(&changed_files);
+      self.record_selection("API selection", changed_files);
+      self.suppress_status_messages = false;
+      self.notify(StatusLevel::Info, NotificationSource::Api, format!("Selection changed: {} files", changed));
+      self.update_token_count_non_blocking()?;
+    }
+    Ok(ApiResponse::ok(self.api_selection_json(changed)))
+  }
+
+  /// Returns the selection as served by the API.
+  fn api_selection_json(&self, changed: usize) -> serde_json::Value {
+    let files: Vec<String> = file_utils::get_selected_files(&self.state.file_tree).iter().map(|path| crate::api::relative_path(&self.state.roots, path)).collect();
+    serde_json::json!({
+      "count": files.len(),
+      "files": files,
+      "changed": changed,
+      "tokens": self.token_count,
+      "pending_tokens": self.pending_token_calculations.len(),
+    })
+  }
+
+  /// Cancels the running backend, which kills its process group and removes its temp files.
+  fn cancel_backend(&mut self) {
+    if !self.is_processing {
+      self.notify(StatusLevel::Info, NotificationSource::App, "Nothing is running".to_string());
+      return;
+    }
+
+    self.cancellation_token.cancel();
+    self.is_processing = false;
+    self.current_request_id = None;
+    let backend = &self.state.repomix_options.backend;
+    self.notify(StatusLevel::Warning, NotificationSource::from(backend), format!("Cancelled {} run", backend.display_name()));
+  }
+
   /// Performs periodic updates.
   fn periodic_update(&mut self) {
-    // clear old status messages
-    if !self.status_message.is_empty() && !self.is_processing {
-      let should_clear = if self.is_bulk_token_calculation {
-        // keep bulk calculation messages longer (5 seconds)
-        self.status_updated_at.elapsed() > Duration::from_secs(5)
-      } else if self.status_message.contains("Calculating tokens") {
-        // clear token calculation messages after 1 second
-        self.status_updated_at.elapsed() > Duration::from_secs(1)
-      } else if self.status_message.starts_with("✓") {
-        // clear completion messages after 2 seconds
-        self.status_updated_at.elapsed() > Duration::from_secs(2)
-      } else {
-        // clear other messages normally (3 seconds)
-        self.status_updated_at.elapsed() > Duration::from_secs(3)
-      };
+    // toasts expire on their own based on their level
 
-      if should_clear {
-        self.status_message.clear();
-      }
+    // refresh the pattern preview once typing pauses
+    if self.state.pattern_prompt.as_ref().is_some_and(PatternPrompt::needs_refresh) {
+      self.refresh_pattern_prompt();
     }
 
     // clear suppress flag after 2 seconds
@@ -1049,15 +1994,15 @@ impl App {
     }
   }
 
-  /// Sets a status message and updates the timestamp.
-  fn set_status_message(&mut self, message: String) {
-    self.status_message = message;
-    self.status_updated_at = Instant::now();
+  /// Shows a notification as a toast and adds it to the message log.
+  fn notify(&mut self, level: StatusLevel, source: NotificationSource, message: String) {
+    self.state.notifications.push(level, source, message, None);
   }
 
-  /// Clears the current status message.
-  fn clear_status_message(&mut self) {
-    self.status_message.clear();
+  /// Like notify, with full output that's only shown in the message log.
+  fn notify_with_details(&mut self, level: StatusLevel, source: NotificationSource, message: String, details: String) {
+    let details = (!details.trim().is_empty()).then_some(details);
+    self.state.notifications.push(level, source, message, details);
   }
 
   /// Expands the root directory to show initial files.
@@ -1072,30 +2017,32 @@ impl App {
 
   /// Syncs app state with UI state.
   fn sync_app_state(&mut self) {
-    self.state.status_message = self.status_message.clone();
     self.state.is_processing = self.is_processing;
     self.state.token_count = self.token_count;
   }
 
   /// Updates repomix background download and returns true if status changed.
   async fn update_repomix_download(&mut self) -> Result<bool> {
-    let status_changed = self.repomix.as_mut().unwrap().update_background_download().await;
+    let Some(repomix) = self.repomix.as_mut() else {
+      return Ok(false);
+    };
+    let status_changed = repomix.update_background_download().await;
 
     if status_changed {
       // update status message
-      match self.repomix.as_ref().unwrap().download_status() {
-        crate::repomix_integration::DownloadStatus::Ready => {
-          self.set_status_message("Repomix ready!".to_string());
+      match repomix.download_status().clone() {
+        DownloadStatus::Ready => {
+          self.notify(StatusLevel::Success, NotificationSource::Repomix, "Repomix ready!".to_string());
         }
-        crate::repomix_integration::DownloadStatus::Downloading(msg) => {
-          self.set_status_message(format!("Downloading repomix: {}", msg));
+        DownloadStatus::Downloading(msg) => {
+          self.notify(StatusLevel::Progress, NotificationSource::Repomix, format!("Downloading repomix: {}", msg));
         }
-        crate::repomix_integration::DownloadStatus::Failed(err) => {
-          self.set_status_message(format!("Repomix download failed: {}", err));
+        DownloadStatus::Failed(err) => {
+          self.notify(StatusLevel::Error, NotificationSource::Repomix, format!("Repomix download failed: {}", err));
         }
-        crate::repomix_integration::DownloadStatus::NotStarted => {
+        DownloadStatus::NotStarted => {
           // restart download if failed
-          self.repomix.as_mut().unwrap().start_background_download().await;
+          repomix.start_background_download().await;
         }
       }
     }
@@ -1103,6 +2050,31 @@ impl App {
     Ok(status_changed)
   }
 
+  /// Picks up yek provisioning progress and returns true if status changed.
+  fn update_yek_download(&mut self) -> bool {
+    let Some(yek) = self.yek.as_mut() else {
+      return false;
+    };
+    if !yek.update() {
+      return false;
+    }
+
+    match yek.download_status().clone() {
+      DownloadStatus::Ready => {
+        let binary = yek.binary_path().map(|path| path.display().to_string()).unwrap_or_default();
+        self.notify(StatusLevel::Success, NotificationSource::Yek, format!("Yek ready ({})", binary));
+      }
+      DownloadStatus::Downloading(msg) => {
+        self.notify(StatusLevel::Progress, NotificationSource::Yek, msg);
+      }
+      DownloadStatus::Failed(err) => {
+        self.notify(StatusLevel::Error, NotificationSource::Yek, format!("Yek setup failed: {}", err));
+      }
+      DownloadStatus::NotStarted => {}
+    }
+    true
+  }
+
   /// Saves the current repomix options to persistent configuration.
   pub fn save_repomix_options(&mut self) -> Result<()> {
     self.config.update_repomix_options(
@@ -1114,6 +2086,11 @@ impl App {
     Ok(())
   }
 
+  /// Saves the current yek options to persistent configuration.
+  pub fn save_yek_options(&mut self) -> Result<()> {
+    self.config.update_yek_options(&self.state.yek_options)
+  }
+
   /// Background task that processes token calculation requests.
   /// Runs independently from the main UI thread, uses shared cache with semaphore concurrency control.
   async fn token_calculation_task(_token_counter: TokenCounter, mut request_receiver: mpsc::UnboundedReceiver<PathBuf>, result_sender: mpsc::UnboundedSender<(PathBuf, usize)>) {
@@ -1148,169 +2125,64 @@ impl App {
   }
 
   /// Background task that handles backend execution requests.
-  /// Runs independently from the main UI thread, supports immediate cancellation.
-  async fn backend_execution_task_lazy(_current_backend: Backend, mut request_receiver: mpsc::UnboundedReceiver<BackendRequest>, result_sender: mpsc::UnboundedSender<BackendResult>) {
+  /// Runs independently from the main UI thread, cancelling a request kills its process group.
+  async fn backend_execution_task_lazy(repomix_config: RepomixConfig, mut request_receiver: mpsc::UnboundedReceiver<BackendRequest>, event_sender: mpsc::UnboundedSender<BackendEvent>) {
     // lazily initialize backends only when needed
-    let mut yek_instance: Option<Arc<Yek>> = None;
     let mut repomix_instance: Option<Arc<Mutex<Repomix>>> = None;
 
     // process requests until the receiver is closed
     while let Some(request) = request_receiver.recv().await {
-      let cancellation_token = request.cancellation_token.clone();
-      let result_sender = result_sender.clone();
+      let event_sender = event_sender.clone();
 
       // initialize the required backend if not already done
       match request.backend {
         Backend::Repomix => {
           if repomix_instance.is_none() {
-            match Repomix::new() {
+            match Repomix::new(&repomix_config) {
               Ok(mut r) => {
                 r.start_background_download().await;
                 repomix_instance = Some(Arc::new(Mutex::new(r)));
               }
               Err(e) => {
-                let _ = result_sender.send(BackendResult {
-                  success: false,
-                  message: String::new(),
-                  output_file: None,
-                  error: Some(format!("Failed to initialize repomix: {}", e)),
-                  request_id: request.request_id,
-                });
-                continue;
-              }
-            }
-          }
-        }
-        Backend::Yek => {
-          if yek_instance.is_none() {
-            match Yek::new() {
-              Ok(y) => {
-                yek_instance = Some(Arc::new(y));
-              }
-              Err(e) => {
-                let _ = result_sender.send(BackendResult {
-                  success: false,
-                  message: String::new(),
-                  output_file: None,
-                  error: Some(format!("Failed to initialize yek: {}", e)),
-                  request_id: request.request_id,
-                });
+                let _ = event_sender.send(BackendEvent::Finished(BackendResult::failed(request.request_id, request.backend.clone(), format!("Failed to initialize repomix: {}", e))));
                 continue;
               }
             }
           }
         }
+        // the yek binary was provisioned by the app and comes with the request, custom commands too
+        Backend::Yek | Backend::Custom(_) => {}
       };
 
-      // clone the instances for the spawned task
+      // clone the instance for the spawned task, the yek binary and custom command come with the request
       let repomix_clone = repomix_instance.clone();
-      let yek_clone = yek_instance.clone();
 
-      // spawn a cancellable task
+      // spawn a task per run, progress is streamed through the monitor
       tokio::spawn(async move {
-        // execute the backend op
-        let result = match request.backend {
-          Backend::Repomix => {
-            if let Some(repomix_arc) = repomix_clone {
-              // run repomix with cancellation support
-              tokio::select! {
-                  result = async {
-                      let mut manager = repomix_arc.lock().await;
-                      manager.run_isolated_repomix(
-                          &request.selected_files,
-                          &request.repomix_options,
-                          &request.root_path,
-                          &request.file_tree,
-                      ).await
-                  } => {
-                      match result {
-                          Ok(output) => BackendResult {
-                              success: true,
-                              message: output,
-                              output_file: request.repomix_options.output_file.map(PathBuf::from),
-                              error: None,
-                              request_id: request.request_id,
-                          },
-                          Err(e) => BackendResult {
-                              success: false,
-                              message: String::new(),
-                              output_file: None,
-                              error: Some(format!("Error: repomix error {}", e)),
-                              request_id: request.request_id,
-                          },
-                      }
-                  }
-                  _ = cancellation_token.cancelled() => {
-                      // operation was cancelled, the process will be killed by the os
-                      // when the parent task is dropped
-                      BackendResult {
-                          success: false,
-                          message: String::new(),
-                          output_file: None,
-                          error: Some("Operation cancelled".to_string()),
-                          request_id: request.request_id,
-                      }
-                  }
-              }
-            } else {
-              BackendResult {
-                success: false,
-                message: String::new(),
-                output_file: None,
-                error: Some("Repomix not initialized".to_string()),
-                request_id: request.request_id,
-              }
-            }
-          }
-          Backend::Yek => {
-            if let Some(yek_arc) = yek_clone {
-              // run yek with cancellation support
-              tokio::select! {
-                  result = yek_arc.run_yek_integrated(&request.selected_files, &request.root_path) => {
-                      match result {
-                          Ok(output) => BackendResult {
-                              success: true,
-                              message: output,
-                              // yek doesn't create output files
-                              output_file: None,
-                              error: None,
-                              request_id: request.request_id,
-                          },
-                          Err(e) => BackendResult {
-                              success: false,
-                              message: String::new(),
-                              output_file: None,
-                              error: Some(format!("Error: yek error {}", e)),
-                              request_id: request.request_id,
-                          },
-                      }
-                  }
-                  _ = cancellation_token.cancelled() => {
-                      // operation was cancelled, the process will be killed by the os
-                      // when the parent task is dropped
-                      BackendResult {
-                          success: false,
-                          message: String::new(),
-                          output_file: None,
-                          error: Some("Operation cancelled".to_string()),
-                          request_id: request.request_id,
-                      }
-                  }
-              }
-            } else {
-              BackendResult {
-                success: false,
-                message: String::new(),
-                output_file: None,
-                error: Some("Yek not initialized".to_string()),
-                request_id: request.request_id,
-              }
+        let monitor = RunMonitor::new(&request, event_sender.clone());
+        let (mut result, content) = crate::packer::run_request(&request, repomix_clone.as_deref(), &monitor).await;
+
+        // copy to clipboard
+        if let Some(content) = content {
+          let copied = async {
+            monitor.check_cancelled()?;
+            monitor.report("Copying to clipboard", None, content.len() as u64);
+            crate::process_runner::copy_to_clipboard(&content).await
+          };
+          match copied.await {
+            Ok(()) => result.message.push_str(" and copied to clipboard"),
+            Err(e) => {
+              let mut failed = BackendResult::failed(request.request_id, request.backend.clone(), format!("Error: {} error {}", request.backend.display_name(), e));
+              failed.flagged = result.flagged;
+              failed.redacted = result.redacted;
+              result = failed;
             }
           }
-        };
+          result.content = Some(content);
+        }
 
-        // send result back to main thread (non-blocking)
-        if result_sender.send(result).is_err() {
+        // send result back to main thread (non-blocking), cancelled results are ignored there
+        if event_sender.send(BackendEvent::Finished(result)).is_err() {
           // main thread has closed, exit
         }
       });
@@ -1362,13 +2234,15 @@ pub fn restore_terminal(_terminal: &mut Terminal<CrosstermBackend<io::Stdout>>)
 }
 
 /// Runs the siff app, sets up terminal, runs the app, and cleans up.
-pub async fn run_app(root_path: &Path, backend: crate::types::Backend) -> Result<()> {
+/// Paths in `initial_selection` are selected once the scan finishes.
+pub async fn run_app(roots: &[PathBuf], backend: crate::types::Backend, api_address: Option<ApiAddress>, initial_selection: Vec<String>) -> Result<()> {
   // setup terminal
   let mut terminal = setup_terminal()?;
 
   // create and run the app
   let result = async {
-    let mut app = App::new(root_path, backend).await?;
+    let mut app = App::new(roots, backend, api_address).await?;
+    app.initial_selection = initial_selection;
 
     // expand root directory (default)
     app.expand_root();
@@ -1383,3 +2257,96 @@ pub async fn run_app(root_path: &Path, backend: crate::types::Backend) -> Result
 
   result
 }
+
+// test for switching backends at runtime
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::custom_backend::CustomBackendConfig;
+
+  fn last_message(app: &App) -> String {
+    app.state.notifications.entries().next_back().map(|notification| notification.message.clone()).unwrap_or_default()
+  }
+
+  fn checked(result: Result<()>) -> oneshot::Receiver<Result<()>> {
+    let (sender, receiver) = oneshot::channel();
+    let _ = sender.send(result);
+    receiver
+  }
+
+  #[tokio::test]
+  async fn test_switch_backend() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let yek_binary = temp_dir.path().join("yek");
+    let mut config = SifConfig::default();
+    config.yek.path = Some(yek_binary.clone());
+    config.custom_backends = vec![CustomBackendConfig {
+      name: "cat".to_string(),
+      command: vec!["sh".to_string(), "-c".to_string(), "cat \"$@\"".to_string(), "sh".to_string(), "{files}".to_string()],
+      files: Default::default(),
+      options: Default::default(),
+      file_marker: None,
+    }];
+    let mut app = App::with_config(&[temp_dir.path().to_path_buf()], Backend::Custom("cat".to_string()), None, config).await.unwrap();
+    let cat = Backend::Custom("cat".to_string());
+
+    // repomix and yek are only initialized once they're switched to
+    assert!(app.repomix.is_none() && app.yek.is_none());
+
+    // repomix is checked in the background, switching again before it's picked up skips past it
+    app.start_repomix_check = |_| checked(Ok(()));
+    app.switch_backend().await;
+    assert!(app.repomix_check.is_some());
+    assert_eq!(app.state.repomix_options.backend, cat);
+    app.switch_backend().await;
+    assert!(app.repomix_check.is_none());
+    assert_eq!(app.state.repomix_options.backend, cat);
+    assert!(last_message(&app).starts_with("Error: can't switch to Yek"));
+
+    // a failed check keeps the current backend
+    app.start_repomix_check = |_| checked(Err(anyhow::anyhow!("Node.js not found")));
+    app.switch_backend().await;
+    assert!(app.update_repomix_check().await);
+    assert!(app.repomix.is_none());
+    assert_eq!(app.state.repomix_options.backend, cat);
+    assert_eq!(last_message(&app), "Error: can't switch to Repomix, Node.js not found");
+
+    // a passed check switches, the install itself is left to the main loop
+    app.start_repomix_check = |_| checked(Ok(()));
+    app.switch_backend().await;
+    assert!(app.update_repomix_check().await);
+    assert!(app.repomix.is_some());
+    assert_eq!(app.state.repomix_options.backend, Backend::Repomix);
+
+    // yek is initialized once its binary exists, custom backends switch right away
+    std::fs::write(&yek_binary, "").unwrap();
+    app.switch_backend().await;
+    assert!(app.yek.is_some());
+    assert_eq!(app.state.repomix_options.backend, Backend::Yek);
+    app.switch_backend().await;
+    assert_eq!(app.state.repomix_options.backend, cat);
+  }
+
+  #[tokio::test]
+  async fn test_directory_tokens_with_several_roots() {
+    let (first, second) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    std::fs::create_dir(first.path().join("src")).unwrap();
+    std::fs::write(first.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(second.path().join("api.proto"), "syntax = \"proto3\";\n").unwrap();
+    let roots = [first.path().to_path_buf(), second.path().to_path_buf()];
+    let mut app = App::with_config(&roots, Backend::Repomix, None, SifConfig::default()).await.unwrap();
+    app.state.file_tree = file_utils::scan_roots(&app.state.roots, &app.config.scan).unwrap();
+
+    // every dir above a selected file adds up its tokens, up to the virtual root
+    let main_rs = first.path().canonicalize().unwrap().join("src/main.rs");
+    file_utils::toggle_selection_recursive(&mut app.state.file_tree, &main_rs).unwrap();
+    app.state.individual_token_counts.insert(main_rs.clone(), Some(5));
+    app.recalculate_directory_token_counts();
+    assert_eq!(app.state.individual_token_counts[main_rs.parent().unwrap()], Some(5));
+    assert_eq!(app.state.individual_token_counts[&first.path().canonicalize().unwrap()], Some(5));
+    assert_eq!(app.state.individual_token_counts[Path::new("")], Some(5));
+    assert!(!app.state.individual_token_counts.contains_key(&second.path().canonicalize().unwrap()));
+  }
+}
@@ -0,0 +1,115 @@
+use crate::keymap::Action;
+use crate::types::Backend;
+
+/// Scores how well a query fuzzy matches the text (characters in order, case insensitive).
+/// Consecutive characters and word starts score higher, returns None if it doesn't match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+  let text: Vec<char> = text.to_lowercase().chars().collect();
+  let mut score = 0;
+  let mut position = 0;
+  let mut previous_match: Option<usize> = None;
+
+  for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+    let offset = text[position..].iter().position(|c| *c == query_char)?;
+    let index = position + offset;
+
+    score += 1;
+    if previous_match.is_some_and(|previous| previous + 1 == index) {
+      score += 5;
+    }
+    if index == 0 || text[index - 1] == ' ' {
+      score += 3;
+    }
+    // small penalty for skipped characters
+    score -= offset.min(3) as i64;
+
+    previous_match = Some(index);
+    position = index + 1;
+  }
+
+  Some(score)
+}
+
+/// State of the command palette while it's open.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPalette {
+  /// Text typed so far
+  pub input: String,
+  /// Actions matching the input, best match first
+  pub matches: Vec<Action>,
+  /// Index of the highlighted match
+  pub selected: usize,
+}
+
+impl CommandPalette {
+  /// Creates a palette listing every action available for the backend.
+  pub fn new(backend: &Backend) -> Self {
+    let mut palette = Self::default();
+    palette.refresh(backend);
+    palette
+  }
+
+  /// Re-filters the actions for the current input and resets the highlight.
+  pub fn refresh(&mut self, backend: &Backend) {
+    let mut scored: Vec<(i64, usize, Action)> = Action::ALL
+      .iter()
+      .enumerate()
+      .filter(|(_, action)| action.is_available(backend) && **action != Action::CommandPalette)
+      .filter_map(|(order, action)| fuzzy_score(&self.input, action.description()).map(|score| (score, order, *action)))
+      .collect();
+
+    // best score first, ties keep the help order
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    self.matches = scored.into_iter().map(|(_, _, action)| action).collect();
+    self.selected = 0;
+  }
+
+  /// Moves the highlight up or down, wrapping around.
+  pub fn move_selection(&mut self, down: bool) {
+    if self.matches.is_empty() {
+      return;
+    }
+    self.selected = if down {
+      (self.selected + 1) % self.matches.len()
+    } else {
+      (self.selected + self.matches.len() - 1) % self.matches.len()
+    };
+  }
+
+  /// Returns the highlighted action.
+  pub fn selected_action(&self) -> Option<Action> {
+    self.matches.get(self.selected).copied()
+  }
+}
+
+// test for fuzzy matching and palette filtering
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fuzzy_score() {
+    assert!(fuzzy_score("cmp", "Toggle compress").is_some());
+    assert!(fuzzy_score("xyz", "Toggle compress").is_none());
+    assert_eq!(fuzzy_score("", "Quit"), Some(0));
+
+    // word starts and consecutive characters rank higher
+    assert!(fuzzy_score("sel", "Select all").unwrap() > fuzzy_score("sel", "Toggle selection").unwrap());
+    assert!(fuzzy_score("ua", "Unselect all").unwrap() > fuzzy_score("ua", "Quit").unwrap_or(i64::MIN));
+  }
+
+  #[test]
+  fn test_palette_filters_actions() {
+    let mut palette = CommandPalette::new(&Backend::Yek);
+    assert!(!palette.matches.contains(&Action::ToggleCompress));
+    assert!(!palette.matches.contains(&Action::CommandPalette));
+
+    palette.input = "unsel".to_string();
+    palette.refresh(&Backend::Repomix);
+    assert_eq!(palette.selected_action(), Some(Action::UnselectAll));
+
+    palette.move_selection(false);
+    assert_eq!(palette.selected, palette.matches.len() - 1);
+  }
+}
@@ -1,4 +1,13 @@
-use crate::types::{Backend, OutputFormat};
+use crate::api::ApiConfig;
+use crate::custom_backend::CustomBackendConfig;
+use crate::file_utils::ScanOptions;
+use crate::keymap::KeymapConfig;
+use crate::redaction::RedactionRule;
+use crate::repomix_integration::RepomixConfig;
+use crate::secrets::SecretsConfig;
+use crate::theme::ThemeName;
+use crate::types::{Backend, OutputFormat, RepomixOptions, SortMode, YekOptions};
+use crate::yek_integration::YekConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -18,6 +27,39 @@ pub struct SifConfig {
   pub output_format: OutputFormat,
   /// last used backend
   pub default_backend: Backend,
+  /// sort mode for the file tree
+  #[serde(default)]
+  pub sort_mode: SortMode,
+  /// keybindings (preset plus per action overrides)
+  #[serde(default)]
+  pub keymap: KeymapConfig,
+  /// color theme (NO_COLOR overrides it with monochrome)
+  #[serde(default)]
+  pub theme: ThemeName,
+  /// repomix version and install source
+  #[serde(default)]
+  pub repomix: RepomixConfig,
+  /// yek version pin and binary path
+  #[serde(default)]
+  pub yek: YekConfig,
+  /// options passed to yek
+  #[serde(default)]
+  pub yek_options: YekOptions,
+  /// packers run as external commands
+  #[serde(default)]
+  pub custom_backends: Vec<CustomBackendConfig>,
+  /// secret scanning of the selection before packing
+  #[serde(default)]
+  pub secrets: SecretsConfig,
+  /// text masked in packed files for every backend
+  #[serde(default)]
+  pub redaction_rules: Vec<RedactionRule>,
+  /// local JSON API for editor plugins
+  #[serde(default)]
+  pub api: ApiConfig,
+  /// how directories are scanned (symlinks)
+  #[serde(default)]
+  pub scan: ScanOptions,
 }
 
 impl Default for SifConfig {
@@ -26,9 +68,20 @@ impl Default for SifConfig {
     Self {
       compress: false,
       remove_comments: false,
-      include_file_tree: false,
+      include_file_tree: true,
       output_format: OutputFormat::Xml,
       default_backend: Backend::Repomix,
+      sort_mode: SortMode::Name,
+      keymap: KeymapConfig::default(),
+      theme: ThemeName::Dark,
+      repomix: RepomixConfig::default(),
+      yek: YekConfig::default(),
+      yek_options: YekOptions::default(),
+      custom_backends: Vec::new(),
+      secrets: SecretsConfig::default(),
+      redaction_rules: Vec::new(),
+      api: ApiConfig::default(),
+      scan: ScanOptions::default(),
     }
   }
 }
@@ -80,6 +133,24 @@ impl SifConfig {
     self.output_format = output_format;
     self.save()
   }
+
+  /// Returns the saved repomix options for a run with the given backend.
+  pub fn repomix_options(&self, backend: Backend) -> RepomixOptions {
+    RepomixOptions {
+      backend,
+      compress: self.compress,
+      remove_comments: self.remove_comments,
+      file_tree: self.include_file_tree,
+      output_format: self.output_format.clone(),
+      output_file: None, // output file is not persisted (for file tree)
+    }
+  }
+
+  /// Updates the config with new yek options and saves.
+  pub fn update_yek_options(&mut self, yek_options: &YekOptions) -> Result<()> {
+    self.yek_options = yek_options.clone();
+    self.save()
+  }
 }
 
 /// Gets the path to the siff config file.
@@ -95,6 +166,10 @@ fn get_config_path() -> Result<PathBuf> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::custom_backend::FileListMode;
+  use crate::repomix_integration::{RepomixSource, DEFAULT_REPOMIX_VERSION};
+  use crate::secrets::SecretPolicy;
+  use crate::types::{YekLimit, YekPriorityRule};
 
   #[test]
   fn test_config_serialization() {
@@ -104,6 +179,49 @@ mod tests {
       include_file_tree: true,
       output_format: OutputFormat::Markdown,
       default_backend: Backend::Yek,
+      sort_mode: SortMode::Size,
+      keymap: KeymapConfig::default(),
+      theme: ThemeName::ColorblindSafe,
+      repomix: RepomixConfig {
+        version: "0.4.0".to_string(),
+        source: RepomixSource::Tarball(PathBuf::from("/opt/repomix-0.4.0.tgz")),
+      },
+      yek: YekConfig {
+        version: Some("0.21.0".to_string()),
+        path: None,
+      },
+      yek_options: YekOptions {
+        limit: YekLimit::Tokens(128_000),
+        priority_rules: vec![YekPriorityRule { pattern: "^src/".to_string(), score: 100 }],
+        ..YekOptions::default()
+      },
+      custom_backends: vec![CustomBackendConfig {
+        name: "packer".to_string(),
+        command: vec!["packer".to_string(), "--root".to_string(), "{root}".to_string()],
+        files: FileListMode::Stdin,
+        options: Default::default(),
+        file_marker: None,
+      }],
+      secrets: SecretsConfig {
+        policy: SecretPolicy::Redact,
+        allow: vec!["tests/fixtures/**".to_string()],
+      },
+      redaction_rules: vec![RedactionRule {
+        name: "host".to_string(),
+        pattern: "db.corp.internal".to_string(),
+        regex: false,
+        ignore_case: false,
+        replacement: None,
+      }],
+      api: ApiConfig {
+        listen: Some("unix:/tmp/siff.sock".to_string()),
+      },
+      scan: ScanOptions {
+        follow_symlinks: true,
+        show_hidden: false,
+        always_shown: vec![".gitignore".to_string()],
+        ..Default::default()
+      },
     };
 
     // test serialization
@@ -118,6 +236,43 @@ mod tests {
     assert!(deserialized.include_file_tree);
     assert_eq!(deserialized.output_format, OutputFormat::Markdown);
     assert_eq!(deserialized.default_backend, Backend::Yek);
+    assert_eq!(deserialized.sort_mode, SortMode::Size);
+    assert_eq!(deserialized.theme, ThemeName::ColorblindSafe);
+    assert_eq!(deserialized.repomix.source, RepomixSource::Tarball(PathBuf::from("/opt/repomix-0.4.0.tgz")));
+    assert_eq!(deserialized.yek.version.as_deref(), Some("0.21.0"));
+    assert_eq!(deserialized.yek_options.limit, YekLimit::Tokens(128_000));
+    assert_eq!(deserialized.yek_options.priority_rules[0].score, 100);
+    assert_eq!(deserialized.custom_backends[0].files, FileListMode::Stdin);
+    assert_eq!(deserialized.secrets.policy, SecretPolicy::Redact);
+    assert_eq!(deserialized.redaction_rules[0].name, "host");
+    assert_eq!(deserialized.api.listen.as_deref(), Some("unix:/tmp/siff.sock"));
+    assert!(deserialized.scan.follow_symlinks && !deserialized.scan.show_hidden);
+    assert_eq!(deserialized.scan.always_shown, vec![".gitignore"]);
+  }
+
+  #[test]
+  fn test_config_missing_new_fields() {
+    // configs saved by older versions don't have newer fields
+    let json = r#"{"compress":false,"remove_comments":true,"include_file_tree":false,"output_format":"Xml","default_backend":"Repomix"}"#;
+    let config: SifConfig = serde_json::from_str(json).unwrap();
+    assert!(config.remove_comments);
+    assert_eq!(config.sort_mode, SortMode::Name);
+    assert_eq!(config.keymap, KeymapConfig::default());
+    assert_eq!(config.theme, ThemeName::Dark);
+    assert_eq!(config.repomix, RepomixConfig::default());
+    assert_eq!(config.yek, YekConfig::default());
+    assert_eq!(config.yek_options, YekOptions::default());
+    assert!(config.custom_backends.is_empty());
+    assert_eq!(config.secrets.policy, SecretPolicy::Block);
+    assert!(config.redaction_rules.is_empty());
+    assert_eq!(config.api, ApiConfig::default());
+    assert_eq!(config.scan, ScanOptions::default());
+
+    // a partial repomix section keeps the other defaults
+    let json = r#"{"compress":false,"remove_comments":true,"include_file_tree":false,"output_format":"Xml","default_backend":"Repomix","repomix":{"source":"system"}}"#;
+    let config: SifConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(config.repomix.source, RepomixSource::System);
+    assert_eq!(config.repomix.version, DEFAULT_REPOMIX_VERSION);
   }
 
   #[test]
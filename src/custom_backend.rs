@@ -0,0 +1,248 @@
+use crate::process_runner::{self, RunMonitor};
+use crate::types::BackendOutput;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+/// Placeholders filled in by siff, options can't use these names.
+const RESERVED_PLACEHOLDERS: [&str; 3] = ["root", "files", "file_list"];
+
+/// How the selected files are handed to a custom command.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FileListMode {
+  /// One argument per file, in place of the `{files}` argument
+  #[default]
+  Args,
+  /// NUL-separated list on stdin
+  Stdin,
+  /// Newline-separated list in a temp file, its path replaces `{file_list}`
+  TempFile,
+}
+
+impl FileListMode {
+  /// Returns the display name for the mode.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      FileListMode::Args => "args",
+      FileListMode::Stdin => "stdin",
+      FileListMode::TempFile => "temp file",
+    }
+  }
+}
+
+/// Packer defined in the config file, run with the selected files and its stdout used as the pack.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CustomBackendConfig {
+  /// Name shown in the UI and used to select the backend
+  pub name: String,
+  /// Program and arguments, with `{root}`, `{files}`, `{file_list}` and `{option}` placeholders
+  pub command: Vec<String>,
+  /// How the selected files are passed
+  #[serde(default)]
+  pub files: FileListMode,
+  /// Values for the option placeholders in the command
+  #[serde(default)]
+  pub options: BTreeMap<String, String>,
+  /// Prefix of the per-file header in the output, counted for progress
+  #[serde(default)]
+  pub file_marker: Option<String>,
+}
+
+impl CustomBackendConfig {
+  /// Checks that the command is complete and every placeholder can be filled in.
+  pub fn validate(&self) -> Result<()> {
+    if self.name.trim().is_empty() {
+      anyhow::bail!("Custom backend name can't be empty");
+    }
+    if ["repomix", "yek"].contains(&self.name.to_lowercase().as_str()) {
+      anyhow::bail!("Custom backend can't be named '{}'", self.name);
+    }
+    let Some(program) = self.command.first() else {
+      anyhow::bail!("Custom backend '{}' has no command", self.name);
+    };
+    if program.contains('{') {
+      anyhow::bail!("Custom backend '{}' program can't be a placeholder", self.name);
+    }
+
+    for name in self.options.keys() {
+      if RESERVED_PLACEHOLDERS.contains(&name.as_str()) {
+        anyhow::bail!("Custom backend option '{}' is reserved", name);
+      }
+    }
+
+    let mut uses_files = false;
+    let mut uses_file_list = false;
+    for argument in &self.command {
+      for captures in placeholder_regex().captures_iter(argument) {
+        match &captures[1] {
+          "root" => {}
+          "files" if argument == "{files}" => uses_files = true,
+          "files" => anyhow::bail!("{{files}} must be an argument of its own"),
+          "file_list" => uses_file_list = true,
+          name if self.options.contains_key(name) => {}
+          name => anyhow::bail!("Unknown placeholder {{{}}} in custom backend '{}'", name, self.name),
+        }
+      }
+    }
+
+    match self.files {
+      FileListMode::Args if !uses_files => anyhow::bail!("Custom backend '{}' passes files as args but has no {{files}} argument", self.name),
+      FileListMode::TempFile if !uses_file_list => anyhow::bail!("Custom backend '{}' passes a file list but has no {{file_list}} placeholder", self.name),
+      FileListMode::Stdin | FileListMode::TempFile if uses_files => anyhow::bail!("{{files}} is only available when files are passed as args"),
+      FileListMode::Args | FileListMode::Stdin if uses_file_list => anyhow::bail!("{{file_list}} is only available when files are passed as a temp file"),
+      _ => Ok(()),
+    }
+  }
+
+  /// Checks the config and that the program can be found, without running it.
+  pub fn check_available(&self) -> Result<()> {
+    self.validate()?;
+    let program = &self.command[0];
+    let found = if program.contains(std::path::MAIN_SEPARATOR) {
+      Path::new(program).is_file()
+    } else {
+      process_runner::find_executable(program).is_some()
+    };
+    if !found {
+      anyhow::bail!("Command '{}' for custom backend '{}' not found", program, self.name);
+    }
+    Ok(())
+  }
+
+  /// Returns the command with placeholders replaced, files are relative to the root.
+  fn expand(&self, files: &[String], root_path: &Path, file_list: Option<&Path>) -> Vec<String> {
+    let mut arguments = Vec::new();
+    for argument in &self.command {
+      if argument == "{files}" {
+        arguments.extend(files.iter().cloned());
+        continue;
+      }
+      let expanded = placeholder_regex().replace_all(argument, |captures: &regex::Captures| match &captures[1] {
+        "root" => root_path.to_string_lossy().to_string(),
+        "file_list" => file_list.map(|path| path.to_string_lossy().to_string()).unwrap_or_default(),
+        name => self.options.get(name).cloned().unwrap_or_default(),
+      });
+      arguments.push(expanded.to_string());
+    }
+    arguments
+  }
+
+  /// Runs the command with the selected files, its stdout is the packed content.
+  pub async fn run(&self, selected_files: &[PathBuf], root_path: &Path, monitor: &RunMonitor) -> Result<BackendOutput> {
+    self.validate()?;
+    let mut output = BackendOutput::default();
+
+    // pass paths relative to the root, the command runs there
+    let mut files = Vec::new();
+    for file_path in selected_files {
+      match file_path.strip_prefix(root_path) {
+        Ok(relative_path) => files.push(relative_path.to_string_lossy().to_string()),
+        Err(_) => output.warnings.push(format!("Skipping file outside root directory: {}", file_path.display())),
+      }
+    }
+    if files.is_empty() {
+      anyhow::bail!("No valid files to process");
+    }
+
+    // file lists and anything the command writes besides stdout go to a private temp dir
+    let temp_dir = tempfile::Builder::new().prefix("siff-custom-").tempdir().context("Failed to create temporary directory for custom backend")?;
+    let list_path = temp_dir.path().join("file-list");
+    let (file_list, input_file) = match self.files {
+      FileListMode::Args => (None, None),
+      FileListMode::Stdin => {
+        let list: String = files.iter().map(|file| format!("{}\0", file)).collect();
+        std::fs::write(&list_path, list).context("Failed to write file list")?;
+        (None, Some(list_path.as_path()))
+      }
+      FileListMode::TempFile => {
+        std::fs::write(&list_path, files.join("\n") + "\n").context("Failed to write file list")?;
+        (Some(list_path.as_path()), None)
+      }
+    };
+
+    let arguments = self.expand(&files, root_path, file_list);
+    let mut command = Command::new(&arguments[0]);
+    command.args(&arguments[1..]).current_dir(root_path).env("TMPDIR", temp_dir.path()).env("TEMP", temp_dir.path()).env("TMP", temp_dir.path());
+    let command_output = process_runner::run_monitored(command, monitor, "Packing", self.file_marker.as_deref(), None, input_file)
+      .await
+      .with_context(|| format!("Failed to execute {}", arguments[0]))?;
+
+    output.stderr = String::from_utf8_lossy(&command_output.stderr).trim().to_string();
+    if !command_output.status.success() {
+      anyhow::bail!("{} failed with exit code {}: {}", self.name, command_output.status.code().unwrap_or(-1), output.stderr);
+    }
+    output.content = String::from_utf8_lossy(&command_output.stdout).to_string();
+    if output.content.trim().is_empty() {
+      anyhow::bail!("{} produced no output", self.name);
+    }
+
+    output.message = format!("{} files processed with {}", files.len(), self.name);
+    Ok(output)
+  }
+}
+
+/// Matches `{name}` placeholders in command arguments.
+fn placeholder_regex() -> &'static Regex {
+  static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+  PLACEHOLDER.get_or_init(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_]*)\}").expect("valid placeholder regex"))
+}
+
+/// Finds a custom backend by name.
+pub fn find<'a>(backends: &'a [CustomBackendConfig], name: &str) -> Option<&'a CustomBackendConfig> {
+  backends.iter().find(|backend| backend.name == name)
+}
+
+// test for custom backend placeholders
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn backend(command: &[&str], files: FileListMode) -> CustomBackendConfig {
+    CustomBackendConfig {
+      name: "packer".to_string(),
+      command: command.iter().map(ToString::to_string).collect(),
+      files,
+      options: BTreeMap::from([("style".to_string(), "compact".to_string())]),
+      file_marker: None,
+    }
+  }
+
+  #[test]
+  fn test_custom_backend_placeholders() {
+    let config = backend(&["packer", "--root={root}", "--style", "{style}", "{files}"], FileListMode::Args);
+    assert!(config.validate().is_ok());
+    let files = ["a.rs".to_string(), "b c.rs".to_string()];
+    assert_eq!(config.expand(&files, Path::new("/repo"), None), ["packer", "--root=/repo", "--style", "compact", "a.rs", "b c.rs"]);
+
+    // placeholders have to match the file list mode and the options
+    assert!(backend(&["packer", "{file_list}"], FileListMode::TempFile).validate().is_ok());
+    assert!(backend(&["packer"], FileListMode::Stdin).validate().is_ok());
+    assert!(backend(&["packer"], FileListMode::Args).validate().is_err());
+    assert!(backend(&["packer", "{files}"], FileListMode::Stdin).validate().is_err());
+    assert!(backend(&["packer", "--files={files}"], FileListMode::Args).validate().is_err());
+    assert!(backend(&["packer", "{unknown}", "{files}"], FileListMode::Args).validate().is_err());
+    assert!(backend(&[], FileListMode::Stdin).validate().is_err());
+
+    let config = backend(&["packer", "--list={file_list}"], FileListMode::TempFile);
+    assert_eq!(config.expand(&files, Path::new("/repo"), Some(Path::new("/tmp/list"))), ["packer", "--list=/tmp/list"]);
+  }
+
+  #[test]
+  fn test_switching_through_custom_backends() {
+    use crate::types::Backend;
+
+    let names = ["packer".to_string(), "bundler".to_string()];
+    assert_eq!(Backend::Yek.next(&names), Backend::Custom("packer".to_string()));
+    assert_eq!(Backend::Custom("packer".to_string()).next(&names), Backend::Custom("bundler".to_string()));
+    assert_eq!(Backend::Custom("bundler".to_string()).next(&names), Backend::Repomix);
+    // a backend removed from the config goes back to the start
+    assert_eq!(Backend::Custom("gone".to_string()).next(&names), Backend::Repomix);
+    assert_eq!(Backend::Yek.next(&[]), Backend::Repomix);
+  }
+}
@@ -0,0 +1,164 @@
+use crate::types::FileContent;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file to tell text from binary.
+const SNIFF_LEN: usize = 8192;
+
+/// Extensions that always hold text, files with them aren't sniffed while scanning.
+const TEXT_EXTENSIONS: &[&str] = &[
+  "rs", "py", "js", "mjs", "cjs", "ts", "tsx", "jsx", "go", "java", "kt", "kts", "scala", "c", "h", "cc", "cpp", "hpp", "cs", "rb", "php", "swift", "m", "dart", "lua", "pl", "r", "ex", "exs", "erl", "hs", "ml", "clj", "zig", "sh", "bash",
+  "zsh", "fish", "ps1", "sql", "md", "rst", "txt", "toml", "yaml", "yml", "json", "xml", "html", "css", "scss", "vue", "svelte", "proto", "graphql", "ini", "cfg", "conf", "lock", "csv", "tex", "tf", "gradle", "cmake",
+];
+
+/// Text encodings siff can decode, everything is converted to UTF-8 before packing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+  Utf8,
+  Utf16Le,
+  Utf16Be,
+  Latin1,
+}
+
+/// Detects the encoding of text content from its first bytes.
+/// Returns None if the content looks binary (NUL bytes or many control characters).
+pub fn detect(bytes: &[u8]) -> Option<Encoding> {
+  let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+
+  // byte order marks are the only reliable signal
+  if sample.starts_with(&[0xEF, 0xBB, 0xBF]) {
+    return Some(Encoding::Utf8);
+  }
+  if sample.starts_with(&[0xFF, 0xFE]) {
+    return Some(Encoding::Utf16Le);
+  }
+  if sample.starts_with(&[0xFE, 0xFF]) {
+    return Some(Encoding::Utf16Be);
+  }
+
+  // UTF-16 without a BOM, mostly ASCII text has a NUL in every other byte
+  if let Some(encoding) = detect_utf16(sample) {
+    return Some(encoding);
+  }
+
+  if sample.contains(&0) {
+    return None;
+  }
+  let control = sample.iter().filter(|&&byte| (byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)) || byte == 0x7F).count();
+  if control * 10 > sample.len() {
+    return None;
+  }
+
+  // a sample can end in the middle of a character
+  match std::str::from_utf8(sample) {
+    Ok(_) => Some(Encoding::Utf8),
+    Err(e) if e.error_len().is_none() => Some(Encoding::Utf8),
+    Err(_) => Some(Encoding::Latin1),
+  }
+}
+
+/// Detects BOM-less UTF-16 by where the NUL bytes are.
+fn detect_utf16(sample: &[u8]) -> Option<Encoding> {
+  let pairs = sample.len() / 2;
+  if pairs < 4 {
+    return None;
+  }
+  let even_nuls = sample.iter().step_by(2).take(pairs).filter(|&&byte| byte == 0).count();
+  let odd_nuls = sample.iter().skip(1).step_by(2).filter(|&&byte| byte == 0).count();
+  if odd_nuls * 10 >= pairs * 9 && even_nuls * 10 <= pairs {
+    Some(Encoding::Utf16Le)
+  } else if even_nuls * 10 >= pairs * 9 && odd_nuls * 10 <= pairs {
+    Some(Encoding::Utf16Be)
+  } else {
+    None
+  }
+}
+
+/// Decodes file content to a string, with the encoding it was in.
+/// Returns None for binary content. Invalid sequences are replaced, not rejected.
+pub fn decode(bytes: &[u8]) -> Option<(String, Encoding)> {
+  let encoding = detect(bytes)?;
+  let text = match encoding {
+    Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+    Encoding::Utf16Le | Encoding::Utf16Be => {
+      let body = if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) { &bytes[2..] } else { bytes };
+      let units = body.chunks_exact(2).map(|pair| {
+        if encoding == Encoding::Utf16Le {
+          u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+          u16::from_be_bytes([pair[0], pair[1]])
+        }
+      });
+      char::decode_utf16(units).map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+    }
+    // every byte is the code point of the same value
+    Encoding::Latin1 => bytes.iter().map(|&byte| char::from(byte)).collect(),
+  };
+  Some((text, encoding))
+}
+
+/// Reads the start of a file, enough to detect its encoding.
+pub fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+  let mut sample = Vec::with_capacity(SNIFF_LEN);
+  std::fs::File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut sample)?;
+  Ok(sample)
+}
+
+/// Sniffs the start of a file to tell text from binary, without reading all of it.
+/// Files with a well known text extension aren't read at all, which keeps scans of large repos fast.
+pub fn sniff_file(path: &Path) -> FileContent {
+  let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+  if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+    return FileContent::Text;
+  }
+  match read_head(path) {
+    Err(_) => FileContent::Unreadable,
+    Ok(sample) if detect(&sample).is_none() => FileContent::Binary,
+    Ok(_) => FileContent::Text,
+  }
+}
+
+// test for binary and encoding detection
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_detect_binary() {
+    assert_eq!(detect(b""), Some(Encoding::Utf8));
+    assert_eq!(detect("fn main() {}\n// héllo\n".as_bytes()), Some(Encoding::Utf8));
+    // png, zip (jar) and sqlite headers
+    assert_eq!(detect(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x01\x00"), None);
+    assert_eq!(detect(b"PK\x03\x04\x14\x00\x08\x08\x08\x00META-INF/MANIFEST.MF"), None);
+    assert_eq!(detect(b"SQLite format 3\x00\x10\x00\x01\x01\x00@  "), None);
+    assert_eq!(detect(b"\x01\x02\x03\x04\x05\x06abc"), None);
+    // a sample cut in the middle of a character is still UTF-8
+    let text = "é".repeat(SNIFF_LEN);
+    assert_eq!(detect(&text.as_bytes()[..SNIFF_LEN + 1]), Some(Encoding::Utf8));
+  }
+
+  #[test]
+  fn test_decode() {
+    let utf16_le: Vec<u8> = [0xFF, 0xFE].into_iter().chain("let x = 1; // ü".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+    assert_eq!(decode(&utf16_le), Some(("let x = 1; // ü".to_string(), Encoding::Utf16Le)));
+
+    // no BOM, found by the NUL bytes
+    let utf16_be: Vec<u8> = "SELECT 1;".encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert_eq!(decode(&utf16_be), Some(("SELECT 1;".to_string(), Encoding::Utf16Be)));
+
+    assert_eq!(decode(b"caf\xe9 cr\xe8me"), Some(("café crème".to_string(), Encoding::Latin1)));
+    assert_eq!(decode(b"\x00\x01binary\x00"), None);
+  }
+
+  #[test]
+  fn test_sniff_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("data.bin"), b"\x00\x01\x02\x03").unwrap();
+    assert_eq!(sniff_file(&temp_dir.path().join("data.bin")), FileContent::Binary);
+    assert_eq!(sniff_file(&temp_dir.path().join("missing")), FileContent::Unreadable);
+
+    // known text extensions aren't read
+    assert_eq!(sniff_file(&temp_dir.path().join("missing.rs")), FileContent::Text);
+  }
+}
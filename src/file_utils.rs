@@ -1,146 +1,700 @@
-use crate::types::{FileNode, ScanResult};
-use anyhow::Result;
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use crate::encoding;
+use crate::types::{FileNode, ScanEvent, SelectionState, SortMode};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use tokio::sync::mpsc;
+
+// global registry of files siff wrote itself, so they never end up in its own input
+static PRODUCED_FILES: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+/// Names hidden unless configured otherwise: the git dir and large build and dependency dirs
+/// that contain thousands of generated files users don't want to process.
+const DEFAULT_ALWAYS_HIDDEN: [&str; 24] = [
+  ".git",
+  ".gitignore",
+  "target",
+  "node_modules",
+  "build",
+  "dist",
+  ".next",
+  ".nuxt",
+  "__pycache__",
+  ".pytest_cache",
+  ".mypy_cache",
+  ".tox",
+  "venv",
+  ".venv",
+  "env",
+  ".env",
+  "coverage",
+  ".coverage",
+  "tmp",
+  "temp",
+  ".tmp",
+  "logs",
+  ".DS_Store",
+  "Thumbs.db",
+];
+
+/// Options for scanning directories.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanOptions {
+  /// Walk into symlinked dirs (links that loop back are shown but not walked)
+  #[serde(default)]
+  pub follow_symlinks: bool,
+  /// Show dotfiles and dot dirs
+  #[serde(default = "default_show_hidden")]
+  pub show_hidden: bool,
+  /// Names that are never shown or walked into (case insensitive)
+  #[serde(default = "default_always_hidden")]
+  pub always_hidden: Vec<String>,
+  /// Names that are always shown, even if hidden by the toggle or `always_hidden`
+  #[serde(default)]
+  pub always_shown: Vec<String>,
+}
+
+impl Default for ScanOptions {
+  fn default() -> Self {
+    Self {
+      follow_symlinks: false,
+      show_hidden: default_show_hidden(),
+      always_hidden: default_always_hidden(),
+      always_shown: Vec::new(),
+    }
+  }
+}
+
+/// Name of the file in a root that adds scan rules for that root.
+pub const ROOT_RULES_FILE: &str = ".siff.json";
+
+/// Scan rules from a root's `.siff.json`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RootRules {
+  /// Added to the config file's scan options
+  scan: RootScanRules,
+}
+
+/// Scan options a root changes, names are added to the config file's lists.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RootScanRules {
+  /// Overrides `follow_symlinks` when set
+  follow_symlinks: Option<bool>,
+  /// More names that are never shown in this root
+  always_hidden: Vec<String>,
+  /// More names that are always shown in this root
+  always_shown: Vec<String>,
+}
+
+impl ScanOptions {
+  /// Returns the options for a root, with the rules from its `.siff.json` added.
+  pub fn for_root(&self, root: &Path) -> Result<ScanOptions> {
+    let rules_path = root.join(ROOT_RULES_FILE);
+    let content = match std::fs::read_to_string(&rules_path) {
+      Ok(content) => content,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self.clone()),
+      Err(e) => return Err(e).with_context(|| format!("Failed to read {}", rules_path.display())),
+    };
+    let rules: RootRules = serde_json::from_str(&content).with_context(|| format!("Invalid {}", rules_path.display()))?;
+
+    let mut options = self.clone();
+    options.follow_symlinks = rules.scan.follow_symlinks.unwrap_or(options.follow_symlinks);
+    options.always_hidden.extend(rules.scan.always_hidden);
+    options.always_shown.extend(rules.scan.always_shown);
+    Ok(options)
+  }
+}
+
+/// Dotfiles are shown unless toggled off.
+fn default_show_hidden() -> bool {
+  true
+}
+
+/// Returns the built-in always hidden names.
+fn default_always_hidden() -> Vec<String> {
+  DEFAULT_ALWAYS_HIDDEN.iter().map(ToString::to_string).collect()
+}
 
 /// Scans a directory and builds a complete file tree.
-/// Walks through all files and dirs recursively.
+/// Blocks until the whole tree is scanned, the TUI uses the streaming scanner instead.
 /// Creates a flat hashmap for efficient lookups.
-pub fn scan_directory(root_path: &Path) -> ScanResult {
+#[cfg(test)]
+pub fn scan_directory(root_path: &Path) -> Result<HashMap<PathBuf, FileNode>> {
+  scan_roots(&SessionRoots::single(root_path), &ScanOptions::default())
+}
+
+/// Scans the session's roots into one tree, failing if a root's `.siff.json` is invalid.
+pub fn scan_roots(roots: &SessionRoots, options: &ScanOptions) -> Result<HashMap<PathBuf, FileNode>> {
+  if let Some((_, root_path)) = roots.iter().find(|(_, root)| !root.is_dir()) {
+    return Err(anyhow::anyhow!("Not a directory: {}", root_path.display()));
+  }
+
+  let (sender, mut receiver) = mpsc::unbounded_channel();
+  scan_roots_streaming(roots, options, sender);
+
+  // merge all batches, parents always arrive before their children
   let mut file_tree = HashMap::new();
+  while let Ok(event) = receiver.try_recv() {
+    match event {
+      ScanEvent::Nodes(nodes) => {
+        merge_scanned_nodes(&mut file_tree, nodes);
+      }
+      ScanEvent::Warning(message) => anyhow::bail!(message),
+      ScanEvent::Finished { .. } => {}
+    }
+  }
+
+  // sort children for consistent display order
+  sort_children(&mut file_tree, SortMode::Name, &HashMap::new());
+  mark_link_targets(&mut file_tree, roots);
+
+  Ok(file_tree)
+}
+
+/// Dir waiting to be read by the scanner.
+struct PendingDir {
+  /// Path of the dir in the tree
+  path: PathBuf,
+  /// Depth of the dir in the tree
+  depth: usize,
+  /// Real location when the dir was reached through a followed symlink
+  real_path: Option<PathBuf>,
+  /// Real dirs holding the symlinks followed to get here, a link to any of them (or above) would loop
+  link_dirs: Arc<Vec<PathBuf>>,
+  /// Scan options of the root the dir is in
+  options: Arc<ScanOptions>,
+}
+
+/// Shared work queue for the parallel directory scanner.
+struct ScanQueue {
+  /// Queue state guarded by a mutex
+  state: Mutex<ScanQueueState>,
+  /// Signalled whenever dirs are queued or a worker finishes
+  ready: Condvar,
+}
+
+/// Dirs waiting to be read and bookkeeping for termination.
+struct ScanQueueState {
+  /// Dirs that still need to be read
+  pending_dirs: Vec<PendingDir>,
+  /// Number of workers currently reading a dir
+  active_workers: usize,
+  /// Number of dirs that couldn't be read (permissions issues)
+  unreadable_dirs: usize,
+  /// Set when the receiver is gone and scanning should stop
+  cancelled: bool,
+}
+
+/// Scans the roots with parallel walkers and streams batches of nodes to the sender.
+/// Each batch holds the entries of one dir, sent before any of its subdirs are read,
+/// so parents always arrive before their children. Blocks until the scan is done.
+/// Several roots are top-level nodes under a virtual root, a single root is the top node itself.
+/// Each root is scanned with its own `.siff.json` rules, an invalid file is reported and the config file's rules are used.
+pub fn scan_roots_streaming(roots: &SessionRoots, options: &ScanOptions, sender: mpsc::UnboundedSender<ScanEvent>) {
+  // root nodes first, the rest of the tree hangs off them
+  let session_root = roots.root_path();
+  let mut root_nodes = vec![FileNode::new(session_root.clone(), true, 0)];
+  let mut pending_dirs = Vec::with_capacity(roots.len());
+  for (name, root) in roots.iter() {
+    let root_options = options.for_root(root).unwrap_or_else(|e| {
+      let _ = sender.send(ScanEvent::Warning(format!("{:#}, using the scan options from the config file", e)));
+      options.clone()
+    });
+    let depth = if root == session_root { 0 } else { 1 };
+    if depth == 1 {
+      // named like the prefix of their paths
+      let mut node = FileNode::new(root.to_path_buf(), true, 1);
+      node.name = name.to_string();
+      root_nodes.push(node);
+    }
+    pending_dirs.push(PendingDir {
+      path: root.to_path_buf(),
+      depth,
+      real_path: None,
+      link_dirs: Arc::default(),
+      options: Arc::new(root_options),
+    });
+  }
+  if sender.send(ScanEvent::Nodes(root_nodes)).is_err() {
+    return;
+  }
+
+  let queue = ScanQueue {
+    state: Mutex::new(ScanQueueState {
+      pending_dirs,
+      active_workers: 0,
+      unreadable_dirs: 0,
+      cancelled: false,
+    }),
+    ready: Condvar::new(),
+  };
+
+  // io bound, so a few more walkers than cores is fine
+  let worker_count = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4).clamp(2, 16);
+
+  std::thread::scope(|scope| {
+    for _ in 0..worker_count {
+      scope.spawn(|| scan_worker(&queue, &sender));
+    }
+  });
+
+  let unreadable_dirs = queue.state.lock().map(|state| state.unreadable_dirs).unwrap_or(0);
+  let _ = sender.send(ScanEvent::Finished { unreadable_dirs });
+}
+
+/// Worker loop for the parallel scanner.
+/// Pops dirs from the shared queue until no dirs are left and no worker is busy.
+fn scan_worker(queue: &ScanQueue, sender: &mpsc::UnboundedSender<ScanEvent>) {
+  loop {
+    // wait for a dir to read, or exit when all work is done
+    let dir = {
+      let Ok(mut state) = queue.state.lock() else {
+        return;
+      };
+      loop {
+        if state.cancelled {
+          return;
+        }
+        if let Some(next) = state.pending_dirs.pop() {
+          state.active_workers += 1;
+          break next;
+        }
+        if state.active_workers == 0 {
+          queue.ready.notify_all();
+          return;
+        }
+        state = match queue.ready.wait(state) {
+          Ok(state) => state,
+          Err(_) => return,
+        };
+      }
+    };
+
+    let entries = read_directory_entries(&dir);
+
+    // send the batch before queuing subdirs so parents arrive first
+    let (subdirs, send_failed, unreadable) = match entries {
+      Some((nodes, subdirs)) => {
+        let send_failed = !nodes.is_empty() && sender.send(ScanEvent::Nodes(nodes)).is_err();
+        (subdirs, send_failed, false)
+      }
+      None => (Vec::new(), false, true),
+    };
+
+    let Ok(mut state) = queue.state.lock() else {
+      return;
+    };
+    if send_failed {
+      // receiver dropped (app quit), stop all workers
+      state.cancelled = true;
+    }
+    if unreadable {
+      state.unreadable_dirs += 1;
+    }
+    state.pending_dirs.extend(subdirs);
+    state.active_workers -= 1;
+    queue.ready.notify_all();
+  }
+}
+
+/// Reads the entries of a single dir into nodes.
+/// Returns none if the dir can't be read (permissions issues).
+fn read_directory_entries(dir: &PendingDir) -> Option<(Vec<FileNode>, Vec<PendingDir>)> {
+  let entries = std::fs::read_dir(&dir.path).ok()?;
+
+  let mut nodes = Vec::new();
+  let mut subdirs = Vec::new();
+
+  // skip entries can't read
+  for entry in entries.flatten() {
+    let path = entry.path();
+
+    // skip problematic files and dirs (skipped dirs are never walked into)
+    if should_skip_file(&path, &dir.options) {
+      continue;
+    }
+
+    // file type of the entry itself, symbolic links are resolved below
+    let Ok(file_type) = entry.file_type() else {
+      continue;
+    };
+
+    if file_type.is_symlink() {
+      nodes.push(read_symlink(dir, path, &mut subdirs));
+      continue;
+    }
 
-  // use walkdir to recursively traverse the dir tree
-  // walkdir handles symlinks and permissions
-  for entry in WalkDir::new(root_path)
-    .follow_links(false) // don't follow symbolic links to avoid cycles
+    let is_directory = file_type.is_dir();
+    let mut node = FileNode::new(path.clone(), is_directory, dir.depth + 1);
+    node.real_path = dir.real_path.as_ref().map(|real_path| real_path.join(entry.file_name()));
+
+    if is_directory {
+      subdirs.push(PendingDir {
+        path,
+        depth: dir.depth + 1,
+        real_path: node.real_path.clone(),
+        link_dirs: dir.link_dirs.clone(),
+        options: dir.options.clone(),
+      });
+    } else {
+      // record size and modification time of files for sorting
+      if let Ok(metadata) = entry.metadata() {
+        node.size = metadata.len();
+        node.modified = metadata.modified().ok();
+      }
+      node.content = encoding::sniff_file(&path);
+    }
+
+    nodes.push(node);
+  }
+
+  Some((nodes, subdirs))
+}
+
+/// Creates the node for a symlink, queuing its target dir when links are followed and it doesn't loop.
+/// Links to dirs are dirs in the tree, empty when they're not walked. Broken links are kept as files.
+fn read_symlink(dir: &PendingDir, path: PathBuf, subdirs: &mut Vec<PendingDir>) -> FileNode {
+  let metadata = std::fs::metadata(&path).ok();
+  let is_directory = metadata.as_ref().is_some_and(|metadata| metadata.is_dir());
+  let mut node = FileNode::new(path.clone(), is_directory, dir.depth + 1);
+  node.link_target = std::fs::read_link(&path).ok();
+  node.real_path = path.canonicalize().ok();
+
+  match (&node.real_path, metadata) {
+    (Some(target), Some(_)) if is_directory && dir.options.follow_symlinks => {
+      // a link to a dir that's being walked (or one of its parents) would never end
+      let current = dir.real_path.clone().or_else(|| dir.path.canonicalize().ok()).unwrap_or_else(|| dir.path.clone());
+      let loops = current.starts_with(target) || dir.link_dirs.iter().any(|link_dir| link_dir.starts_with(target));
+      if !loops {
+        let mut link_dirs = dir.link_dirs.as_ref().clone();
+        link_dirs.push(current);
+        subdirs.push(PendingDir {
+          path,
+          depth: dir.depth + 1,
+          real_path: Some(target.clone()),
+          link_dirs: Arc::new(link_dirs),
+          options: dir.options.clone(),
+        });
+      }
+    }
+    (_, Some(metadata)) if !is_directory => {
+      node.size = metadata.len();
+      node.modified = metadata.modified().ok();
+      node.content = encoding::sniff_file(&path);
+    }
+    // broken links can't be read
+    (_, None) => node.content = encoding::sniff_file(&path),
+    _ => {}
+  }
+  node
+}
+
+/// Marks the nodes symlinks in the tree point at, once the scan is done.
+pub fn mark_link_targets(file_tree: &mut HashMap<PathBuf, FileNode>, roots: &SessionRoots) {
+  let real_roots: Vec<(PathBuf, &Path)> = roots.iter().filter_map(|(_, root)| Some((root.canonicalize().ok()?, root))).collect();
+  let targets: Vec<PathBuf> = file_tree
+    .values()
+    .filter(|node| node.link_target.is_some())
+    .filter_map(|node| {
+      let real_path = node.real_path.as_ref()?;
+      real_roots.iter().find_map(|(real_root, root)| Some(root.join(real_path.strip_prefix(real_root).ok()?)))
+    })
+    .collect();
+  for target in targets {
+    if let Some(node) = file_tree.get_mut(&target) {
+      node.is_link_target = true;
+    }
+  }
+}
+
+/// Drops files that resolve to a file already in the list (reached through another symlink),
+/// so the same content isn't counted or packed twice. Paths without symlinks are kept first.
+pub fn unique_files(file_tree: &HashMap<PathBuf, FileNode>, files: Vec<PathBuf>) -> Vec<PathBuf> {
+  let real_path = |path: &PathBuf| file_tree.get(path).and_then(|node| node.real_path.clone());
+  if !files.iter().any(|path| real_path(path).is_some()) {
+    return files;
+  }
+
+  let mut seen: HashSet<PathBuf> = files.iter().filter(|path| real_path(path).is_none()).filter_map(|path| path.canonicalize().ok()).collect();
+  files
     .into_iter()
-    .filter_map(|e| e.ok())
-  // skip entries can't read (permissions issues)
-  {
-    let path = entry.path().to_path_buf();
-    let is_directory = entry.file_type().is_dir();
-
-    // calculate depth relative to root dir
-    // helps indent the tree view
-    let depth = entry.depth();
-
-    // skip problematic files and dirs
-    if should_skip_file(&path) {
+    .filter(|path| match real_path(path) {
+      Some(real_path) => seen.insert(real_path),
+      None => true,
+    })
+    .collect()
+}
+
+/// Merges a batch of scanned nodes into the file tree.
+/// Links each node to its parent and updates file counts, size and modification time
+/// of all ancestor dirs. Returns the dirs whose children changed (they need re-sorting).
+pub fn merge_scanned_nodes(file_tree: &mut HashMap<PathBuf, FileNode>, nodes: Vec<FileNode>) -> Vec<PathBuf> {
+  let mut touched_dirs = HashSet::new();
+
+  for node in nodes {
+    // each path is only scanned once, but the root may already exist
+    if file_tree.contains_key(&node.path) {
       continue;
     }
 
-    let node = FileNode::new(path.clone(), is_directory, depth);
+    let path = node.path.clone();
+
+    // add path as a child of its parent (parent node)
+    if let Some(parent_path) = parent_in_tree(file_tree, &path).filter(|_| node.depth > 0) {
+      if let Some(parent_node) = file_tree.get_mut(parent_path) {
+        if parent_node.is_directory {
+          parent_node.children.push(path.clone());
+          touched_dirs.insert(parent_path.to_path_buf());
+        }
+      }
+    }
+
+    // count the file in every ancestor so selection state can be derived
+    // and aggregate size and modification time for sorting
+    if !node.is_directory {
+      let mut current_path = parent_in_tree(file_tree, &path);
+      while let Some(parent_path) = current_path {
+        let Some(parent_node) = file_tree.get_mut(parent_path) else {
+          break;
+        };
+        parent_node.total_files += 1;
+        parent_node.non_text_files += usize::from(!node.content.is_text());
+        parent_node.size += node.size;
+        parent_node.modified = parent_node.modified.max(node.modified);
+        parent_node.is_selected = parent_node.selection_state() == SelectionState::All;
+        current_path = if parent_node.depth == 0 { None } else { parent_in_tree(file_tree, parent_path) };
+      }
+    }
 
-    // if is a dir, populate children later
-    // for now, just create the node
     file_tree.insert(path, node);
   }
 
-  // now build parent-child relationships
-  // happens in second pass to make sure all nodes exist
-  build_parent_child_relationships(&mut file_tree, root_path)?;
+  touched_dirs.into_iter().collect()
+}
 
-  Ok(file_tree)
+/// Returns the parent of a path in the tree.
+/// The roots of a multi-root session have no parent on disk, they hang off the virtual root.
+fn parent_in_tree<'a>(file_tree: &HashMap<PathBuf, FileNode>, path: &'a Path) -> Option<&'a Path> {
+  path.parent().filter(|parent| file_tree.contains_key(*parent)).or_else(|| {
+    let virtual_root = Path::new("");
+    file_tree.contains_key(virtual_root).then_some(virtual_root)
+  })
+}
+
+/// Dirs scanned in one session and the names their paths are prefixed with.
+/// A single root is the top of the tree, several roots are top-level nodes under a virtual root (an empty path)
+/// and their paths relative to the session (packed output, API, patterns) start with the root's name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRoots {
+  /// Scanned dirs with their names, the name of a single root is empty
+  roots: Vec<(String, PathBuf)>,
 }
 
-/// Builds parent-child relationships in the file tree.
-/// Creates the hierarchical structure needed for tree navigation.
-fn build_parent_child_relationships(file_tree: &mut HashMap<PathBuf, FileNode>, root_path: &Path) -> Result<()> {
-  // collect all paths first to avoid borrowing issues
-  let all_paths: Vec<PathBuf> = file_tree.keys().cloned().collect();
-
-  for path in all_paths {
-    if let Some(parent_path) = path.parent() {
-      // only process if parent is within our scanned tree
-      if parent_path >= root_path && file_tree.contains_key(parent_path) {
-        // add path as a child of its parent (parent node)
-        if let Some(parent_node) = file_tree.get_mut(parent_path) {
-          if parent_node.is_directory && !parent_node.children.contains(&path) {
-            parent_node.children.push(path.clone());
+impl SessionRoots {
+  /// Checks the roots and names them after their dirs, duplicate names get a number (`api`, `api-2`).
+  pub fn new(roots: &[PathBuf]) -> Result<Self> {
+    match roots {
+      [] => anyhow::bail!("No directory given"),
+      [root] => Ok(Self::single(root)),
+      _ => {
+        let roots = roots
+          .iter()
+          .map(|root| root.canonicalize().with_context(|| format!("Directory does not exist: {}", root.display())))
+          .collect::<Result<Vec<_>>>()?;
+        for (index, root) in roots.iter().enumerate() {
+          if let Some(other) = roots[..index].iter().find(|other| root.starts_with(other) || other.starts_with(root)) {
+            anyhow::bail!("Roots overlap: {} and {}", other.display(), root.display());
           }
         }
+
+        let mut named: Vec<(String, PathBuf)> = Vec::with_capacity(roots.len());
+        for root in roots {
+          let base_name = root.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "root".to_string());
+          let mut name = base_name.clone();
+          let mut number = 1;
+          while named.iter().any(|(other, _)| *other == name) {
+            number += 1;
+            name = format!("{}-{}", base_name, number);
+          }
+          named.push((name, root));
+        }
+        Ok(Self { roots: named })
       }
     }
   }
 
-  // sort children for consistent display order
-  // dirs first, then files, both alphabetically
+  /// Creates a session with one root.
+  pub fn single(root: &Path) -> Self {
+    Self {
+      roots: vec![(String::new(), root.to_path_buf())],
+    }
+  }
+
+  /// Returns the number of roots.
+  pub fn len(&self) -> usize {
+    self.roots.len()
+  }
+
+  /// Returns the key of the top node in the tree, the root itself or an empty path above several roots.
+  pub fn root_path(&self) -> PathBuf {
+    match self.roots.as_slice() {
+      [(_, root)] => root.clone(),
+      _ => PathBuf::new(),
+    }
+  }
+
+  /// Returns the dir relative output files are written to, the first root.
+  pub fn base_dir(&self) -> &Path {
+    &self.roots[0].1
+  }
+
+  /// Iterates over the roots with their names.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+    self.roots.iter().map(|(name, root)| (name.as_str(), root.as_path()))
+  }
+
+  /// Returns a path relative to the session, prefixed with its root's name when there are several roots.
+  /// Paths outside every root are returned as they are.
+  pub fn relative(&self, path: &Path) -> PathBuf {
+    for (name, root) in &self.roots {
+      if let Ok(relative_path) = path.strip_prefix(root) {
+        return match (name.is_empty(), relative_path.as_os_str().is_empty()) {
+          (true, _) => relative_path.to_path_buf(),
+          (false, true) => PathBuf::from(name),
+          (false, false) => Path::new(name).join(relative_path),
+        };
+      }
+    }
+    path.to_path_buf()
+  }
+
+  /// Maps a path relative to the session back to the file system, none if it doesn't start with a root's name.
+  pub fn resolve(&self, relative_path: &Path) -> Option<PathBuf> {
+    // tools like fd and find print ./src/main.rs
+    let cleaned: PathBuf = relative_path.components().filter(|component| !matches!(component, Component::CurDir)).collect();
+    let mut components = cleaned.components();
+    match self.roots.as_slice() {
+      [(_, root)] if cleaned.as_os_str().is_empty() => Some(root.clone()),
+      [(_, root)] => Some(root.join(&cleaned)),
+      roots => match components.next() {
+        None => Some(PathBuf::new()),
+        Some(Component::Normal(first)) => roots.iter().find(|(name, _)| first == name.as_str()).map(|(_, root)| root.join(components.as_path())),
+        Some(_) => None,
+      },
+    }
+  }
+}
+
+/// Returns the dirs above the given paths, each once.
+/// These are the dirs whose children can change order when the paths' selection changes.
+pub fn ancestor_dirs(file_tree: &HashMap<PathBuf, FileNode>, paths: &[PathBuf]) -> Vec<PathBuf> {
+  let mut dirs = HashSet::new();
+  for path in paths {
+    let mut current_path = parent_in_tree(file_tree, path);
+    // dirs already seen have their ancestors in the set too
+    while let Some(parent_path) = current_path.filter(|parent_path| dirs.insert(parent_path.to_path_buf())) {
+      current_path = if file_tree.get(parent_path).is_some_and(|node| node.depth == 0) {
+        None
+      } else {
+        parent_in_tree(file_tree, parent_path)
+      };
+    }
+  }
+  dirs.into_iter().collect()
+}
+
+/// Sorts the children of every dir according to the sort mode.
+/// Uses real node types so dirs always come before files.
+/// Token sorting uses the given file token counts, dirs use the sum of their files.
+pub fn sort_children(file_tree: &mut HashMap<PathBuf, FileNode>, sort_mode: SortMode, file_token_counts: &HashMap<PathBuf, usize>) {
   // need to collect the paths first to avoid borrowing issues
   let directory_paths: Vec<PathBuf> = file_tree.iter().filter(|(_, node)| node.is_directory).map(|(path, _)| path.clone()).collect();
 
-  for dir_path in directory_paths {
-    if let Some(node) = file_tree.get_mut(&dir_path) {
-      node.children.sort_by(|a, b| {
-        // need to look up the nodes without borrowing file_tree mutably
-        let a_name = a.file_name().unwrap_or_default();
-        let b_name = b.file_name().unwrap_or_default();
-
-        // determine if paths are dirs by checking if they end with known dir patterns
-        // or by checking the file extension (is heuristic since can't borrow file_tree)
-        let a_is_likely_dir = a.extension().is_none() || a.to_string_lossy().ends_with('/');
-        let b_is_likely_dir = b.extension().is_none() || b.to_string_lossy().ends_with('/');
-
-        match (a_is_likely_dir, b_is_likely_dir) {
-          // dirs first
-          (true, false) => std::cmp::Ordering::Less,
-          // files second
-          (false, true) => std::cmp::Ordering::Greater,
-          // same type, alphabetical
-          _ => a_name.cmp(b_name),
+  sort_children_of(file_tree, &directory_paths, sort_mode, file_token_counts);
+}
+
+/// Sorts the children of the given dirs according to the sort mode.
+/// Used after merging scanned nodes so only changed dirs are re-sorted.
+pub fn sort_children_of(file_tree: &mut HashMap<PathBuf, FileNode>, directory_paths: &[PathBuf], sort_mode: SortMode, file_token_counts: &HashMap<PathBuf, usize>) {
+  // aggregate token counts for dirs once instead of per comparison
+  let mut token_totals: HashMap<PathBuf, usize> = HashMap::new();
+  if sort_mode == SortMode::Tokens {
+    for (file_path, count) in file_token_counts {
+      token_totals.insert(file_path.clone(), *count);
+      let mut current_path = file_path.parent();
+      while let Some(parent_path) = current_path {
+        if !file_tree.contains_key(parent_path) {
+          break;
         }
-      });
+        *token_totals.entry(parent_path.to_path_buf()).or_default() += count;
+        current_path = parent_path.parent();
+      }
     }
   }
 
-  Ok(())
-}
+  for dir_path in directory_paths {
+    // take the children out so the tree can be read while sorting
+    let mut children = match file_tree.get_mut(dir_path) {
+      Some(node) => std::mem::take(&mut node.children),
+      None => continue,
+    };
 
-/// Determines if a file should be skipped during scanning.
-/// Only skips files that would cause technical issues or performance problems.
-/// Respects user choice for everything else.
-fn should_skip_file(path: &Path) -> bool {
-  let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    children.sort_by(|a, b| compare_nodes(file_tree.get(a), file_tree.get(b), sort_mode, &token_totals));
 
-  // always skip .git dir
-  if file_name == ".git" {
-    return true;
+    if let Some(node) = file_tree.get_mut(dir_path) {
+      node.children = children;
+    }
   }
+}
 
-  // always skip .gitignore
-  if file_name == ".gitignore" {
-    return true;
+/// Compares two sibling nodes for the given sort mode.
+/// Dirs first, then by mode, falling back to name for ties.
+fn compare_nodes(a: Option<&FileNode>, b: Option<&FileNode>, sort_mode: SortMode, token_totals: &HashMap<PathBuf, usize>) -> Ordering {
+  let (a, b) = match (a, b) {
+    (Some(a), Some(b)) => (a, b),
+    (Some(_), None) => return Ordering::Less,
+    (None, Some(_)) => return Ordering::Greater,
+    (None, None) => return Ordering::Equal,
+  };
+
+  // dirs first, files second
+  let type_order = b.is_directory.cmp(&a.is_directory);
+
+  // larger, newer and more selected entries come first
+  let mode_order = match sort_mode {
+    SortMode::Name => Ordering::Equal,
+    SortMode::Size => b.size.cmp(&a.size),
+    SortMode::Tokens => token_totals.get(&b.path).cmp(&token_totals.get(&a.path)),
+    SortMode::Modified => b.modified.cmp(&a.modified),
+    SortMode::SelectedFirst => selection_rank(b).cmp(&selection_rank(a)),
+  };
+
+  type_order.then(mode_order).then_with(|| a.name.cmp(&b.name))
+}
+
+/// Ranks selection states for selected-first sorting.
+fn selection_rank(node: &FileNode) -> u8 {
+  match node.selection_state() {
+    SelectionState::All => 2,
+    SelectionState::Partial => 1,
+    SelectionState::None => 0,
   }
+}
 
-  // skip common large build/dependency directories that cause performance issues
-  // these typically contain thousands of generated files that users don't want to process
-  // TODO: make this configurable, or test speedups for token counter
-  let large_dirs_to_skip = [
-    "target",
-    "node_modules",
-    "build",
-    "dist",
-    ".next",
-    ".nuxt",
-    "__pycache__",
-    ".pytest_cache",
-    ".mypy_cache",
-    ".tox",
-    "venv",
-    ".venv",
-    "env",
-    ".env",
-    "coverage",
-    ".coverage",
-    "tmp",
-    "temp",
-    ".tmp",
-    "logs",
-    ".DS_Store",
-    "Thumbs.db",
-  ];
-
-  if large_dirs_to_skip.iter().any(|&skip_name| file_name.eq_ignore_ascii_case(skip_name)) {
+/// Determines if a file should be skipped during scanning.
+/// Skips hidden names (see `ScanOptions`) and files that would cause technical issues.
+/// Respects user choice for everything else.
+fn should_skip_file(path: &Path, options: &ScanOptions) -> bool {
+  let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+  let listed = |names: &[String]| names.iter().any(|name| file_name.eq_ignore_ascii_case(name));
+
+  // always shown names win over the hidden toggle and the always hidden list
+  if !listed(&options.always_shown) && (listed(&options.always_hidden) || (!options.show_hidden && file_name.starts_with('.'))) {
     return true;
   }
 
@@ -193,118 +747,154 @@ fn flatten_node_recursive(file_tree: &HashMap<PathBuf, FileNode>, node: &FileNod
 
 /// Gets all selected files from the tree, respecting user choice.
 /// Returns a list of file paths that are currently selected for processing.
-/// Only filters out files that would cause technical issues (binaries, circular references).
+/// Binary files are only selected when forced, so only files siff wrote itself are filtered out.
 pub fn get_selected_files(file_tree: &HashMap<PathBuf, FileNode>) -> Vec<PathBuf> {
   file_tree
     .values()
     .filter(|node| node.is_selected && !node.is_directory)
-    .filter(|node| is_text_file(&node.path))
+    .filter(|node| !is_produced_file(&node.path))
     .map(|node| node.path.clone())
     .collect()
 }
 
-/// Determines if a file should be processed.
-/// Only filters out files that would cause technical issues.
-fn is_text_file(path: &Path) -> bool {
-  let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+/// Records a file siff wrote (like a backend output file), so it's never selected as input.
+pub fn register_produced_file(path: &Path) {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  if let Ok(mut produced) = PRODUCED_FILES.get_or_init(Default::default).lock() {
+    produced.insert(path);
+  }
+}
 
-  // skip repomix output files to avoid circular references
-  // TODO: remove this, if can handle circular references and tested it
-  if file_name.starts_with("repomix-output") || file_name.ends_with("-repomix.txt") || file_name.ends_with("-repomix.md") || file_name.ends_with("-repomix.xml") {
+/// Returns true if siff wrote the file itself during this session.
+fn is_produced_file(path: &Path) -> bool {
+  let Some(Ok(produced)) = PRODUCED_FILES.get().map(Mutex::lock) else {
     return false;
+  };
+  // only resolve the path when there's something to compare against
+  !produced.is_empty() && (produced.contains(path) || path.canonicalize().is_ok_and(|path| produced.contains(&path)))
+}
+
+/// Toggles selection of a file or directory, binary and unreadable files are skipped.
+/// Partially selected dirs become fully selected, fully selected dirs are cleared.
+/// Returns the files whose selection changed.
+pub fn toggle_selection_recursive(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path) -> Result<Vec<PathBuf>> {
+  let mut changed_files = Vec::new();
+
+  if let Some(node) = file_tree.get(path) {
+    let new_selection_state = node.selection_state() != SelectionState::All;
+    set_selection_recursive(file_tree, path, new_selection_state, false, &mut changed_files)?;
   }
 
-  // skip files without extensions only if they're likely binaries
-  match path.extension().and_then(|ext| ext.to_str()) {
-    Some(_ext) => {
-      // if has extension, include it
-      true
-    }
-    None => {
-      // if there is no extension, check if it's a known text file
-      // TODO: move this to a config file for user to customize
-      let allowed_no_ext = [
-        "README",
-        "LICENSE",
-        "CHANGELOG",
-        "CONTRIBUTING",
-        "Dockerfile",
-        "Makefile",
-        "Gemfile",
-        "Rakefile",
-        "Procfile",
-        "Vagrantfile",
-        "Jenkinsfile",
-        "BUILD",
-        "WORKSPACE",
-        "justfile",
-        "gradlew",
-        "mvnw",
-      ];
-      if allowed_no_ext.iter().any(|&name| file_name.eq_ignore_ascii_case(name)) {
-        return true;
-      }
+  Ok(changed_files)
+}
 
-      // for other extensionless files, do a basic binary check
-      if let Ok(metadata) = std::fs::metadata(path) {
-        // skip very large files that might be binaries (> 50 MB)
-        if metadata.len() > 50 * 1024 * 1024 {
-          return false;
-        }
-      }
+/// Toggles selection of a file or directory including binary and unreadable files.
+/// The explicit override for files that normal selection skips.
+pub fn force_toggle_selection(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path) -> Result<Vec<PathBuf>> {
+  let mut changed_files = Vec::new();
 
-      // try to read first few bytes to detect binary content
-      if let Ok(mut file) = std::fs::File::open(path) {
-        use std::io::Read;
-        let mut buffer = [0; 512];
-        if let Ok(bytes_read) = file.read(&mut buffer) {
-          // check for null bytes (for common binary files)
-          if buffer[..bytes_read].contains(&0) {
-            return false;
-          }
-        }
-      }
+  if let Some(node) = file_tree.get(path) {
+    let new_selection_state = if node.is_directory { node.selected_files < node.total_files } else { !node.is_selected };
+    set_selection_recursive(file_tree, path, new_selection_state, true, &mut changed_files)?;
+  }
+
+  Ok(changed_files)
+}
+
+/// Selects or clears a file or directory and everything in it, binary and unreadable files aren't selected.
+/// Returns the files whose selection changed.
+pub fn set_selection(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path, selection_state: bool) -> Result<Vec<PathBuf>> {
+  let mut changed_files = Vec::new();
+  set_selection_recursive(file_tree, path, selection_state, false, &mut changed_files)?;
+  Ok(changed_files)
+}
 
-      // assume text if can't detect otherwise
-      true
+/// Returns the files at or below a path, in tree order.
+pub fn files_under(file_tree: &HashMap<PathBuf, FileNode>, path: &Path) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  let mut stack = vec![path.to_path_buf()];
+  while let Some(current) = stack.pop() {
+    match file_tree.get(&current) {
+      Some(node) if node.is_directory => stack.extend(node.children.iter().rev().cloned()),
+      Some(_) => files.push(current),
+      None => {}
     }
   }
+  files
 }
 
-/// Toggles selection of a file or directory.
-/// For dirs, recursively selects/deselects all children.
-pub fn toggle_selection_recursive(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path) -> Result<()> {
-  if let Some(node) = file_tree.get_mut(path) {
-    let new_selection_state = !node.is_selected;
-    node.is_selected = new_selection_state;
-
-    // if is a dir, apply the same selection to all children
-    if node.is_directory {
-      let children = node.children.clone();
-      for child_path in children {
-        toggle_selection_recursive_helper(file_tree, &child_path, new_selection_state)?;
+/// Resolves paths relative to the session (or absolute) to nodes in the tree.
+/// Returns the resolved paths and the ones that are missing, ignored, outside the roots or not scanned yet.
+pub fn resolve_paths(file_tree: &HashMap<PathBuf, FileNode>, roots: &SessionRoots, paths: &[String]) -> (Vec<PathBuf>, Vec<String>) {
+  let canonical_roots: Vec<(PathBuf, &Path)> = roots.iter().filter_map(|(_, root)| Some((root.canonicalize().ok()?, root))).collect();
+  let mut resolved = Vec::with_capacity(paths.len());
+  let mut unknown = Vec::new();
+  for text in paths {
+    let path = Path::new(text);
+    let mut candidate = if path.is_absolute() { Some(path.components().collect()) } else { roots.resolve(path) };
+    // editors may send the resolved path of a root that was given through a symlink
+    if !candidate.as_ref().is_some_and(|candidate| file_tree.contains_key(candidate)) && path.is_absolute() {
+      if let Ok(canonical_path) = path.canonicalize() {
+        if let Some(found) = canonical_roots.iter().find_map(|(canonical_root, root)| Some(root.join(canonical_path.strip_prefix(canonical_root).ok()?))) {
+          candidate = Some(found);
+        }
       }
     }
+    let escapes = path.components().any(|component| matches!(component, Component::ParentDir));
+    if let Some(candidate) = candidate.filter(|candidate| !escapes && file_tree.contains_key(candidate)) {
+      resolved.push(candidate);
+    } else {
+      unknown.push(text.clone());
+    }
   }
+  (resolved, unknown)
+}
 
-  Ok(())
+/// Splits a path list into entries, NUL separated if the input has any NUL (`fd -0`, `git diff -z`), otherwise one per line.
+pub fn parse_path_list(input: &[u8]) -> Vec<String> {
+  let separator = if input.contains(&0) { b'\0' } else { b'\n' };
+  input
+    .split(|byte| *byte == separator)
+    .map(|entry| String::from_utf8_lossy(entry).trim_end_matches('\r').to_string())
+    .filter(|entry| !entry.trim().is_empty())
+    .collect()
 }
 
-/// Helper function for recursive selection toggling.
-/// Applies the given selection state to a node and all its descendants.
-fn toggle_selection_recursive_helper(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path, selection_state: bool) -> Result<()> {
-  if let Some(node) = file_tree.get_mut(path) {
-    node.is_selected = selection_state;
+/// Reads a path list from a file, or from stdin when the source is `-`.
+pub fn read_path_list(source: &Path) -> Result<Vec<String>> {
+  let input = if source == Path::new("-") {
+    let mut input = Vec::new();
+    std::io::Read::read_to_end(&mut std::io::stdin(), &mut input).context("Failed to read path list from stdin")?;
+    input
+  } else {
+    std::fs::read(source).with_context(|| format!("Failed to read path list {}", source.display()))?
+  };
+  Ok(parse_path_list(&input))
+}
 
-    if node.is_directory {
-      let children = node.children.clone();
-      for child_path in children {
-        toggle_selection_recursive_helper(file_tree, &child_path, selection_state)?;
+/// Flips the selection of the given files, keeping ancestor dirs in sync.
+/// Binary and unreadable files are never selected this way, only cleared.
+pub fn flip_file_selections(file_tree: &mut HashMap<PathBuf, FileNode>, paths: &[PathBuf]) {
+  flip_files(file_tree, paths, false);
+}
+
+/// Replays recorded selection changes (undo/redo), including binary files that were forced.
+pub fn replay_file_selections(file_tree: &mut HashMap<PathBuf, FileNode>, paths: &[PathBuf]) {
+  flip_files(file_tree, paths, true);
+}
+
+/// Flips the selection of each file in the list.
+fn flip_files(file_tree: &mut HashMap<PathBuf, FileNode>, paths: &[PathBuf], include_binary: bool) {
+  let mut changed_files = Vec::new();
+
+  for path in paths {
+    if let Some(node) = file_tree.get(path) {
+      if !node.is_directory {
+        let new_selection_state = !node.is_selected;
+        let _ = set_selection_recursive(file_tree, path, new_selection_state, include_binary, &mut changed_files);
       }
     }
   }
-
-  Ok(())
 }
 
 /// Expands all dirs in the file tree recursively.
@@ -329,46 +919,91 @@ pub fn collapse_all_directories(file_tree: &mut HashMap<PathBuf, FileNode>) {
 
 /// Selects all items (files and directories) that are currently visible in the file tree.
 /// For directories, select all their contents.
-pub fn select_all_visible_files(file_tree: &mut HashMap<PathBuf, FileNode>, visible_files: &[PathBuf]) -> Result<()> {
+/// Returns the files whose selection changed overall.
+pub fn select_all_visible_files(file_tree: &mut HashMap<PathBuf, FileNode>, visible_files: &[PathBuf]) -> Result<Vec<PathBuf>> {
   // first, unselect everything
-  unselect_all_items(file_tree);
+  let previously_selected: HashSet<PathBuf> = unselect_all_items(file_tree).into_iter().collect();
 
   // select each visible item and all its contents
+  let mut newly_selected = Vec::new();
   for path in visible_files {
     if let Some(node) = file_tree.get(path) {
-      if !node.is_selected {
-        set_selection_recursive(file_tree, path, true)?;
+      if node.selection_state() != SelectionState::All {
+        set_selection_recursive(file_tree, path, true, false, &mut newly_selected)?;
       }
     }
   }
 
-  Ok(())
+  // files selected both before and after did not change
+  let newly_selected_set: HashSet<&PathBuf> = newly_selected.iter().collect();
+  let mut changed_files: Vec<PathBuf> = previously_selected.iter().filter(|path| !newly_selected_set.contains(path)).cloned().collect();
+  changed_files.extend(newly_selected.iter().filter(|path| !previously_selected.contains(*path)).cloned());
+
+  Ok(changed_files)
 }
 
 /// Sets the selection state of a file or directory recursively.
-/// For directories, apply the same selection state to all children.
-fn set_selection_recursive(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path, selection_state: bool) -> Result<()> {
-  if let Some(node) = file_tree.get_mut(path) {
-    node.is_selected = selection_state;
+/// Keeps the selected file counts of all ancestor dirs in sync.
+/// Binary and unreadable files are only selected with `include_binary`, clearing always applies.
+fn set_selection_recursive(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path, selection_state: bool, include_binary: bool, changed_files: &mut Vec<PathBuf>) -> Result<()> {
+  let delta = apply_selection_to_subtree(file_tree, path, selection_state, include_binary, changed_files);
+
+  // propagate the change in selected files up to the root
+  if delta != 0 && file_tree.get(path).is_some_and(|node| node.depth > 0) {
+    let mut current_path = parent_in_tree(file_tree, path);
+    while let Some(parent_path) = current_path {
+      let Some(parent_node) = file_tree.get_mut(parent_path) else {
+        break;
+      };
+      parent_node.selected_files = parent_node.selected_files.saturating_add_signed(delta);
+      parent_node.is_selected = parent_node.selection_state() == SelectionState::All;
+      current_path = if parent_node.depth == 0 { None } else { parent_in_tree(file_tree, parent_path) };
+    }
+  }
 
-    // if is a directory, apply the same selection to all children
-    if node.is_directory {
-      let children = node.children.clone();
-      for child_path in children {
-        set_selection_recursive(file_tree, &child_path, selection_state)?;
+  Ok(())
+}
+
+/// Applies the selection state to a node and all its descendants.
+/// Returns the change in number of selected files within the subtree.
+fn apply_selection_to_subtree(file_tree: &mut HashMap<PathBuf, FileNode>, path: &Path, selection_state: bool, include_binary: bool, changed_files: &mut Vec<PathBuf>) -> isize {
+  let children = match file_tree.get_mut(path) {
+    Some(node) if !node.is_directory => {
+      if node.is_selected == selection_state || (selection_state && !include_binary && !node.content.is_text()) {
+        return 0;
       }
+      node.is_selected = selection_state;
+      changed_files.push(path.to_path_buf());
+      return if selection_state { 1 } else { -1 };
     }
+    Some(node) => node.children.clone(),
+    None => return 0,
+  };
+
+  let delta: isize = children.iter().map(|child_path| apply_selection_to_subtree(file_tree, child_path, selection_state, include_binary, changed_files)).sum();
+
+  if let Some(node) = file_tree.get_mut(path) {
+    node.selected_files = node.selected_files.saturating_add_signed(delta);
+    node.is_selected = node.selection_state() == SelectionState::All;
   }
 
-  Ok(())
+  delta
 }
 
 /// Unselects all files and dirs in the file tree.
-/// Clears all selections for a fresh start.
-pub fn unselect_all_items(file_tree: &mut HashMap<PathBuf, FileNode>) {
+/// Clears all selections for a fresh start, returns the files that were selected.
+pub fn unselect_all_items(file_tree: &mut HashMap<PathBuf, FileNode>) -> Vec<PathBuf> {
+  let mut changed_files = Vec::new();
+
   for node in file_tree.values_mut() {
+    if node.is_selected && !node.is_directory {
+      changed_files.push(node.path.clone());
+    }
     node.is_selected = false;
+    node.selected_files = 0;
   }
+
+  changed_files
 }
 
 /// Creates a file tree structure as text for repomix output.
@@ -384,30 +1019,13 @@ pub fn generate_file_tree_text(file_tree: &HashMap<PathBuf, FileNode>, root_path
   // get root node and generate tree from its children
   if let Some(root_node) = file_tree.get(root_path) {
     if root_node.is_directory {
-      // collect and sort children for consistent output
-      let mut children: Vec<&PathBuf> = root_node.children.iter().collect();
-      children.sort_by(|a, b| {
-        let a_name = a.file_name().unwrap_or_default();
-        let b_name = b.file_name().unwrap_or_default();
-
-        // get node types for sorting (directories first)
-        let a_is_dir = file_tree.get(*a).map(|n| n.is_directory).unwrap_or(false);
-        let b_is_dir = file_tree.get(*b).map(|n| n.is_directory).unwrap_or(false);
-
-        match (a_is_dir, b_is_dir) {
-          // directories first
-          (true, false) => std::cmp::Ordering::Less,
-          // files second
-          (false, true) => std::cmp::Ordering::Greater,
-          // alphabetical within same type
-          _ => a_name.cmp(b_name),
-        }
-      });
+      // children are already ordered by the active sort mode
+      let children = &root_node.children;
 
       // generate tree structure for each child
       for (index, child_path) in children.iter().enumerate() {
         let is_last = index == children.len() - 1;
-        if let Some(child_node) = file_tree.get(*child_path) {
+        if let Some(child_node) = file_tree.get(child_path) {
           generate_tree_node_recursive(file_tree, child_node, &mut result, "", is_last);
         }
       }
@@ -432,30 +1050,13 @@ fn generate_tree_node_recursive(file_tree: &HashMap<PathBuf, FileNode>, node: &F
 
   // if it's a directory, add its children
   if node.is_directory {
-    // collect and sort children
-    let mut children: Vec<&PathBuf> = node.children.iter().collect();
-    children.sort_by(|a, b| {
-      let a_name = a.file_name().unwrap_or_default();
-      let b_name = b.file_name().unwrap_or_default();
-
-      // get node types for sorting (directories first)
-      let a_is_dir = file_tree.get(*a).map(|n| n.is_directory).unwrap_or(false);
-      let b_is_dir = file_tree.get(*b).map(|n| n.is_directory).unwrap_or(false);
-
-      match (a_is_dir, b_is_dir) {
-        // directories first
-        (true, false) => std::cmp::Ordering::Less,
-        // files second
-        (false, true) => std::cmp::Ordering::Greater,
-        // alphabetical within same type
-        _ => a_name.cmp(b_name),
-      }
-    });
+    // children are already ordered by the active sort mode
+    let children = &node.children;
 
     // create tree for each child
     for (index, child_path) in children.iter().enumerate() {
       let is_last_child = index == children.len() - 1;
-      if let Some(child_node) = file_tree.get(*child_path) {
+      if let Some(child_node) = file_tree.get(child_path) {
         let new_prefix = format!("{}{}", prefix, extension);
         generate_tree_node_recursive(file_tree, child_node, result, &new_prefix, is_last_child);
       }
@@ -468,6 +1069,7 @@ fn generate_tree_node_recursive(file_tree: &HashMap<PathBuf, FileNode>, node: &F
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::types::FileContent;
   use std::fs;
   use tempfile::TempDir;
 
@@ -491,4 +1093,285 @@ mod tests {
     assert!(file_tree.contains_key(&root.join("src/main.rs")));
     assert!(file_tree.contains_key(&root.join("README.md")));
   }
+
+  #[test]
+  fn test_tri_state_directory_selection() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    fs::create_dir_all(root.join("src/nested")).unwrap();
+    fs::write(root.join("src/a.rs"), "a").unwrap();
+    fs::write(root.join("src/nested/b.rs"), "b").unwrap();
+
+    let mut file_tree = scan_directory(root).unwrap();
+    let src = root.join("src");
+    assert_eq!(file_tree[&src].total_files, 2);
+    assert_eq!(file_tree[&src].selection_state(), SelectionState::None);
+
+    // selecting one file makes every ancestor partial
+    toggle_selection_recursive(&mut file_tree, &src.join("a.rs")).unwrap();
+    assert_eq!(file_tree[&src].selection_state(), SelectionState::Partial);
+    assert_eq!(file_tree[root].selection_state(), SelectionState::Partial);
+    assert!(!file_tree[&src].is_selected);
+
+    // toggling a partial dir completes the selection
+    toggle_selection_recursive(&mut file_tree, &src).unwrap();
+    assert_eq!(file_tree[&src].selection_state(), SelectionState::All);
+    assert_eq!(file_tree[&src.join("nested")].selection_state(), SelectionState::All);
+    assert_eq!(file_tree[root].selected_files, 2);
+
+    // toggling a fully selected dir clears it
+    toggle_selection_recursive(&mut file_tree, &src).unwrap();
+    assert_eq!(file_tree[&src].selection_state(), SelectionState::None);
+    assert_eq!(file_tree[root].selected_files, 0);
+  }
+
+  #[test]
+  fn test_sort_children_uses_node_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    fs::create_dir(root.join("foo.d")).unwrap();
+    fs::create_dir(root.join("src")).unwrap();
+    fs::write(root.join("Makefile"), "all:").unwrap();
+    fs::write(root.join("a.rs"), "fn main() { println!(\"hello\"); }").unwrap();
+
+    let mut file_tree = scan_directory(root).unwrap();
+    let names = |file_tree: &HashMap<PathBuf, FileNode>| file_tree[root].children.iter().map(|path| file_tree[path].name.clone()).collect::<Vec<_>>();
+
+    // dirs first (even with an extension), then files (even without one)
+    assert_eq!(names(&file_tree), vec!["foo.d", "src", "Makefile", "a.rs"]);
+
+    // larger files first within the same type
+    sort_children(&mut file_tree, SortMode::Size, &HashMap::new());
+    assert_eq!(names(&file_tree), vec!["foo.d", "src", "a.rs", "Makefile"]);
+
+    // selected files first
+    toggle_selection_recursive(&mut file_tree, &root.join("Makefile")).unwrap();
+    sort_children(&mut file_tree, SortMode::SelectedFirst, &HashMap::new());
+    assert_eq!(names(&file_tree), vec!["foo.d", "src", "Makefile", "a.rs"]);
+
+    // generated tree text follows the same order
+    let tree_text = generate_file_tree_text(&file_tree, root);
+    assert!(tree_text.find("Makefile").unwrap() < tree_text.find("a.rs").unwrap());
+
+    // only the dirs above changed files are re-sorted
+    fs::write(root.join("src/b.rs"), "").unwrap();
+    fs::write(root.join("src/c.rs"), "").unwrap();
+    let mut file_tree = scan_directory(root).unwrap();
+    let changed = toggle_selection_recursive(&mut file_tree, &root.join("src/c.rs")).unwrap();
+    let mut changed_dirs = ancestor_dirs(&file_tree, &changed);
+    changed_dirs.sort();
+    assert_eq!(changed_dirs, vec![root.to_path_buf(), root.join("src")]);
+    sort_children_of(&mut file_tree, &changed_dirs, SortMode::SelectedFirst, &HashMap::new());
+    assert_eq!(file_tree[&root.join("src")].children, vec![root.join("src/c.rs"), root.join("src/b.rs")]);
+    assert_eq!(names(&file_tree), vec!["src", "foo.d", "Makefile", "a.rs"]);
+  }
+
+  #[test]
+  fn test_scan_prunes_skipped_dirs() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+    fs::write(root.join("node_modules/pkg/index.js"), "").unwrap();
+    fs::create_dir_all(root.join("a/b/c")).unwrap();
+    fs::write(root.join("a/b/c/deep.rs"), "").unwrap();
+
+    let file_tree = scan_directory(root).unwrap();
+
+    // skipped dirs are never walked into, so no orphaned nodes
+    assert!(!file_tree.keys().any(|path| path.starts_with(root.join("node_modules"))));
+
+    // every node except the root is linked to its parent
+    for (path, node) in &file_tree {
+      if path != root {
+        assert!(file_tree[path.parent().unwrap()].children.contains(path));
+        assert_eq!(node.depth, file_tree[path.parent().unwrap()].depth + 1);
+      }
+    }
+    assert_eq!(file_tree[root].total_files, 1);
+  }
+
+  #[test]
+  fn test_produced_files_are_never_selected() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("repomix-output.xml"), "<file/>").unwrap();
+    fs::write(root.join("packed.md"), "# packed").unwrap();
+
+    let mut file_tree = scan_directory(root).unwrap();
+    toggle_selection_recursive(&mut file_tree, root).unwrap();
+
+    // files are excluded because siff wrote them, not because of their name
+    register_produced_file(&root.join("packed.md"));
+    let mut selected = get_selected_files(&file_tree);
+    selected.sort();
+    assert_eq!(selected, vec![root.join("main.rs"), root.join("repomix-output.xml")]);
+  }
+
+  #[test]
+  fn test_path_lists() {
+    // newline lists skip blank lines and CRLF endings
+    assert_eq!(parse_path_list(b"src/a.rs\r\n\n./b.rs\n"), vec!["src/a.rs", "./b.rs"]);
+    // NUL lists keep names with newlines
+    assert_eq!(parse_path_list(b"odd\nname\0src/a.rs\0"), vec!["odd\nname", "src/a.rs"]);
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("src")).unwrap();
+    fs::write(root.join("src/a.rs"), "a").unwrap();
+    fs::write(root.join("b.rs"), "b").unwrap();
+    let file_tree = scan_directory(root).unwrap();
+
+    let paths = ["./src/a.rs", "src", "missing.rs", "../b.rs"].map(String::from);
+    let (resolved, unknown) = resolve_paths(&file_tree, &SessionRoots::single(root), &paths);
+    assert_eq!(resolved, vec![root.join("src/a.rs"), root.join("src")]);
+    assert_eq!(unknown, vec!["missing.rs", "../b.rs"]);
+  }
+
+  #[test]
+  fn test_multiple_roots() {
+    let temp_dir = TempDir::new().unwrap();
+    let base = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir_all(base.join("service/src")).unwrap();
+    fs::write(base.join("service/src/main.rs"), "fn main() {}").unwrap();
+    fs::create_dir_all(base.join("shared/proto/generated")).unwrap();
+    fs::write(base.join("shared/proto/api.proto"), "syntax = \"proto3\";").unwrap();
+    fs::write(base.join("shared/proto/generated/api.pb.go"), "package api").unwrap();
+    fs::create_dir_all(base.join("legacy/service")).unwrap();
+    fs::write(base.join("legacy/service/app.py"), "").unwrap();
+    fs::write(base.join("other.rs"), "").unwrap();
+
+    // roots hang off a virtual root, named after their dirs with duplicates numbered
+    let roots = SessionRoots::new(&[base.join("service"), base.join("shared/proto"), base.join("legacy/service")]).unwrap();
+    assert_eq!(roots.root_path(), PathBuf::new());
+    let mut file_tree = scan_roots(&roots, &ScanOptions::default()).unwrap();
+    let top_level: Vec<&str> = file_tree[&roots.root_path()].children.iter().map(|path| file_tree[path].name.as_str()).collect();
+    assert_eq!(top_level, vec!["proto", "service", "service-2"]);
+    assert!(!file_tree.contains_key(&base.join("other.rs")) && !file_tree.contains_key(&base.join("shared")));
+    assert_eq!(file_tree[&roots.root_path()].total_files, 4);
+
+    // paths relative to the session start with the root's name and map back
+    assert_eq!(roots.relative(&base.join("legacy/service/app.py")), Path::new("service-2/app.py"));
+    assert_eq!(roots.resolve(Path::new("./proto/api.proto")), Some(base.join("shared/proto/api.proto")));
+    assert_eq!(roots.resolve(Path::new("shared/proto/api.proto")), None);
+
+    // selection inside a root reaches the virtual root
+    set_selection(&mut file_tree, &base.join("shared/proto"), true).unwrap();
+    assert_eq!(file_tree[&roots.root_path()].selected_files, 2);
+    assert_eq!(file_tree[&roots.root_path()].selection_state(), SelectionState::Partial);
+    let (resolved, unknown) = resolve_paths(&file_tree, &roots, &["proto/api.proto".to_string(), base.join("service/src/main.rs").display().to_string(), "other.rs".to_string()]);
+    assert_eq!(resolved, vec![base.join("shared/proto/api.proto"), base.join("service/src/main.rs")]);
+    assert_eq!(unknown, vec!["other.rs"]);
+
+    // each root's .siff.json adds to the scan rules for that root only
+    fs::write(base.join("shared/proto").join(ROOT_RULES_FILE), r#"{ "scan": { "always_hidden": ["generated"] } }"#).unwrap();
+    fs::create_dir_all(base.join("service/generated")).unwrap();
+    fs::write(base.join("service/generated/schema.rs"), "").unwrap();
+    let file_tree = scan_roots(&roots, &ScanOptions::default()).unwrap();
+    assert!(!file_tree.contains_key(&base.join("shared/proto/generated")));
+    assert!(file_tree.contains_key(&base.join("service/generated/schema.rs")));
+    fs::write(base.join("service").join(ROOT_RULES_FILE), "{ scan").unwrap();
+    assert!(scan_roots(&roots, &ScanOptions::default()).is_err());
+
+    // a single root is the top node itself, overlapping roots are rejected
+    assert_eq!(SessionRoots::new(&[base.join("service")]).unwrap().root_path(), base.join("service"));
+    assert_eq!(SessionRoots::single(&base).relative(&base.join("other.rs")), Path::new("other.rs"));
+    assert!(SessionRoots::new(&[base.join("shared"), base.join("shared/proto")]).is_err());
+  }
+
+  #[test]
+  fn test_hidden_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join(".github/workflows")).unwrap();
+    fs::write(root.join(".github/workflows/ci.yml"), "on: push").unwrap();
+    fs::write(root.join(".gitignore"), "target").unwrap();
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+    fs::create_dir_all(root.join("Node_Modules")).unwrap();
+
+    // dotfiles are shown by default, always hidden names are not (case insensitive)
+    let file_tree = scan_directory(root).unwrap();
+    assert!(file_tree.contains_key(&root.join(".github/workflows/ci.yml")));
+    assert!(!file_tree.contains_key(&root.join(".gitignore")) && !file_tree.contains_key(&root.join("Node_Modules")));
+
+    // always shown names win over the toggle and the always hidden list
+    let options = ScanOptions {
+      show_hidden: false,
+      always_shown: vec![".gitignore".to_string()],
+      ..Default::default()
+    };
+    let file_tree = scan_roots(&SessionRoots::single(root), &options).unwrap();
+    assert!(file_tree.contains_key(&root.join(".gitignore")) && file_tree.contains_key(&root.join("main.rs")));
+    assert!(!file_tree.contains_key(&root.join(".github")));
+  }
+
+  #[test]
+  fn test_binary_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR").unwrap();
+    fs::write(root.join("legacy.txt"), b"caf\xe9").unwrap();
+
+    let mut file_tree = scan_directory(root).unwrap();
+    assert_eq!(file_tree[&root.join("logo.png")].content, FileContent::Binary);
+    assert_eq!(file_tree[&root.join("legacy.txt")].content, FileContent::Text);
+    assert_eq!(file_tree[root].non_text_files, 1);
+
+    // selecting the dir skips the binary file, the dir still counts as fully selected
+    toggle_selection_recursive(&mut file_tree, root).unwrap();
+    assert_eq!(file_tree[root].selection_state(), SelectionState::All);
+    assert_eq!(get_selected_files(&file_tree).len(), 2);
+    assert!(toggle_selection_recursive(&mut file_tree, &root.join("logo.png")).unwrap().is_empty());
+
+    // flips from patterns and the API don't select it either
+    flip_file_selections(&mut file_tree, &[root.join("logo.png")]);
+    assert!(!file_tree[&root.join("logo.png")].is_selected);
+
+    // forcing selects it, undo and redo replay the forced change
+    let changed = force_toggle_selection(&mut file_tree, &root.join("logo.png")).unwrap();
+    assert_eq!(changed, vec![root.join("logo.png")]);
+    assert_eq!(get_selected_files(&file_tree).len(), 3);
+    replay_file_selections(&mut file_tree, &changed);
+    assert!(!file_tree[&root.join("logo.png")].is_selected);
+    replay_file_selections(&mut file_tree, &changed);
+    assert!(file_tree[&root.join("logo.png")].is_selected);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("packages/lib")).unwrap();
+    fs::write(root.join("packages/lib/index.ts"), "export {}").unwrap();
+    fs::create_dir(root.join("app")).unwrap();
+    symlink("../packages/lib", root.join("app/lib")).unwrap();
+    symlink("..", root.join("packages/lib/parent")).unwrap();
+
+    // not followed, links to dirs are empty dirs
+    let file_tree = scan_roots(&SessionRoots::single(root), &ScanOptions::default()).unwrap();
+    let link = &file_tree[&root.join("app/lib")];
+    assert!(link.is_directory && link.children.is_empty());
+    assert_eq!(link.link_target, Some(PathBuf::from("../packages/lib")));
+    assert!(file_tree[&root.join("packages/lib")].is_link_target);
+
+    // followed, the link back to a parent is kept but not walked
+    let options = ScanOptions { follow_symlinks: true, ..Default::default() };
+    let mut file_tree = scan_roots(&SessionRoots::single(root), &options).unwrap();
+    assert!(file_tree.contains_key(&root.join("app/lib/index.ts")));
+    assert!(file_tree[&root.join("app/lib/parent")].children.is_empty());
+    assert!(file_tree[&root.join("packages/lib/parent")].children.is_empty());
+
+    // the same file through the link is packed and counted once
+    toggle_selection_recursive(&mut file_tree, root).unwrap();
+    let selected = unique_files(&file_tree, get_selected_files(&file_tree));
+    assert_eq!(selected, vec![root.join("packages/lib/index.ts")]);
+  }
 }
@@ -0,0 +1,466 @@
+use crate::types::Backend;
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Named command that can be bound to one or more keys.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+  Quit,
+  Run,
+  CancelRun,
+  MoveUp,
+  MoveDown,
+  Collapse,
+  Expand,
+  ToggleSelection,
+  ForceSelection,
+  SelectByPattern,
+  CycleSort,
+  ToggleHidden,
+  ExpandAll,
+  CollapseAll,
+  SelectAll,
+  UnselectAll,
+  Undo,
+  Redo,
+  SwitchBackend,
+  SaveDefaultBackend,
+  ToggleCompress,
+  ToggleRemoveComments,
+  CycleOutputFormat,
+  ToggleFileTree,
+  CycleSizeLimit,
+  ShowHelp,
+  CommandPalette,
+  CycleTheme,
+  ShowLog,
+}
+
+/// Group an action is listed under in help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionCategory {
+  Navigation,
+  Selection,
+  Output,
+  General,
+}
+
+impl ActionCategory {
+  /// All categories, in the order they're listed in help.
+  pub const ALL: [ActionCategory; 4] = [ActionCategory::Navigation, ActionCategory::Selection, ActionCategory::Output, ActionCategory::General];
+
+  /// Returns the display name for the category.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      ActionCategory::Navigation => "Navigation",
+      ActionCategory::Selection => "Selection",
+      ActionCategory::Output => "Backend and output",
+      ActionCategory::General => "General",
+    }
+  }
+}
+
+impl Action {
+  /// All actions, in the order they're listed in help.
+  pub const ALL: [Action; 29] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::Collapse,
+    Action::Expand,
+    Action::ToggleSelection,
+    Action::ForceSelection,
+    Action::SelectByPattern,
+    Action::CycleSort,
+    Action::ToggleHidden,
+    Action::ExpandAll,
+    Action::CollapseAll,
+    Action::SelectAll,
+    Action::UnselectAll,
+    Action::Undo,
+    Action::Redo,
+    Action::SwitchBackend,
+    Action::SaveDefaultBackend,
+    Action::ToggleCompress,
+    Action::ToggleRemoveComments,
+    Action::CycleOutputFormat,
+    Action::ToggleFileTree,
+    Action::CycleSizeLimit,
+    Action::Run,
+    Action::CancelRun,
+    Action::ShowHelp,
+    Action::ShowLog,
+    Action::CommandPalette,
+    Action::CycleTheme,
+    Action::Quit,
+  ];
+
+  /// Returns the description shown in help.
+  pub fn description(&self) -> &'static str {
+    match self {
+      Action::Quit => "Quit",
+      Action::Run => "Run processing backend",
+      Action::CancelRun => "Cancel running backend",
+      Action::MoveUp => "Move up",
+      Action::MoveDown => "Move down",
+      Action::Collapse => "Collapse directory",
+      Action::Expand => "Expand directory",
+      Action::ToggleSelection => "Toggle selection",
+      Action::ForceSelection => "Toggle selection, including binary files",
+      Action::SelectByPattern => "Select by pattern",
+      Action::CycleSort => "Cycle sort mode",
+      Action::ToggleHidden => "Toggle hidden files",
+      Action::ExpandAll => "Expand all",
+      Action::CollapseAll => "Collapse all",
+      Action::SelectAll => "Select all",
+      Action::UnselectAll => "Unselect all",
+      Action::Undo => "Undo selection change",
+      Action::Redo => "Redo selection change",
+      Action::SwitchBackend => "Switch backend",
+      Action::SaveDefaultBackend => "Make backend the default",
+      Action::ToggleCompress => "Toggle compress",
+      Action::ToggleRemoveComments => "Toggle remove comments",
+      Action::CycleOutputFormat => "Cycle output format",
+      Action::ToggleFileTree => "Toggle file tree in output",
+      Action::CycleSizeLimit => "Cycle output size limit",
+      Action::ShowHelp => "Show help",
+      Action::CommandPalette => "Open command palette",
+      Action::CycleTheme => "Cycle color theme",
+      Action::ShowLog => "Show message log",
+    }
+  }
+
+  /// Returns the help category of the action.
+  pub fn category(&self) -> ActionCategory {
+    match self {
+      Action::MoveUp | Action::MoveDown | Action::Collapse | Action::Expand | Action::ExpandAll | Action::CollapseAll | Action::CycleSort | Action::ToggleHidden => ActionCategory::Navigation,
+      Action::ToggleSelection | Action::ForceSelection | Action::SelectByPattern | Action::SelectAll | Action::UnselectAll | Action::Undo | Action::Redo => ActionCategory::Selection,
+      Action::SwitchBackend | Action::SaveDefaultBackend | Action::ToggleCompress | Action::ToggleRemoveComments | Action::CycleOutputFormat | Action::ToggleFileTree | Action::CycleSizeLimit | Action::Run | Action::CancelRun => {
+        ActionCategory::Output
+      }
+      Action::ShowHelp | Action::ShowLog | Action::CommandPalette | Action::CycleTheme | Action::Quit => ActionCategory::General,
+    }
+  }
+
+  /// Returns true if the action applies to the given backend.
+  pub fn is_available(&self, backend: &Backend) -> bool {
+    match self {
+      Action::ToggleCompress | Action::ToggleRemoveComments | Action::CycleOutputFormat => *backend == Backend::Repomix,
+      Action::CycleSizeLimit => *backend == Backend::Yek,
+      // custom backends only take the options from their config
+      Action::ToggleFileTree => !matches!(backend, Backend::Custom(_)),
+      _ => true,
+    }
+  }
+}
+
+/// Actions shown together in the hints bar, with their short label.
+const HINT_GROUPS: &[(&[Action], &str)] = &[
+  (&[Action::ShowHelp], "help"),
+  (&[Action::MoveUp, Action::MoveDown], "navigate"),
+  (&[Action::Collapse, Action::Expand], "collapse/expand dirs"),
+  (&[Action::ToggleSelection], "select files"),
+  (&[Action::SelectByPattern], "select by pattern"),
+  (&[Action::CycleSort], "sort"),
+  (&[Action::ExpandAll], "expand all"),
+  (&[Action::CollapseAll], "collapse all"),
+  (&[Action::SelectAll], "select all"),
+  (&[Action::UnselectAll], "unselect all"),
+  (&[Action::Undo, Action::Redo], "undo/redo"),
+  (&[Action::SwitchBackend], "backend"),
+  (&[Action::Run], "run"),
+  (&[Action::Quit], "quit"),
+];
+
+/// A key with its modifiers, as typed in config (`ctrl+r`, `space`, `A`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+  pub code: KeyCode,
+  pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+  /// Creates a binding, dropping shift for characters since the case already carries it.
+  pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+    let modifiers = match code {
+      KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+      _ => modifiers,
+    };
+    Self { code, modifiers }
+  }
+
+  /// Parses a key description such as `j`, `ctrl+r`, `alt+x`, `space`, `up` or `f1`.
+  pub fn parse(text: &str) -> Result<Self> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut key = text;
+
+    // everything before the last `+` is a modifier (a lone `+` is the key itself)
+    while let Some((modifier, rest)) = key.split_once('+').filter(|(_, rest)| !rest.is_empty()) {
+      modifiers |= match modifier.to_lowercase().as_str() {
+        "ctrl" | "control" => KeyModifiers::CONTROL,
+        "alt" => KeyModifiers::ALT,
+        "shift" => KeyModifiers::SHIFT,
+        _ => return Err(anyhow!("Unknown modifier '{}' in '{}'", modifier, text)),
+      };
+      key = rest;
+    }
+
+    let code = match key.to_lowercase().as_str() {
+      "space" => KeyCode::Char(' '),
+      "enter" | "return" => KeyCode::Enter,
+      "esc" | "escape" => KeyCode::Esc,
+      "tab" => KeyCode::Tab,
+      "backspace" => KeyCode::Backspace,
+      "delete" | "del" => KeyCode::Delete,
+      "up" => KeyCode::Up,
+      "down" => KeyCode::Down,
+      "left" => KeyCode::Left,
+      "right" => KeyCode::Right,
+      "home" => KeyCode::Home,
+      "end" => KeyCode::End,
+      "pageup" => KeyCode::PageUp,
+      "pagedown" => KeyCode::PageDown,
+      lower => {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+          (Some(c), None) => KeyCode::Char(c),
+          _ => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+            _ => return Err(anyhow!("Unknown key '{}'", text)),
+          },
+        }
+      }
+    };
+
+    Ok(Self::new(code, modifiers))
+  }
+
+  /// Returns the binding for a key event.
+  pub fn from_event(key: &KeyEvent) -> Self {
+    Self::new(key.code, key.modifiers)
+  }
+}
+
+impl fmt::Display for KeyBinding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.modifiers.contains(KeyModifiers::CONTROL) {
+      write!(f, "^")?;
+    }
+    if self.modifiers.contains(KeyModifiers::ALT) {
+      write!(f, "M-")?;
+    }
+    if self.modifiers.contains(KeyModifiers::SHIFT) {
+      write!(f, "S-")?;
+    }
+    match self.code {
+      KeyCode::Char(' ') => write!(f, "Space"),
+      KeyCode::Char(c) => write!(f, "{}", c),
+      KeyCode::Enter => write!(f, "Enter"),
+      KeyCode::Esc => write!(f, "Esc"),
+      KeyCode::Tab => write!(f, "Tab"),
+      KeyCode::Backspace => write!(f, "Backspace"),
+      KeyCode::Delete => write!(f, "Del"),
+      KeyCode::Up => write!(f, "↑"),
+      KeyCode::Down => write!(f, "↓"),
+      KeyCode::Left => write!(f, "←"),
+      KeyCode::Right => write!(f, "→"),
+      KeyCode::Home => write!(f, "Home"),
+      KeyCode::End => write!(f, "End"),
+      KeyCode::PageUp => write!(f, "PgUp"),
+      KeyCode::PageDown => write!(f, "PgDn"),
+      KeyCode::F(n) => write!(f, "F{}", n),
+      _ => write!(f, "?"),
+    }
+  }
+}
+
+/// Built-in set of default bindings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapPreset {
+  /// Arrow keys plus h/j/k/l
+  #[default]
+  Vim,
+  /// Arrow keys only
+  Arrows,
+}
+
+impl KeymapPreset {
+  /// Returns the default keys for an action in this preset.
+  fn default_keys(&self, action: Action) -> &'static [&'static str] {
+    let vim = *self == KeymapPreset::Vim;
+    match action {
+      Action::Quit => &["q", "esc", "ctrl+c"],
+      Action::Run => &["r"],
+      Action::CancelRun => &["x"],
+      Action::MoveUp if vim => &["up", "k"],
+      Action::MoveUp => &["up"],
+      Action::MoveDown if vim => &["down", "j"],
+      Action::MoveDown => &["down"],
+      Action::Collapse if vim => &["left", "h"],
+      Action::Collapse => &["left"],
+      Action::Expand if vim => &["right", "l"],
+      Action::Expand => &["right"],
+      Action::ToggleSelection => &["space"],
+      Action::ForceSelection => &["B"],
+      Action::SelectByPattern => &["/"],
+      Action::CycleSort => &["s"],
+      Action::ToggleHidden => &["."],
+      Action::ExpandAll => &["E"],
+      Action::CollapseAll => &["C"],
+      Action::SelectAll => &["A"],
+      Action::UnselectAll => &["U"],
+      Action::Undo => &["u"],
+      Action::Redo => &["ctrl+r"],
+      Action::SwitchBackend => &["b"],
+      Action::SaveDefaultBackend => &["d"],
+      Action::ToggleCompress => &["c"],
+      Action::ToggleRemoveComments => &["m"],
+      Action::CycleOutputFormat => &["f"],
+      Action::ToggleFileTree => &["t"],
+      Action::CycleSizeLimit => &["z"],
+      Action::ShowHelp => &["?"],
+      Action::ShowLog => &["L"],
+      Action::CommandPalette => &[":", "ctrl+p"],
+      // only reachable from the command palette unless bound in config
+      Action::CycleTheme => &[],
+    }
+  }
+}
+
+/// Keybinding settings stored in config.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct KeymapConfig {
+  /// Preset the bindings start from
+  #[serde(default)]
+  pub preset: KeymapPreset,
+  /// Per action overrides, replacing the preset keys (an empty list unbinds the action)
+  #[serde(default)]
+  pub bindings: BTreeMap<Action, Vec<String>>,
+}
+
+/// Active mapping between keys and actions.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+  /// Action for each bound key
+  actions: HashMap<KeyBinding, Action>,
+  /// Keys for each action, in the order they were configured
+  keys: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+  fn default() -> Self {
+    Self::from_config(&KeymapConfig::default()).expect("default keymap has no conflicts")
+  }
+}
+
+impl Keymap {
+  /// Builds the keymap from config, failing on unknown keys or keys bound to more than one action.
+  pub fn from_config(config: &KeymapConfig) -> Result<Self> {
+    let mut keymap = Self {
+      actions: HashMap::new(),
+      keys: HashMap::new(),
+    };
+    let mut conflicts = Vec::new();
+
+    for action in Action::ALL {
+      let key_texts: Vec<&str> = match config.bindings.get(&action) {
+        Some(keys) => keys.iter().map(String::as_str).collect(),
+        None => config.preset.default_keys(action).to_vec(),
+      };
+
+      for key_text in key_texts {
+        let binding = KeyBinding::parse(key_text)?;
+        match keymap.actions.get(&binding) {
+          Some(existing) if *existing != action => conflicts.push(format!("'{}' is bound to both '{}' and '{}'", key_text, existing.description(), action.description())),
+          Some(_) => {}
+          None => {
+            keymap.actions.insert(binding, action);
+            keymap.keys.entry(action).or_default().push(binding);
+          }
+        }
+      }
+    }
+
+    if !conflicts.is_empty() {
+      return Err(anyhow!("Keymap conflict: {}", conflicts.join(", ")));
+    }
+
+    Ok(keymap)
+  }
+
+  /// Returns the action bound to a key event, if any.
+  pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+    self.actions.get(&KeyBinding::from_event(key)).copied()
+  }
+
+  /// Returns the keys bound to an action.
+  pub fn keys_for(&self, action: Action) -> &[KeyBinding] {
+    self.keys.get(&action).map(Vec::as_slice).unwrap_or(&[])
+  }
+
+  /// Returns the first key bound to an action, as shown in hints.
+  pub fn first_key(&self, action: Action) -> Option<String> {
+    self.keys_for(action).first().map(ToString::to_string)
+  }
+
+  /// Generates the hints bar text from the first key of each action.
+  /// Groups with an unbound action are left out.
+  pub fn hints(&self, backend: &Backend) -> String {
+    HINT_GROUPS
+      .iter()
+      .filter(|(actions, _)| actions.iter().all(|action| action.is_available(backend)))
+      .filter_map(|(actions, label)| {
+        let keys: Option<Vec<String>> = actions.iter().map(|action| self.first_key(*action)).collect();
+        keys.map(|keys| format!("{} {}", keys.join("/"), label))
+      })
+      .collect::<Vec<_>>()
+      .join(" • ")
+  }
+}
+
+// test for key parsing, presets and conflict detection
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_key_binding_parse() {
+    assert_eq!(KeyBinding::parse("ctrl+r").unwrap(), KeyBinding::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+    assert_eq!(KeyBinding::parse("space").unwrap().to_string(), "Space");
+    assert_eq!(KeyBinding::parse("+").unwrap(), KeyBinding::new(KeyCode::Char('+'), KeyModifiers::NONE));
+    assert_eq!(KeyBinding::parse("F5").unwrap().code, KeyCode::F(5));
+    assert!(KeyBinding::parse("hyper+x").is_err());
+    assert!(KeyBinding::parse("nope").is_err());
+
+    // shifted characters match the uppercase binding
+    let event = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT);
+    assert_eq!(Keymap::default().action_for(&event), Some(Action::SelectAll));
+  }
+
+  #[test]
+  fn test_keymap_presets_and_overrides() {
+    let default = Keymap::default();
+    assert_eq!(default.action_for(&KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)), Some(Action::MoveDown));
+    assert!(default.hints(&Backend::Repomix).starts_with("? help • ↑/↓ navigate • ←/→ collapse/expand dirs • Space select files"));
+
+    let mut config = KeymapConfig {
+      preset: KeymapPreset::Arrows,
+      ..Default::default()
+    };
+    config.bindings.insert(Action::Run, vec!["enter".to_string()]);
+    let arrows = Keymap::from_config(&config).unwrap();
+    assert_eq!(arrows.action_for(&KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+    assert_eq!(arrows.action_for(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)), Some(Action::Run));
+    assert!(arrows.hints(&Backend::Yek).contains("Enter run"));
+
+    // binding a key already used by another action is a conflict
+    config.bindings.insert(Action::Undo, vec!["s".to_string()]);
+    let error = Keymap::from_config(&config).unwrap_err().to_string();
+    assert!(error.contains("'s' is bound to both 'Cycle sort mode' and 'Undo selection change'"));
+  }
+}
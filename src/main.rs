@@ -1,15 +1,30 @@
+mod api;
 mod app;
+mod command_palette;
 mod config;
+mod custom_backend;
+mod encoding;
 mod file_utils;
+mod keymap;
+mod mcp;
+mod notifications;
+mod packer;
+mod process_runner;
+mod redaction;
 mod repomix_integration;
+mod secrets;
+mod selection_history;
+mod selection_patterns;
+mod theme;
 mod token_counter;
 mod types;
 mod ui;
 mod yek_integration;
 
-use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 /// Main siff entry point
 #[derive(Parser)]
@@ -17,10 +32,15 @@ use std::path::PathBuf;
 #[command(about = "File browser with repomix and yek as supported parsing backends")]
 #[command(version = "0.1.1")]
 #[command(long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-  /// Directory to scan for files (defaults to current dir)
+  /// Maintenance commands (the file browser runs when none is given)
+  #[command(subcommand)]
+  command: Option<Commands>,
+
+  /// Directories to scan for files (defaults to current dir), several are shown as top-level nodes
   #[arg(value_name = "DIRECTORY")]
-  directory: Option<PathBuf>,
+  directories: Vec<PathBuf>,
 
   /// Enable verbose output for debugging
   #[arg(short, long)]
@@ -33,12 +53,93 @@ struct Cli {
   /// Use repomix backend (default)
   #[arg(long)]
   repomix: bool,
+
+  /// Serve the local JSON API on 127.0.0.1:PORT or unix:/path (overrides the config)
+  #[arg(long, value_name = "ADDRESS")]
+  api: Option<String>,
+
+  /// Select the paths listed in a file, or stdin with -, one per line or NUL separated
+  #[arg(long, value_name = "FILE|-")]
+  select_from: Option<PathBuf>,
+}
+
+/// Siff subcommands.
+#[derive(Subcommand)]
+enum Commands {
+  /// Manage cached backend installs
+  Cache {
+    #[command(subcommand)]
+    action: CacheCommand,
+  },
+  /// Run an MCP server on stdio exposing the file tree, selection and packing
+  Mcp {
+    /// Directories to serve (defaults to current dir)
+    #[arg(value_name = "DIRECTORY")]
+    directories: Vec<PathBuf>,
+
+    /// Backend used to pack the selection: repomix, yek or a custom backend name (defaults to the saved backend)
+    #[arg(long)]
+    backend: Option<String>,
+  },
+  /// Pack the listed paths without the UI, writing the packed content to stdout
+  Pack {
+    /// Directories the paths are relative to (defaults to current dir)
+    #[arg(value_name = "DIRECTORY")]
+    directories: Vec<PathBuf>,
+
+    /// File with the paths to pack, or stdin with -, one per line or NUL separated
+    #[arg(long, value_name = "FILE|-")]
+    select_from: PathBuf,
+
+    /// Backend used to pack: repomix, yek or a custom backend name (defaults to the saved backend)
+    #[arg(long)]
+    backend: Option<String>,
+
+    /// Write the packed content to a file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+  },
+}
+
+/// Actions for the repomix install cache.
+#[derive(Subcommand)]
+enum CacheCommand {
+  /// List cached repomix installs and verify them
+  List,
+  /// Remove cached repomix installs other than the configured one
+  Prune {
+    /// Also remove the configured install
+    #[arg(long)]
+    all: bool,
+  },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
   // parse command line arguments
-  let cli = Cli::parse();
+  let cli = match Cli::try_parse() {
+    Ok(cli) => cli,
+    Err(e) => {
+      let _ = e.print();
+      if let Some(hint) = e.use_stderr().then(|| subcommand_dir_hint(std::env::args_os().nth(1))).flatten() {
+        eprintln!("\n{}", hint);
+      }
+      std::process::exit(e.exit_code());
+    }
+  };
+
+  match cli.command {
+    Some(Commands::Cache { action }) => return run_cache_command(action),
+    Some(Commands::Mcp { directories, backend }) => {
+      // stdout belongs to the protocol, errors go to stderr
+      let directories = resolve_directories(directories)?;
+      return mcp::run_server(&directories, parse_backend(backend.as_deref())?).await;
+    }
+    Some(Commands::Pack { directories, select_from, backend, output }) => {
+      return run_pack_command(&resolve_directories(directories)?, &select_from, parse_backend(backend.as_deref())?, output).await;
+    }
+    None => {}
+  }
 
   // determine the backend to use
   let backend = if cli.yek && cli.repomix {
@@ -56,23 +157,16 @@ async fn main() -> Result<()> {
     }
   };
 
-  // determine the directory to scan
-  let target_directory = cli.directory.unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-
-  // validate that the dir exists
-  if !target_directory.exists() {
-    anyhow::bail!("Directory does not exist: {}", target_directory.display());
-  }
-
-  if !target_directory.is_dir() {
-    anyhow::bail!("Path is not a directory: {}", target_directory.display());
-  }
+  // determine the directories to scan
+  let target_directories = resolve_directories(cli.directories)?;
 
   // print startup info if verbose
   if cli.verbose {
     println!("Starting Siff...");
     println!("Backend: {}", backend.display_name());
-    println!("Target directory: {}", target_directory.display());
+    for directory in &target_directories {
+      println!("Target directory: {}", directory.display());
+    }
     println!("Scanning for files...");
   }
 
@@ -90,17 +184,32 @@ async fn main() -> Result<()> {
         eprintln!("This is a one-time setup and subsequent runs will be fast.");
       }
       types::Backend::Yek => {
-        eprintln!("\nSiff includes yek integration but failed to initialize.");
-        eprintln!("This is likely a build or installation issue.");
-        eprintln!("Please try reinstalling Siff:");
-        eprintln!("  cargo install --force siff");
+        eprintln!("\nSiff uses yek from your PATH, or installs it with cargo on first use.");
+        eprintln!("Please install yek or Rust (which includes cargo):");
+        eprintln!("  cargo install yek");
+        eprintln!("  Rust: https://rustup.rs/");
+      }
+      types::Backend::Custom(_) => {
+        eprintln!("\nCheck the custom_backends section of the config file, or start with --repomix or --yek.");
       }
     }
     std::process::exit(1);
   }
 
+  // the local API is off unless an address is given here or in the config
+  let api_address = match cli.api.or_else(|| config::SifConfig::load().ok().and_then(|config| config.api.listen)) {
+    Some(address) => Some(api::ApiAddress::parse(&address)?),
+    None => None,
+  };
+
+  // read the list before the UI takes over the terminal, stdin may be a pipe
+  let initial_selection = match &cli.select_from {
+    Some(source) => file_utils::read_path_list(source)?,
+    None => Vec::new(),
+  };
+
   // run the app
-  if let Err(e) = app::run_app(&target_directory, backend).await {
+  if let Err(e) = app::run_app(&target_directories, backend, api_address, initial_selection).await {
     eprintln!("Error: {}", e);
 
     // print the error chain for debugging
@@ -116,23 +225,148 @@ async fn main() -> Result<()> {
   Ok(())
 }
 
+/// Points out a directory with the same name as the subcommand, which `siff ./NAME` opens instead.
+fn subcommand_dir_hint(first_arg: Option<std::ffi::OsString>) -> Option<String> {
+  let name = first_arg?.into_string().ok()?;
+  let is_subcommand = Cli::command().get_subcommands().any(|subcommand| subcommand.get_name() == name);
+  (is_subcommand && Path::new(&name).is_dir()).then(|| format!("Note: `{0}` is a siff command, use `siff ./{0}` to open the directory named {0}", name))
+}
+
+/// Returns the directories to scan (the current dir if none were given), they have to exist.
+fn resolve_directories(directories: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+  if directories.is_empty() {
+    return Ok(vec![std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))]);
+  }
+
+  for target_directory in &directories {
+    // validate that the dir exists
+    if !target_directory.exists() {
+      anyhow::bail!("Directory does not exist: {}", target_directory.display());
+    }
+
+    if !target_directory.is_dir() {
+      anyhow::bail!("Path is not a directory: {}", target_directory.display());
+    }
+  }
+
+  Ok(directories)
+}
+
+/// Parses a backend name from the command line, the saved default backend if none was given.
+fn parse_backend(name: Option<&str>) -> Result<types::Backend> {
+  let config = config::SifConfig::load().unwrap_or_default();
+  match name {
+    None => Ok(config.default_backend),
+    Some(name) if name.eq_ignore_ascii_case("repomix") => Ok(types::Backend::Repomix),
+    Some(name) if name.eq_ignore_ascii_case("yek") => Ok(types::Backend::Yek),
+    Some(name) => match custom_backend::find(&config.custom_backends, name) {
+      Some(custom_backend) => Ok(types::Backend::Custom(custom_backend.name.clone())),
+      None => anyhow::bail!("Unknown backend '{}', use repomix, yek or a custom backend from the config", name),
+    },
+  }
+}
+
 /// Checks if the chosen backend is available in the system PATH.
 /// To check if can actually run the backend before starting the app.
 async fn check_backend_availability(backend: &types::Backend) -> Result<()> {
   match backend {
     types::Backend::Repomix => {
       // check if npm is available for downloading repomix
-      crate::repomix_integration::Repomix::check_build_dependencies().await
+      let repomix_config = config::SifConfig::load().map(|config| config.repomix).unwrap_or_default();
+      crate::repomix_integration::Repomix::check_build_dependencies(&repomix_config).await
     }
     types::Backend::Yek => {
-      // for yek, use the embedded binary so it's always available
-      // just need to check if it can be initialized
-      match crate::yek_integration::Yek::new() {
-        Ok(_) => Ok(()),
-        Err(e) => Err(anyhow::anyhow!("Yek backend failed: {}", e)),
+      // yek is found or installed in the background once the UI is up, only check that's possible
+      let yek_config = config::SifConfig::load().map(|config| config.yek).unwrap_or_default();
+      crate::yek_integration::YekProvisioner::check_available(&yek_config).map_err(|e| anyhow::anyhow!("Yek backend failed: {}", e))
+    }
+    types::Backend::Custom(name) => {
+      let custom_backends = config::SifConfig::load().map(|config| config.custom_backends).unwrap_or_default();
+      let custom_backend = custom_backend::find(&custom_backends, name).ok_or_else(|| anyhow::anyhow!("Custom backend '{}' is not defined in the config", name))?;
+      custom_backend.check_available()
+    }
+  }
+}
+
+/// Packs the listed paths with the backend, the content goes to stdout or the output file and the summary to stderr.
+async fn run_pack_command(roots: &[PathBuf], select_from: &Path, backend: types::Backend, output: Option<PathBuf>) -> Result<()> {
+  let paths = file_utils::read_path_list(select_from)?;
+  let config = config::SifConfig::load().unwrap_or_default();
+  let roots = file_utils::SessionRoots::new(roots)?;
+  let (scan_roots, scan_options) = (roots.clone(), config.scan.clone());
+  let mut file_tree = tokio::task::spawn_blocking(move || file_utils::scan_roots(&scan_roots, &scan_options)).await??;
+
+  let (resolved, unknown) = file_utils::resolve_paths(&file_tree, &roots, &paths);
+  for path in &unknown {
+    eprintln!("Warning: not in the tree: {}", path);
+  }
+  for path in &resolved {
+    if let Some(node) = file_tree.get(path).filter(|node| !node.is_directory && !node.content.is_text()) {
+      eprintln!("Warning: {} file not selected: {}", node.content.display_name(), api::relative_path(&roots, path));
+    }
+    file_utils::set_selection(&mut file_tree, path, true)?;
+  }
+  let selected_files = file_utils::get_selected_files(&file_tree);
+  if selected_files.is_empty() {
+    anyhow::bail!("None of the {} listed paths matched a file in the tree", paths.len());
+  }
+
+  let mut packer = packer::HeadlessPacker::new(backend, config);
+  let pack = packer.pack(&file_tree, &roots, selected_files).await?;
+  for line in &pack.summary {
+    eprintln!("{}", line);
+  }
+
+  match output {
+    Some(output) => std::fs::write(&output, &pack.content).with_context(|| format!("Failed to write {}", output.display()))?,
+    None => {
+      let mut stdout = std::io::stdout().lock();
+      stdout.write_all(pack.content.as_bytes())?;
+      stdout.flush()?;
+    }
+  }
+  Ok(())
+}
+
+/// Lists or prunes the repomix install cache.
+fn run_cache_command(action: CacheCommand) -> Result<()> {
+  use repomix_integration::{CachedInstall, Repomix};
+
+  // the configured install is kept when pruning
+  let repomix_config = config::SifConfig::load().map(|config| config.repomix).unwrap_or_default();
+  let active = Repomix::cache_dir_for(&repomix_config).ok().flatten().map(|(path, _)| path);
+
+  let describe = |install: &CachedInstall| {
+    let name = install.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let status = match &install.status {
+      Ok(version) => format!("repomix {}, verified", version),
+      Err(problem) => format!("invalid: {}", problem),
+    };
+    let marker = if active.as_ref() == Some(&install.path) { " (active)" } else { "" };
+    format!("  {:<20} {:>8.1} MB  {}{}", name, install.size as f64 / (1024.0 * 1024.0), status, marker)
+  };
+
+  match action {
+    CacheCommand::List => {
+      let installs = repomix_integration::cached_installs()?;
+      if installs.is_empty() {
+        println!("No cached repomix installs");
+      }
+      for install in &installs {
+        println!("{}", describe(install));
+      }
+    }
+    CacheCommand::Prune { all } => {
+      let keep = if all { None } else { active.as_deref() };
+      let removed = repomix_integration::prune_cache(keep)?;
+      println!("Removed {} cached repomix install(s)", removed.len());
+      for install in &removed {
+        println!("{}", describe(install));
       }
     }
   }
+
+  Ok(())
 }
 
 // test for cli parsing and directory validation
@@ -158,15 +392,43 @@ mod tests {
   fn test_cli_parsing() {
     // test default case
     let cli = Cli::parse_from(["siff"]);
-    assert!(cli.directory.is_none());
+    assert!(cli.directories.is_empty());
     assert!(!cli.verbose);
 
     // test with directory
     let cli = Cli::parse_from(["siff", "/tmp"]);
-    assert_eq!(cli.directory, Some(PathBuf::from("/tmp")));
+    assert_eq!(cli.directories, vec![PathBuf::from("/tmp")]);
+
+    // test several roots
+    let cli = Cli::parse_from(["siff", "service", "proto", "--yek"]);
+    assert_eq!(cli.directories, vec![PathBuf::from("service"), PathBuf::from("proto")]);
 
     // test with verbose flag
     let cli = Cli::parse_from(["siff", "--verbose"]);
     assert!(cli.verbose);
+
+    // test cache subcommands
+    let cli = Cli::parse_from(["siff", "cache", "prune", "--all"]);
+    assert!(matches!(cli.command, Some(Commands::Cache { action: CacheCommand::Prune { all: true } })));
+    assert!(Cli::try_parse_from(["siff", "cache", "list", "/tmp"]).is_err());
+
+    // test api address
+    let cli = Cli::parse_from(["siff", "--api", "127.0.0.1:7777"]);
+    assert_eq!(cli.api.as_deref(), Some("127.0.0.1:7777"));
+
+    // test mcp subcommand
+    let cli = Cli::parse_from(["siff", "mcp", "/tmp", "--backend", "yek"]);
+    assert!(matches!(cli.command, Some(Commands::Mcp { ref directories, backend: Some(ref name) }) if directories.len() == 1 && name == "yek"));
+
+    // test selection lists
+    let cli = Cli::parse_from(["siff", "/tmp", "--select-from", "-"]);
+    assert_eq!(cli.select_from, Some(PathBuf::from("-")));
+    let cli = Cli::parse_from(["siff", "pack", "--select-from", "files.txt", "-o", "out.txt"]);
+    assert!(matches!(cli.command, Some(Commands::Pack { ref directories, output: Some(_), .. }) if directories.is_empty()));
+    assert!(Cli::try_parse_from(["siff", "pack", "/tmp"]).is_err());
+
+    // a directory named like a subcommand is opened with a path
+    let cli = Cli::parse_from(["siff", "./pack"]);
+    assert!(cli.command.is_none() && cli.directories == vec![PathBuf::from("./pack")]);
   }
 }
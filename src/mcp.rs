@@ -0,0 +1,494 @@
+use crate::config::SifConfig;
+use crate::file_utils::{self, SessionRoots};
+use crate::packer::HeadlessPacker;
+use crate::redaction::{self, Redactor};
+use crate::secrets::{self, SecretPolicy};
+use crate::selection_patterns;
+use crate::token_counter::{format_token_count, TokenCounter};
+use crate::types::{Backend, FileNode, SelectionState};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// MCP protocol version used when the client doesn't ask for one.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Arguments of the list_tree tool.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ListTreeArgs {
+  /// Dir (or file) to list, relative to the root
+  path: Option<String>,
+  /// Levels below the path to show, everything if not set
+  depth: Option<usize>,
+}
+
+/// Arguments of the count_tokens tool.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CountTokensArgs {
+  /// Files or dirs to count, the selection if not set
+  paths: Option<Vec<String>>,
+}
+
+/// Arguments of the select and deselect tools.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SelectionArgs {
+  /// Files or dirs, relative to the root
+  paths: Vec<String>,
+  /// Selection patterns as typed into the pattern prompt (select only)
+  pattern: Option<String>,
+  /// Clear the whole selection (deselect only)
+  all: bool,
+}
+
+/// Arguments of the read_file tool.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ReadFileArgs {
+  /// File to read, relative to the root
+  path: String,
+  /// First line to return (1-based)
+  start_line: Option<usize>,
+  /// Last line to return (inclusive)
+  end_line: Option<usize>,
+}
+
+/// Session state of the MCP server: the scanned tree, the selection and the backend used for packing.
+pub struct McpServer {
+  /// Dirs the tree was scanned from
+  roots: SessionRoots,
+  /// Files siff includes, with the session's selection
+  file_tree: HashMap<PathBuf, FileNode>,
+  /// User config (backend options, secrets policy, redaction rules)
+  config: SifConfig,
+  /// Token counter with a cache for the session
+  token_counter: TokenCounter,
+  /// Runs pack_selection with the session's backend
+  packer: HeadlessPacker,
+}
+
+impl McpServer {
+  /// Scans the root and creates a session with nothing selected.
+  pub async fn new(roots: &[PathBuf], backend: Backend, config: SifConfig) -> Result<Self> {
+    let roots = SessionRoots::new(roots)?;
+    let (scan_roots, scan_options) = (roots.clone(), config.scan.clone());
+    let file_tree = tokio::task::spawn_blocking(move || file_utils::scan_roots(&scan_roots, &scan_options)).await.context("Scanning the directory failed")??;
+
+    Ok(Self {
+      roots,
+      file_tree,
+      packer: HeadlessPacker::new(backend, config.clone()),
+      config,
+      token_counter: TokenCounter::new()?,
+    })
+  }
+
+  /// Handles one JSON-RPC message and returns the response, None for notifications.
+  pub async fn handle_message(&mut self, message: Value) -> Option<Value> {
+    let Some(object) = message.as_object() else {
+      return Some(error_response(Value::Null, INVALID_REQUEST, "Expected a JSON-RPC object".to_string()));
+    };
+    let id = object.get("id").cloned();
+    let Some(method) = object.get("method").and_then(Value::as_str) else {
+      // responses to requests the server never sends are ignored
+      return id
+        .filter(|_| !object.contains_key("result") && !object.contains_key("error"))
+        .map(|id| error_response(id, INVALID_REQUEST, "Missing method".to_string()));
+    };
+    let params = object.get("params").cloned().unwrap_or_else(|| json!({}));
+
+    let result = match method {
+      "initialize" => Ok(initialize_result(&params)),
+      "ping" => Ok(json!({})),
+      "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+      "tools/call" => self.call_tool(&params).await,
+      // notifications (initialized, cancelled) need no reply
+      _ if id.is_none() => return None,
+      _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    };
+
+    let id = id?;
+    Some(match result {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err((code, message)) => error_response(id, code, message),
+    })
+  }
+
+  /// Runs a tool, tool failures are reported in the result so the agent can read them.
+  async fn call_tool(&mut self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+    let name = params.get("name").and_then(Value::as_str).ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+    let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+    let output = match name {
+      "list_tree" => parse_arguments(arguments).and_then(|args| self.list_tree(args)),
+      "count_tokens" => match parse_arguments(arguments) {
+        Ok(args) => self.count_tokens(args).await,
+        Err(e) => Err(e),
+      },
+      "select" => parse_arguments(arguments).and_then(|args| self.change_selection(args, true)),
+      "deselect" => parse_arguments(arguments).and_then(|args| self.change_selection(args, false)),
+      "read_file" => match parse_arguments(arguments) {
+        Ok(args) => self.read_file(args).await,
+        Err(e) => Err(e),
+      },
+      "pack_selection" => self.pack_selection().await,
+      _ => return Err((INVALID_PARAMS, format!("Unknown tool: {}", name))),
+    };
+
+    Ok(match output {
+      Ok(texts) => json!({ "content": texts.into_iter().map(|text| json!({ "type": "text", "text": text })).collect::<Vec<_>>() }),
+      Err(e) => json!({ "content": [{ "type": "text", "text": format!("Error: {:#}", e) }], "isError": true }),
+    })
+  }
+
+  /// Resolves a path relative to the root, it has to be in siff's tree.
+  fn resolve(&self, path: &str) -> Result<PathBuf> {
+    let trimmed = path.trim().trim_start_matches("./");
+    if Path::new(trimmed).components().any(|component| matches!(component, Component::ParentDir)) {
+      anyhow::bail!("Paths can't leave the root: {}", path);
+    }
+    match self.roots.resolve(Path::new(trimmed)).filter(|resolved| self.file_tree.contains_key(resolved)) {
+      Some(resolved) => Ok(resolved),
+      None => anyhow::bail!("{} is not in the tree (missing, ignored by siff or outside the root)", path),
+    }
+  }
+
+  /// Returns the path relative to the root, for output.
+  fn relative(&self, path: &Path) -> String {
+    self.roots.relative(path).to_string_lossy().to_string()
+  }
+
+  /// Lists the tree with selection markers: `[x]` selected, `[~]` partly selected, `[ ]` not selected.
+  fn list_tree(&self, args: ListTreeArgs) -> Result<Vec<String>> {
+    let start = self.resolve(args.path.as_deref().unwrap_or("."))?;
+    let mut lines = Vec::new();
+    let mut stack = vec![(start.clone(), 0)];
+    while let Some((path, level)) = stack.pop() {
+      let Some(node) = self.file_tree.get(&path) else {
+        continue;
+      };
+      let marker = match node.selection_state() {
+        SelectionState::All => "[x]",
+        SelectionState::Partial => "[~]",
+        SelectionState::None => "[ ]",
+      };
+      let name = if path == start { self.relative(&path) } else { node.name.clone() };
+      let name = if name.is_empty() { ".".to_string() } else { name };
+      if node.is_directory {
+        lines.push(format!("{}{} {}/ ({}/{} files selected)", "  ".repeat(level), marker, name.trim_end_matches('/'), node.selected_files, node.total_files));
+        if args.depth.is_none_or(|depth| level < depth) {
+          stack.extend(node.children.iter().rev().map(|child| (child.clone(), level + 1)));
+        }
+      } else if !node.content.is_text() {
+        lines.push(format!("{}{} {} ({}, not selectable)", "  ".repeat(level), marker, name, node.content.display_name()));
+      } else {
+        lines.push(format!("{}{} {}", "  ".repeat(level), marker, name));
+      }
+    }
+
+    let selected = file_utils::get_selected_files(&self.file_tree).len();
+    lines.push(format!("\n{} files selected", selected));
+    Ok(vec![lines.join("\n")])
+  }
+
+  /// Counts tokens of the given files and dirs, or of the selection.
+  async fn count_tokens(&self, args: CountTokensArgs) -> Result<Vec<String>> {
+    let files = match args.paths {
+      Some(paths) => {
+        let mut files = Vec::new();
+        for path in paths {
+          files.extend(file_utils::files_under(&self.file_tree, &self.resolve(&path)?));
+        }
+        files.sort();
+        files.dedup();
+        files
+      }
+      None => file_utils::get_selected_files(&self.file_tree),
+    };
+    let files = file_utils::unique_files(&self.file_tree, files);
+    if files.is_empty() {
+      anyhow::bail!("No files to count, select files or pass paths");
+    }
+
+    let mut counts = Vec::with_capacity(files.len());
+    for file in &files {
+      counts.push((self.token_counter.count_file_tokens(file).await?, file));
+    }
+    counts.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let total: usize = counts.iter().map(|(count, _)| count).sum();
+    let mut lines: Vec<String> = counts.iter().map(|(count, file)| format!("{:>8}  {}", format_token_count(*count), self.relative(file))).collect();
+    lines.push(format!("\nTotal: {} tokens in {} files", total, files.len()));
+    Ok(vec![lines.join("\n")])
+  }
+
+  /// Adds paths or pattern matches to the selection, or removes paths from it.
+  fn change_selection(&mut self, args: SelectionArgs, selected: bool) -> Result<Vec<String>> {
+    let mut changed = 0;
+    if selected && args.all {
+      anyhow::bail!("'all' only applies to deselect");
+    }
+    if !selected && args.pattern.is_some() {
+      anyhow::bail!("Patterns only apply to select, use select with '!pattern' to remove matches");
+    }
+    if args.paths.is_empty() && args.pattern.is_none() && !args.all {
+      anyhow::bail!("Pass paths{}", if selected { " or a pattern" } else { " or all" });
+    }
+
+    // resolve paths and parse the pattern first, so a bad argument changes nothing
+    let paths = args.paths.iter().map(|path| self.resolve(path)).collect::<Result<Vec<_>>>()?;
+    let patterns = args.pattern.as_deref().map(selection_patterns::parse_patterns).transpose()?;
+
+    if args.all {
+      changed += file_utils::unselect_all_items(&mut self.file_tree).len();
+    }
+    for path in paths {
+      changed += file_utils::set_selection(&mut self.file_tree, &path, selected)?.len();
+    }
+    if let Some(patterns) = patterns {
+      let preview = selection_patterns::preview_patterns(&self.file_tree, &self.roots, &patterns);
+      let flipped = preview.changed_files();
+      file_utils::flip_file_selections(&mut self.file_tree, &flipped);
+      changed += flipped.len();
+    }
+
+    let total = file_utils::get_selected_files(&self.file_tree).len();
+    Ok(vec![format!("{} files changed, {} files selected", changed, total)])
+  }
+
+  /// Reads a file in the tree, with the secrets policy and redaction rules applied like in packs.
+  async fn read_file(&self, args: ReadFileArgs) -> Result<Vec<String>> {
+    let path = self.resolve(&args.path)?;
+    if self.file_tree.get(&path).is_some_and(|node| node.is_directory) {
+      anyhow::bail!("{} is a directory, use list_tree", args.path);
+    }
+    let bytes = tokio::fs::read(&path).await.with_context(|| format!("Failed to read {}", args.path))?;
+    // bigger files can't be scanned for secrets
+    if bytes.len() as u64 > secrets::MAX_SCAN_SIZE {
+      anyhow::bail!("{} is too large to read ({} bytes)", args.path, bytes.len());
+    }
+    if crate::encoding::detect(&bytes).is_none() {
+      anyhow::bail!("{} is not a text file", args.path);
+    }
+
+    let policy = self.config.secrets.policy;
+    let allowed = self.config.secrets.allowed()?;
+    let redactor = Redactor::new(&self.config.redaction_rules)?;
+    let prepared = redaction::prepare_content(&path, &self.roots.relative(&path), &bytes, policy, &allowed, &redactor);
+    if let (Some(flagged), SecretPolicy::Block) = (&prepared.flagged, policy) {
+      anyhow::bail!("{} contains secrets ({}), blocked by the secrets policy", args.path, flagged.summary);
+    }
+
+    let start = args.start_line.unwrap_or(1).max(1);
+    let content = match args.end_line {
+      Some(end) if end < start => anyhow::bail!("end_line is before start_line"),
+      Some(end) => prepared.content.lines().skip(start - 1).take(end - start + 1).collect::<Vec<_>>().join("\n"),
+      None if start > 1 => prepared.content.lines().skip(start - 1).collect::<Vec<_>>().join("\n"),
+      None => prepared.content,
+    };
+
+    let mut texts = vec![content];
+    if let Some(flagged) = prepared.flagged {
+      texts.push(format!("Note: secrets found ({}), policy {}", flagged.summary, policy.display_name()));
+    }
+    if let Some(redacted) = prepared.redacted {
+      texts.push(format!("Note: redaction rules applied ({})", redacted.summary()));
+    }
+    Ok(texts)
+  }
+
+  /// Packs the selection with the backend and returns the summary and the packed content.
+  async fn pack_selection(&mut self) -> Result<Vec<String>> {
+    let selected_files = file_utils::get_selected_files(&self.file_tree);
+    let pack = self.packer.pack(&self.file_tree, &self.roots, selected_files).await?;
+    Ok(vec![pack.summary.join("\n"), pack.content])
+  }
+}
+
+/// Parses tool arguments, rejecting unknown ones.
+fn parse_arguments<T: serde::de::DeserializeOwned>(arguments: Value) -> Result<T> {
+  serde_json::from_value(arguments).context("Invalid arguments")
+}
+
+/// Builds a JSON-RPC error response.
+fn error_response(id: Value, code: i64, message: String) -> Value {
+  json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Answers initialize with the client's protocol version (or the default) and the tools capability.
+fn initialize_result(params: &Value) -> Value {
+  let protocol_version = params.get("protocolVersion").and_then(Value::as_str).unwrap_or(PROTOCOL_VERSION);
+  json!({
+    "protocolVersion": protocol_version,
+    "capabilities": { "tools": { "listChanged": false } },
+    "serverInfo": { "name": "siff", "version": env!("CARGO_PKG_VERSION") },
+    "instructions": "Use list_tree to see the files siff includes, select files by path or pattern, check the size with count_tokens, then call pack_selection for the packed context.",
+  })
+}
+
+/// Tool names, descriptions and input schemas for tools/list.
+fn tool_definitions() -> Value {
+  let paths = json!({ "type": "array", "items": { "type": "string" }, "description": "Paths relative to the root, dirs include everything in them" });
+  json!([
+    {
+      "name": "list_tree",
+      "description": "List the files siff includes (gitignore and siff's skip rules applied). [x] selected, [~] partly selected, [ ] not selected.",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "path": { "type": "string", "description": "Dir to list, relative to the root (default: the root)" },
+          "depth": { "type": "integer", "minimum": 0, "description": "Levels below the path to show (default: all)" },
+        },
+      },
+    },
+    {
+      "name": "count_tokens",
+      "description": "Count tokens per file and in total, for the given paths or the current selection.",
+      "inputSchema": { "type": "object", "properties": { "paths": paths } },
+    },
+    {
+      "name": "select",
+      "description": "Add files to the selection by path, or by siff patterns such as 'src/**/*.rs !**/tests/** ext:toml re:handler' (a leading ! removes matches).",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "paths": paths,
+          "pattern": { "type": "string", "description": "Whitespace separated patterns, applied in order" },
+        },
+      },
+    },
+    {
+      "name": "deselect",
+      "description": "Remove files from the selection by path, or clear it.",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "paths": paths,
+          "all": { "type": "boolean", "description": "Clear the whole selection" },
+        },
+      },
+    },
+    {
+      "name": "read_file",
+      "description": "Read a file in the tree, with the secrets policy and redaction rules applied.",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "path": { "type": "string", "description": "File path relative to the root" },
+          "start_line": { "type": "integer", "minimum": 1, "description": "First line to return (1-based)" },
+          "end_line": { "type": "integer", "minimum": 1, "description": "Last line to return (inclusive)" },
+        },
+        "required": ["path"],
+      },
+    },
+    {
+      "name": "pack_selection",
+      "description": "Pack the selected files with siff's backend and return the packed content.",
+      "inputSchema": { "type": "object", "properties": {} },
+    },
+  ])
+}
+
+/// Runs the MCP server on stdin/stdout until the client closes stdin.
+/// Messages are newline-delimited JSON-RPC, stdout carries nothing else.
+pub async fn run_server(roots: &[PathBuf], backend: Backend) -> Result<()> {
+  let config = SifConfig::load().context("Failed to load configuration")?;
+  let mut server = McpServer::new(roots, backend, config).await?;
+
+  let mut lines = BufReader::new(tokio::io::stdin()).lines();
+  let mut stdout = tokio::io::stdout();
+  while let Some(line) = lines.next_line().await.context("Failed to read from stdin")? {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let response = match serde_json::from_str::<Value>(&line) {
+      Ok(message) => server.handle_message(message).await,
+      Err(e) => Some(error_response(Value::Null, PARSE_ERROR, format!("Parse error: {}", e))),
+    };
+    if let Some(response) = response {
+      let mut text = serde_json::to_string(&response)?;
+      text.push('\n');
+      stdout.write_all(text.as_bytes()).await.context("Failed to write to stdout")?;
+      stdout.flush().await.context("Failed to write to stdout")?;
+    }
+  }
+
+  Ok(())
+}
+
+// test for mcp request handling
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  async fn call(server: &mut McpServer, name: &str, arguments: Value) -> (String, bool) {
+    let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": { "name": name, "arguments": arguments } });
+    let response = server.handle_message(request).await.unwrap();
+    let result = &response["result"];
+    let text = result["content"].as_array().unwrap().iter().map(|item| item["text"].as_str().unwrap()).collect::<Vec<_>>().join("\n");
+    (text, result["isError"].as_bool().unwrap_or(false))
+  }
+
+  #[tokio::test]
+  async fn test_mcp_session() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {\n  println!(\"hi\");\n}\n").unwrap();
+    std::fs::write(root.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+    std::fs::write(root.join("notes.md"), "# notes\n").unwrap();
+    std::fs::write(root.join("keys.py"), format!("key = \"AKIA{}\"\n", "Q3EGUZ7X2RJ5WNLP")).unwrap();
+    let mut config = SifConfig::default();
+    config.secrets.policy = SecretPolicy::Block;
+    let mut server = McpServer::new(&[root.to_path_buf()], Backend::Repomix, config).await.unwrap();
+
+    let response = server
+      .handle_message(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-03-26" } }))
+      .await
+      .unwrap();
+    assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+    assert!(server.handle_message(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await.is_none());
+    let response = server.handle_message(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).await.unwrap();
+    assert_eq!(response["result"]["tools"].as_array().unwrap().len(), 6);
+    let response = server.handle_message(json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/list" })).await.unwrap();
+    assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+    // selection by pattern and path shows up in the tree
+    assert_eq!(call(&mut server, "select", json!({ "pattern": "ext:rs !lib.rs" })).await, ("1 files changed, 1 files selected".to_string(), false));
+    assert_eq!(call(&mut server, "select", json!({ "paths": ["notes.md"] })).await.0, "1 files changed, 2 files selected");
+    let (tree, _) = call(&mut server, "list_tree", json!({})).await;
+    assert!(tree.contains("[~] src/ (1/2 files selected)"));
+    assert!(tree.contains("    [x] main.rs"));
+    assert!(tree.contains("    [ ] lib.rs"));
+    assert!(call(&mut server, "list_tree", json!({ "depth": 0 })).await.0.starts_with("[~] ./ (2/4 files selected)\n\n"));
+    assert_eq!(call(&mut server, "deselect", json!({ "paths": ["notes.md"] })).await.0, "1 files changed, 1 files selected");
+    assert!(call(&mut server, "count_tokens", json!({})).await.0.contains("tokens in 1 files"));
+
+    // reads stay inside the tree and follow the secrets policy
+    assert_eq!(
+      call(&mut server, "read_file", json!({ "path": "src/main.rs", "start_line": 2, "end_line": 2 })).await,
+      ("  println!(\"hi\");".to_string(), false)
+    );
+    assert!(call(&mut server, "read_file", json!({ "path": "../etc/passwd" })).await.1);
+    let (text, is_error) = call(&mut server, "read_file", json!({ "path": "keys.py" })).await;
+    assert!(is_error && text.contains("AWS access key"));
+    assert!(call(&mut server, "select", json!({ "paths": ["missing.rs"] })).await.1);
+
+    // a bad path or pattern leaves the selection as it was
+    assert!(call(&mut server, "deselect", json!({ "all": true, "paths": ["typo.rs"] })).await.1);
+    assert!(call(&mut server, "select", json!({ "paths": ["notes.md"], "pattern": "re:(" })).await.1);
+    assert_eq!(file_utils::get_selected_files(&server.file_tree), vec![root.canonicalize().unwrap().join("src/main.rs")]);
+  }
+}
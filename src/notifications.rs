@@ -0,0 +1,200 @@
+use crate::types::{Backend, StatusLevel};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Maximum number of notifications kept in the log.
+const MAX_LOG_ENTRIES: usize = 500;
+
+/// Part of siff a notification came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationSource {
+  /// General app feedback (selection, navigation, toggles)
+  App,
+  /// Background directory scan
+  Scan,
+  /// Token calculation
+  Tokens,
+  /// Config loading and saving
+  Config,
+  /// Repomix download and runs
+  Repomix,
+  /// Yek runs
+  Yek,
+  /// Runs of backends defined in the config
+  Custom,
+  /// Requests from editor plugins through the local API
+  Api,
+}
+
+impl NotificationSource {
+  /// Returns the display name for the source.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      NotificationSource::App => "app",
+      NotificationSource::Scan => "scan",
+      NotificationSource::Tokens => "tokens",
+      NotificationSource::Config => "config",
+      NotificationSource::Repomix => "repomix",
+      NotificationSource::Yek => "yek",
+      NotificationSource::Custom => "custom",
+      NotificationSource::Api => "api",
+    }
+  }
+}
+
+impl From<&Backend> for NotificationSource {
+  fn from(backend: &Backend) -> Self {
+    match backend {
+      Backend::Repomix => NotificationSource::Repomix,
+      Backend::Yek => NotificationSource::Yek,
+      Backend::Custom(_) => NotificationSource::Custom,
+    }
+  }
+}
+
+/// A single message shown as a toast and kept in the log.
+#[derive(Debug, Clone)]
+pub struct Notification {
+  /// Severity (decides style and how long the toast stays)
+  pub level: StatusLevel,
+  /// Where the message came from
+  pub source: NotificationSource,
+  /// Short message shown in the toast
+  pub message: String,
+  /// Full output (backend stderr, long errors), only shown in the log
+  pub details: Option<String>,
+  /// When the notification was created or last updated
+  pub timestamp: Instant,
+}
+
+impl Notification {
+  /// Returns how long the toast stays visible.
+  fn toast_duration(&self) -> Duration {
+    match self.level {
+      StatusLevel::Progress => Duration::from_secs(1),
+      StatusLevel::Success => Duration::from_secs(2),
+      StatusLevel::Info => Duration::from_secs(3),
+      StatusLevel::Warning => Duration::from_secs(5),
+      StatusLevel::Error => Duration::from_secs(8),
+    }
+  }
+
+  /// Returns how long ago the notification was created, formatted for the log.
+  pub fn age_text(&self) -> String {
+    let seconds = self.timestamp.elapsed().as_secs();
+    if seconds < 60 {
+      format!("{}s ago", seconds)
+    } else if seconds < 3600 {
+      format!("{}m ago", seconds / 60)
+    } else {
+      format!("{}h ago", seconds / 3600)
+    }
+  }
+}
+
+/// Notification history with the current toast.
+#[derive(Debug)]
+pub struct NotificationLog {
+  /// Notifications, oldest first
+  entries: VecDeque<Notification>,
+  /// Whether the latest notification was dismissed from the toast area
+  toast_dismissed: bool,
+  /// Maximum number of notifications kept
+  max_entries: usize,
+}
+
+impl Default for NotificationLog {
+  fn default() -> Self {
+    Self {
+      entries: VecDeque::new(),
+      toast_dismissed: false,
+      max_entries: MAX_LOG_ENTRIES,
+    }
+  }
+}
+
+impl NotificationLog {
+  /// Adds a notification and shows it as the toast.
+  /// Consecutive progress updates from the same source replace each other instead of flooding the log.
+  pub fn push(&mut self, level: StatusLevel, source: NotificationSource, message: String, details: Option<String>) {
+    let notification = Notification {
+      level,
+      source,
+      message,
+      details,
+      timestamp: Instant::now(),
+    };
+
+    match self.entries.back_mut() {
+      Some(last) if last.level == StatusLevel::Progress && level == StatusLevel::Progress && last.source == source => *last = notification,
+      _ => {
+        if self.entries.len() >= self.max_entries {
+          self.entries.pop_front();
+        }
+        self.entries.push_back(notification);
+      }
+    }
+
+    self.toast_dismissed = false;
+  }
+
+  /// Returns the notification to show as a toast, if it hasn't expired.
+  /// Nothing expires while a backend run is in progress.
+  pub fn toast(&self, keep_visible: bool) -> Option<&Notification> {
+    let latest = self.entries.back().filter(|_| !self.toast_dismissed)?;
+    (keep_visible || latest.timestamp.elapsed() < latest.toast_duration()).then_some(latest)
+  }
+
+  /// Hides the current toast (it stays in the log).
+  pub fn dismiss_toast(&mut self) {
+    self.toast_dismissed = true;
+  }
+
+  /// Returns the number of lines the log panel needs (one per notification plus its details).
+  pub fn line_count(&self) -> usize {
+    self.entries.iter().map(|entry| 1 + entry.details.as_ref().map_or(0, |details| details.lines().count())).sum()
+  }
+
+  /// Returns the notifications, oldest first.
+  pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+    self.entries.iter()
+  }
+}
+
+// test for progress coalescing and toast expiry
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_progress_updates_replace_each_other() {
+    let mut log = NotificationLog::default();
+    log.push(StatusLevel::Progress, NotificationSource::Tokens, "Calculating tokens... 1/3".to_string(), None);
+    log.push(StatusLevel::Progress, NotificationSource::Tokens, "Calculating tokens... 2/3".to_string(), None);
+    log.push(StatusLevel::Success, NotificationSource::Tokens, "✓ Calculated tokens for 3 files".to_string(), None);
+    log.push(StatusLevel::Progress, NotificationSource::Tokens, "Calculating tokens... 1/1".to_string(), None);
+
+    let messages: Vec<&str> = log.entries().map(|entry| entry.message.as_str()).collect();
+    assert_eq!(messages, vec!["Calculating tokens... 2/3", "✓ Calculated tokens for 3 files", "Calculating tokens... 1/1"]);
+  }
+
+  #[test]
+  fn test_toast_dismiss_and_expiry() {
+    let mut log = NotificationLog::default();
+    assert!(log.toast(false).is_none());
+
+    log.push(StatusLevel::Error, NotificationSource::Repomix, "Repomix failed".to_string(), Some("stderr output".to_string()));
+    assert_eq!(log.toast(false).map(|toast| toast.source), Some(NotificationSource::Repomix));
+
+    log.dismiss_toast();
+    assert!(log.toast(true).is_none());
+    assert_eq!(log.entries().count(), 1);
+
+    // expired toasts stay hidden unless kept visible
+    log.push(StatusLevel::Progress, NotificationSource::Repomix, "Running".to_string(), None);
+    log.entries.back_mut().unwrap().timestamp -= Duration::from_secs(10);
+    assert!(log.toast(false).is_none());
+    assert!(log.toast(true).is_some());
+  }
+}
@@ -0,0 +1,179 @@
+use crate::config::SifConfig;
+use crate::file_utils::{self, SessionRoots};
+use crate::process_runner::RunMonitor;
+use crate::repomix_integration::Repomix;
+use crate::types::{Backend, BackendRequest, BackendResult, FileNode};
+use crate::yek_integration::{Yek, YekProvisioner};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Runs a backend request end to end: secret scan and redaction, then the backend.
+/// Returns the result and, if the run succeeded, the packed content (nothing is copied anywhere).
+pub async fn run_request(request: &BackendRequest, repomix: Option<&Mutex<Repomix>>, monitor: &RunMonitor) -> (BackendResult, Option<String>) {
+  // scan the selection for secrets and apply redaction rules before anything is packed, changed copies replace the files
+  let prepared = match crate::redaction::prepare(&request.secrets, &request.redaction_rules, &request.selected_files, &request.roots, monitor).await {
+    Ok(prepared) => prepared,
+    Err(e) => return (BackendResult::failed(request.request_id, request.backend.clone(), format!("Error: preparing files failed {:#}", e)), None),
+  };
+  if prepared.blocked {
+    let mut result = BackendResult::failed(request.request_id, request.backend.clone(), format!("Blocked: secrets found in {} selected files", prepared.flagged.len()));
+    result.flagged = prepared.flagged;
+    return (result, None);
+  }
+
+  // execute the backend op
+  let (mut result, content) = match &request.backend {
+    Backend::Repomix => match repomix {
+      Some(repomix) => {
+        let mut manager = repomix.lock().await;
+        // the output file goes next to the real files, not into a staging dir
+        let mut options = request.repomix_options.clone();
+        options.output_file = options.output_file.map(|output_file| request.roots.base_dir().join(output_file).to_string_lossy().to_string());
+        match manager.run_isolated_repomix(&prepared.selected_files, &options, &prepared.root_path, request.file_tree_text.as_deref(), monitor).await {
+          Ok(mut output) => {
+            let content = std::mem::take(&mut output.content);
+            (BackendResult::succeeded(request.request_id, request.backend.clone(), output, options.output_file.map(PathBuf::from)), Some(content))
+          }
+          Err(e) => (BackendResult::failed(request.request_id, request.backend.clone(), format!("Error: repomix error {}", e)), None),
+        }
+      }
+      None => (BackendResult::failed(request.request_id, request.backend.clone(), "Repomix not initialized".to_string()), None),
+    },
+    Backend::Yek => match request.yek_binary.clone().map(Yek::new) {
+      Some(yek) => match yek.run_yek_integrated(&prepared.selected_files, &request.yek_options, &prepared.root_path, monitor).await {
+        // yek doesn't create output files
+        Ok(mut output) => {
+          let content = std::mem::take(&mut output.content);
+          (BackendResult::succeeded(request.request_id, request.backend.clone(), output, None), Some(content))
+        }
+        Err(e) => (BackendResult::failed(request.request_id, request.backend.clone(), format!("Error: yek error {}", e)), None),
+      },
+      None => (BackendResult::failed(request.request_id, request.backend.clone(), "Yek not initialized".to_string()), None),
+    },
+    Backend::Custom(name) => match &request.custom_backend {
+      Some(custom_backend) => match custom_backend.run(&prepared.selected_files, &prepared.root_path, monitor).await {
+        Ok(mut output) => {
+          let content = std::mem::take(&mut output.content);
+          (BackendResult::succeeded(request.request_id, request.backend.clone(), output, None), Some(content))
+        }
+        Err(e) => (BackendResult::failed(request.request_id, request.backend.clone(), format!("Error: {} error {:#}", name, e)), None),
+      },
+      None => (BackendResult::failed(request.request_id, request.backend.clone(), format!("{} is not defined in the config", name)), None),
+    },
+  };
+
+  result.flagged = prepared.flagged;
+  result.redacted = prepared.redacted;
+  (result, content)
+}
+
+/// Packed selection from a run without the UI.
+pub struct HeadlessPack {
+  /// Result message, warnings, flagged and redacted files, one per line
+  pub summary: Vec<String>,
+  /// Packed content
+  pub content: String,
+}
+
+/// Packs selections without the UI (MCP server, `siff pack`), installing the backend on first use.
+pub struct HeadlessPacker {
+  /// Backend every pack runs
+  backend: Backend,
+  /// Backend options, secrets policy and redaction rules
+  config: SifConfig,
+  /// Repomix install, set up on the first pack
+  repomix: Option<Mutex<Repomix>>,
+  /// Finds or installs yek on the first pack
+  yek: YekProvisioner,
+  /// Id for the next request
+  next_request_id: u64,
+}
+
+impl HeadlessPacker {
+  /// Creates a packer, nothing is installed until the first pack.
+  pub fn new(backend: Backend, config: SifConfig) -> Self {
+    Self {
+      yek: YekProvisioner::new(&config.yek),
+      backend,
+      config,
+      repomix: None,
+      next_request_id: 1,
+    }
+  }
+
+  /// Packs the files with the configured options, waiting for the backend to be installed if needed.
+  /// Fails with the backend's error, and the flagged files if secrets blocked the run.
+  pub async fn pack(&mut self, file_tree: &HashMap<PathBuf, FileNode>, roots: &SessionRoots, selected_files: Vec<PathBuf>) -> Result<HeadlessPack> {
+    let selected_files = file_utils::unique_files(file_tree, selected_files);
+    if selected_files.is_empty() {
+      anyhow::bail!("No files selected for processing");
+    }
+    let relative = |path: &Path| roots.relative(path).display().to_string();
+
+    let mut warnings = Vec::new();
+    let mut yek_binary = None;
+    let mut custom_backend = None;
+    match &self.backend {
+      Backend::Repomix => {
+        warnings = crate::repomix_integration::validate_isolated_repomix_options(&self.config.repomix_options(Backend::Repomix), &selected_files);
+        if self.repomix.is_none() {
+          let mut repomix = Repomix::new(&self.config.repomix)?;
+          repomix.wait_until_ready().await?;
+          self.repomix = Some(Mutex::new(repomix));
+        }
+      }
+      Backend::Yek => {
+        warnings = crate::yek_integration::validate_yek_options(&self.config.yek_options, &selected_files).context("Invalid yek options")?;
+        yek_binary = Some(self.yek.wait_until_ready().await?);
+      }
+      Backend::Custom(name) => {
+        let config = crate::custom_backend::find(&self.config.custom_backends, name).with_context(|| format!("Custom backend {} is not defined in the config", name))?;
+        config.validate()?;
+        custom_backend = Some(config.clone());
+      }
+    }
+
+    let repomix_options = self.config.repomix_options(self.backend.clone());
+    let request = BackendRequest {
+      backend: self.backend.clone(),
+      file_tree_text: repomix_options.file_tree.then(|| file_utils::generate_file_tree_text(file_tree, &roots.root_path())),
+      repomix_options,
+      yek_options: self.config.yek_options.clone(),
+      selected_files,
+      roots: roots.clone(),
+      yek_binary,
+      custom_backend,
+      secrets: self.config.secrets.clone(),
+      redaction_rules: self.config.redaction_rules.clone(),
+      request_id: self.next_request_id,
+      cancellation_token: CancellationToken::new(),
+    };
+    self.next_request_id += 1;
+
+    // nobody watches progress here
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let monitor = RunMonitor::new(&request, sender);
+    let (result, content) = run_request(&request, self.repomix.as_ref(), &monitor).await;
+    let content = match content {
+      Some(content) if result.success => content,
+      _ => {
+        let flagged = result.flagged.iter().map(|file| format!("{}: {}", relative(&file.path), file.summary)).collect::<Vec<_>>();
+        anyhow::bail!("{}{}", result.error.unwrap_or_default(), if flagged.is_empty() { String::new() } else { format!("\n{}", flagged.join("\n")) });
+      }
+    };
+
+    let mut summary = vec![result.message];
+    warnings.extend(result.warnings);
+    summary.extend(warnings.iter().map(|warning| format!("Warning: {}", warning)));
+    for file in &result.flagged {
+      summary.push(format!("Secrets in {} ({}, policy {})", relative(&file.path), file.summary, self.config.secrets.policy.display_name()));
+    }
+    for file in &result.redacted {
+      summary.push(format!("Redacted in {}: {}", relative(&file.path), file.summary()));
+    }
+    Ok(HeadlessPack { summary, content })
+  }
+}
@@ -0,0 +1,299 @@
+use crate::types::{Backend, BackendEvent, BackendProgress, BackendRequest};
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// How often progress is sent to the UI while a process runs.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Error message for runs stopped by the user.
+pub const CANCELLED_MESSAGE: &str = "Operation cancelled";
+
+/// Reports progress of a backend run and carries its cancellation token.
+#[derive(Debug, Clone)]
+pub struct RunMonitor {
+  /// Request the run belongs to
+  request_id: u64,
+  /// Backend that's running
+  backend: Backend,
+  /// Number of files sent to the backend
+  files_total: usize,
+  /// When the run started
+  started: Instant,
+  /// Token cancelled when the user stops or restarts the run
+  cancellation_token: CancellationToken,
+  /// Channel to the UI
+  sender: mpsc::UnboundedSender<BackendEvent>,
+}
+
+impl RunMonitor {
+  /// Creates a monitor for a backend request.
+  pub fn new(request: &BackendRequest, sender: mpsc::UnboundedSender<BackendEvent>) -> Self {
+    Self {
+      request_id: request.request_id,
+      backend: request.backend.clone(),
+      files_total: request.selected_files.len(),
+      started: Instant::now(),
+      cancellation_token: request.cancellation_token.clone(),
+      sender,
+    }
+  }
+
+  /// Sends a progress update to the UI.
+  pub fn report(&self, stage: &str, files_processed: Option<usize>, bytes: u64) {
+    let _ = self.sender.send(BackendEvent::Progress(BackendProgress {
+      request_id: self.request_id,
+      backend: self.backend.clone(),
+      stage: stage.to_string(),
+      files_processed,
+      files_total: self.files_total,
+      bytes,
+      elapsed: self.started.elapsed(),
+    }));
+  }
+
+  /// Returns an error if the run was cancelled (checked between steps).
+  pub fn check_cancelled(&self) -> Result<()> {
+    if self.cancellation_token.is_cancelled() {
+      Err(anyhow!(CANCELLED_MESSAGE))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Finds an executable on PATH, like `which`.
+/// The name is used as given (`pack.sh`, `python3.11`), Windows also tries it with the executable extensions.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+  find_executable_in(name, &std::env::var_os("PATH")?)
+}
+
+/// Finds an executable in the dirs of a PATH-like list.
+fn find_executable_in(name: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+  let extensions: &[&str] = if cfg!(windows) { &["exe", "cmd", "bat"] } else { &[] };
+  std::env::split_paths(path)
+    .flat_map(|dir| std::iter::once(dir.join(name)).chain(extensions.iter().map(move |extension| dir.join(format!("{}.{}", name, extension)))))
+    .find(|candidate| candidate.is_file())
+}
+
+/// Copies content to the clipboard using the platform's clipboard command.
+pub async fn copy_to_clipboard(content: &str) -> Result<()> {
+  let clipboard_cmd: &[&str] = if cfg!(target_os = "macos") {
+    &["pbcopy"]
+  } else if cfg!(target_os = "linux") {
+    // try xclip first, then xsel as fallback (for linux)
+    if find_executable("xclip").is_some() {
+      &["xclip", "-selection", "clipboard"]
+    } else if find_executable("xsel").is_some() {
+      &["xsel", "--clipboard", "--input"]
+    } else {
+      return Err(anyhow!("No clipboard utility found. Please install xclip or xsel"));
+    }
+  } else if cfg!(target_os = "windows") {
+    &["clip"]
+  } else {
+    return Err(anyhow!("Unsupported platform for clipboard operations"));
+  };
+
+  let mut child = Command::new(clipboard_cmd[0])
+    .args(&clipboard_cmd[1..])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .context("Failed to spawn clipboard command")?;
+
+  // write content to stdin
+  if let Some(mut stdin) = child.stdin.take() {
+    stdin.write_all(content.as_bytes()).await.context("Failed to write to clipboard command stdin")?;
+    stdin.shutdown().await.context("Failed to close clipboard command stdin")?;
+  }
+
+  // wait for command to complete
+  let output = child.wait_with_output().await.context("Failed to wait for clipboard command")?;
+  if !output.status.success() {
+    return Err(anyhow!("Clipboard command failed: {}", String::from_utf8_lossy(&output.stderr)));
+  }
+
+  Ok(())
+}
+
+/// Kills the whole process group when dropped, so cancelled runs and app shutdown
+/// don't leave node/yek (or anything they spawned) running.
+struct ProcessGroupGuard {
+  /// Process id of the group leader, None once the process has exited
+  pid: Option<u32>,
+}
+
+impl Drop for ProcessGroupGuard {
+  fn drop(&mut self) {
+    if let Some(pid) = self.pid {
+      kill_process_group(pid);
+    }
+  }
+}
+
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+  // the child was started as the leader of its own group, so the group id is its pid
+  unsafe {
+    libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+  }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: u32) {
+  // no process groups, kill_on_drop stops the child itself
+}
+
+/// Runs a backend process in its own process group, streaming progress until it exits.
+/// Counts stdout lines starting with the file marker as processed files, and reports the size of
+/// the output file (or of stdout) as bytes. Kills the process group if the run is cancelled.
+/// The process reads the input file as stdin, or nothing if there's none.
+pub async fn run_monitored(mut command: Command, monitor: &RunMonitor, stage: &str, file_marker: Option<&str>, output_file: Option<&Path>, input_file: Option<&Path>) -> Result<Output> {
+  monitor.check_cancelled()?;
+
+  let stdin = match input_file {
+    Some(path) => Stdio::from(std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?),
+    None => Stdio::null(),
+  };
+  command.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+  #[cfg(unix)]
+  command.process_group(0);
+
+  let mut child = command.spawn().context("Failed to start backend process")?;
+  let mut guard = ProcessGroupGuard { pid: child.id() };
+
+  // read stderr separately so a full pipe can't block the process
+  let mut stderr = child.stderr.take().context("Failed to capture backend stderr")?;
+  let stderr_task = tokio::spawn(async move {
+    let mut buffer = Vec::new();
+    let _ = stderr.read_to_end(&mut buffer).await;
+    buffer
+  });
+
+  let mut stdout = BufReader::new(child.stdout.take().context("Failed to capture backend stdout")?);
+  let mut stdout_buffer = Vec::new();
+  let mut line = Vec::new();
+  let mut files_processed = file_marker.map(|_| 0);
+  let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+
+  loop {
+    tokio::select! {
+      _ = monitor.cancellation_token.cancelled() => {
+        // dropping the guard kills the process group
+        return Err(anyhow!(CANCELLED_MESSAGE));
+      }
+      read = stdout.read_until(b'\n', &mut line) => {
+        if read.context("Failed to read backend output")? == 0 {
+          break;
+        }
+        if let (Some(marker), Some(count)) = (file_marker, files_processed.as_mut()) {
+          if line.starts_with(marker.as_bytes()) {
+            *count += 1;
+          }
+        }
+        stdout_buffer.append(&mut line);
+      }
+      _ = ticker.tick() => {
+        let bytes = match output_file {
+          Some(path) => std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0),
+          None => stdout_buffer.len() as u64,
+        };
+        monitor.report(stage, files_processed, bytes);
+      }
+    }
+  }
+
+  // stdout closed, wait for the process to exit
+  let status = tokio::select! {
+    _ = monitor.cancellation_token.cancelled() => return Err(anyhow!(CANCELLED_MESSAGE)),
+    status = child.wait() => status.context("Failed to wait for backend process")?,
+  };
+  guard.pid = None;
+
+  let stderr = stderr_task.await.unwrap_or_default();
+  Ok(Output { status, stdout: stdout_buffer, stderr })
+}
+
+// test for process cancellation
+// TODO: move tests to main testing file
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+
+  fn monitor(cancellation_token: CancellationToken) -> (RunMonitor, mpsc::UnboundedReceiver<BackendEvent>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let request = BackendRequest {
+      backend: Backend::Yek,
+      repomix_options: Default::default(),
+      yek_options: Default::default(),
+      selected_files: vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")],
+      roots: crate::file_utils::SessionRoots::single(Path::new(".")),
+      file_tree_text: None,
+      yek_binary: None,
+      custom_backend: None,
+      secrets: Default::default(),
+      redaction_rules: Vec::new(),
+      request_id: 1,
+      cancellation_token,
+    };
+    (RunMonitor::new(&request, sender), receiver)
+  }
+
+  #[test]
+  fn test_find_executable_keeps_dots() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("pack.sh"), "").unwrap();
+    std::fs::write(temp_dir.path().join("python3"), "").unwrap();
+    let path = temp_dir.path().as_os_str();
+
+    assert_eq!(find_executable_in("pack.sh", path), Some(temp_dir.path().join("pack.sh")));
+    // a dotted name must not match a different program
+    assert_eq!(find_executable_in("python3.11", path), None);
+    assert_eq!(find_executable_in("pack", path), None);
+  }
+
+  #[tokio::test]
+  async fn test_run_monitored_counts_files() {
+    let (monitor, _receiver) = monitor(CancellationToken::new());
+    let mut command = Command::new("sh");
+    command.args(["-c", "printf '>>>> a.rs\\nfn a() {}\\n>>>> b.rs\\nfn b() {}\\n'; echo done >&2"]);
+
+    let output = run_monitored(command, &monitor, "Packing", Some(">>>> "), None, None).await.unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).matches(">>>> ").count(), 2);
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "done");
+  }
+
+  #[tokio::test]
+  async fn test_cancel_kills_process_group() {
+    let token = CancellationToken::new();
+    let (monitor, _receiver) = monitor(token.clone());
+    let temp_dir = tempfile::tempdir().unwrap();
+    let marker_path = temp_dir.path().join("survived");
+
+    // the grandchild would create the marker file if it survived the cancellation
+    let mut command = Command::new("sh");
+    command.args(["-c", &format!("(sleep 1; touch {}) & sleep 5", marker_path.display())]);
+
+    let cancel = token.clone();
+    tokio::spawn(async move {
+      tokio::time::sleep(Duration::from_millis(200)).await;
+      cancel.cancel();
+    });
+
+    let started = Instant::now();
+    let error = run_monitored(command, &monitor, "Packing", None, None, None).await.unwrap_err();
+    assert_eq!(error.to_string(), CANCELLED_MESSAGE);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!marker_path.exists());
+  }
+}
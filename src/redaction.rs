@@ -0,0 +1,384 @@
+use crate::encoding::{self, Encoding};
+use crate::file_utils::SessionRoots;
+use crate::process_runner::RunMonitor;
+use crate::secrets::{self, FlaggedFile, SecretPolicy, SecretsConfig};
+use anyhow::{Context, Result};
+use globset::GlobSet;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Rule masking text in packed files, e.g. internal hostnames or customer names.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RedactionRule {
+  /// Name of the rule, used in the report and the default replacement
+  pub name: String,
+  /// Text to replace, or a regex when `regex` is set
+  pub pattern: String,
+  /// Whether the pattern is a regex
+  #[serde(default)]
+  pub regex: bool,
+  /// Whether matching ignores case
+  #[serde(default)]
+  pub ignore_case: bool,
+  /// Replacement text, `[NAME]` if not set (regex rules can use `$1` for groups)
+  #[serde(default)]
+  pub replacement: Option<String>,
+}
+
+impl RedactionRule {
+  /// Returns the text matches are replaced with.
+  fn replacement(&self) -> String {
+    self.replacement.clone().unwrap_or_else(|| format!("[{}]", self.name.to_uppercase()))
+  }
+}
+
+/// Redaction rules compiled once per run.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+  /// Rule name, matcher and replacement, in the order they're applied
+  rules: Vec<(String, Regex, String)>,
+}
+
+impl Redactor {
+  /// Compiles the rules, failing on an invalid regex or a rule without a name.
+  pub fn new(rules: &[RedactionRule]) -> Result<Self> {
+    let mut compiled = Vec::with_capacity(rules.len());
+    for rule in rules {
+      if rule.name.trim().is_empty() || rule.pattern.is_empty() {
+        anyhow::bail!("Redaction rules need a name and a pattern");
+      }
+      let pattern = if rule.regex { rule.pattern.clone() } else { regex::escape(&rule.pattern) };
+      let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(rule.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid pattern in redaction rule '{}'", rule.name))?;
+      // literal replacements must not expand `$`
+      let replacement = if rule.regex { rule.replacement() } else { rule.replacement().replace('$', "$$") };
+      compiled.push((rule.name.clone(), regex, replacement));
+    }
+    Ok(Self { rules: compiled })
+  }
+
+  /// Returns true if there are no rules.
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Applies every rule in order, returning the new content and the substitutions per rule.
+  pub fn apply(&self, content: &str) -> (String, BTreeMap<String, usize>) {
+    let mut content = content.to_string();
+    let mut counts = BTreeMap::new();
+    for (name, regex, replacement) in &self.rules {
+      let matches = regex.find_iter(&content).count();
+      if matches > 0 {
+        content = regex.replace_all(&content, replacement.as_str()).to_string();
+        *counts.entry(name.clone()).or_insert(0) += matches;
+      }
+    }
+    (content, counts)
+  }
+}
+
+/// A packed file that redaction rules changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactedFile {
+  /// Absolute path of the original file
+  pub path: PathBuf,
+  /// Substitutions per rule name
+  pub counts: BTreeMap<String, usize>,
+}
+
+impl RedactedFile {
+  /// Returns the number of substitutions in the file.
+  pub fn total(&self) -> usize {
+    self.counts.values().sum()
+  }
+
+  /// Summarizes the substitutions for the message log, e.g. "email 2, hostname 1".
+  pub fn summary(&self) -> String {
+    self.counts.iter().map(|(name, count)| format!("{} {}", name, count)).collect::<Vec<_>>().join(", ")
+  }
+}
+
+/// Files to hand to the backend after the secret scan and redaction rules.
+/// When any file was changed, or there are several roots, these are copies in a staging dir, removed when this is dropped.
+pub struct PreparedFiles {
+  /// Root the backend runs in
+  pub root_path: PathBuf,
+  /// Files the backend packs
+  pub selected_files: Vec<PathBuf>,
+  /// Selected files that contain secrets
+  pub flagged: Vec<FlaggedFile>,
+  /// Files changed by redaction rules
+  pub redacted: Vec<RedactedFile>,
+  /// Whether the run must not go ahead
+  pub blocked: bool,
+  /// Staging dir with the changed copies
+  _staging: Option<tempfile::TempDir>,
+}
+
+/// Content of one file after the secret scan and redaction rules.
+pub struct PreparedContent {
+  /// Content to hand out, with secrets (Redact policy) and rule matches replaced
+  pub content: String,
+  /// Set if the file contains secrets
+  pub flagged: Option<FlaggedFile>,
+  /// Set if redaction rules changed the file
+  pub redacted: Option<RedactedFile>,
+  /// Whether the content differs from the file
+  pub modified: bool,
+}
+
+/// Scans one file's bytes for secrets and applies the redaction rules.
+/// Files matching the allow list or larger than the scan limit aren't scanned.
+/// UTF-16 and Latin-1 text is converted to UTF-8 so backends read it correctly.
+pub fn prepare_content(path: &Path, relative_path: &Path, bytes: &[u8], policy: SecretPolicy, allowed: &GlobSet, redactor: &Redactor) -> PreparedContent {
+  let decoded = encoding::decode(bytes);
+  let is_text = decoded.is_some();
+  let mut prepared = match decoded {
+    Some((content, encoding)) => PreparedContent {
+      content,
+      flagged: None,
+      redacted: None,
+      modified: encoding != Encoding::Utf8,
+    },
+    None => PreparedContent {
+      content: String::from_utf8_lossy(bytes).to_string(),
+      flagged: None,
+      redacted: None,
+      modified: false,
+    },
+  };
+
+  let scan = policy != SecretPolicy::Off && !allowed.is_match(relative_path) && bytes.len() as u64 <= secrets::MAX_SCAN_SIZE;
+  let findings = if scan { secrets::scan_content(relative_path, &prepared.content) } else { Vec::new() };
+  if !findings.is_empty() {
+    prepared.flagged = Some(FlaggedFile {
+      path: path.to_path_buf(),
+      summary: secrets::summarize(&findings),
+    });
+    if policy == SecretPolicy::Redact {
+      prepared.content = secrets::redact(&prepared.content, &findings);
+      prepared.modified = true;
+    }
+  }
+
+  // rules only apply to text, rewriting binary files would corrupt them
+  if !redactor.is_empty() && is_text {
+    let (replaced, counts) = redactor.apply(&prepared.content);
+    if !counts.is_empty() {
+      prepared.content = replaced;
+      prepared.modified = true;
+      prepared.redacted = Some(RedactedFile { path: path.to_path_buf(), counts });
+    }
+  }
+  prepared
+}
+
+/// Scans the selected files for secrets, applies redaction rules and converts non UTF-8 text before they're packed.
+/// Returns the files to pack, which are copies in a staging dir if the secret policy, a rule or the conversion changed any of them.
+/// Several roots are always staged, each in a dir named like the root so the backend sees the session's paths.
+pub async fn prepare(config: &SecretsConfig, rules: &[RedactionRule], selected_files: &[PathBuf], roots: &SessionRoots, monitor: &RunMonitor) -> Result<PreparedFiles> {
+  let mut prepared = PreparedFiles {
+    root_path: roots.base_dir().to_path_buf(),
+    selected_files: selected_files.to_vec(),
+    flagged: Vec::new(),
+    redacted: Vec::new(),
+    blocked: false,
+    _staging: None,
+  };
+  let redactor = Redactor::new(rules)?;
+
+  // without secrets or rules to check, only files that aren't UTF-8 are read in full
+  let check_content = config.policy != SecretPolicy::Off || !redactor.is_empty();
+
+  monitor.check_cancelled()?;
+  let stage = match (config.policy, redactor.is_empty()) {
+    (SecretPolicy::Off, true) => "Checking encodings",
+    (SecretPolicy::Off, false) => "Applying redaction rules",
+    _ => "Scanning for secrets",
+  };
+  monitor.report(stage, None, 0);
+
+  // reading every file is blocking work, keep it off the runtime threads
+  let allowed = config.allowed()?;
+  let policy = config.policy;
+  let files = selected_files.to_vec();
+  let session = roots.clone();
+  let (flagged, redacted, changed) = tokio::task::spawn_blocking(move || {
+    let mut flagged = Vec::new();
+    let mut redacted = Vec::new();
+    let mut changed = BTreeMap::new();
+    for path in files {
+      if !check_content && encoding::read_head(&path).ok().and_then(|head| encoding::detect(&head)).is_none_or(|found| found == Encoding::Utf8) {
+        continue;
+      }
+      let Ok(bytes) = std::fs::read(&path) else {
+        continue;
+      };
+      let file = prepare_content(&path, &session.relative(&path), &bytes, policy, &allowed, &redactor);
+      flagged.extend(file.flagged);
+      redacted.extend(file.redacted);
+      if file.modified {
+        changed.insert(path, file.content);
+      }
+    }
+    (flagged, redacted, changed)
+  })
+  .await
+  .context("Preparing files failed")?;
+  monitor.check_cancelled()?;
+
+  prepared.flagged = flagged;
+  prepared.redacted = redacted;
+  prepared.blocked = config.policy == SecretPolicy::Block && !prepared.flagged.is_empty();
+  if prepared.blocked || (changed.is_empty() && roots.len() == 1) {
+    return Ok(prepared);
+  }
+
+  // the backend packs copies of the selection with the changes applied
+  let staging = tempfile::Builder::new().prefix("siff-staging-").tempdir().context("Failed to create staging directory")?;
+  let mut staged_files = Vec::with_capacity(selected_files.len());
+  for path in selected_files {
+    let relative_path = roots.relative(path);
+    if relative_path.is_absolute() {
+      // the backends skip files outside the root, keep them as they are
+      staged_files.push(path.clone());
+      continue;
+    }
+    let staged_path = staging.path().join(&relative_path);
+    if let Some(parent) = staged_path.parent() {
+      std::fs::create_dir_all(parent).context("Failed to create staging directory")?;
+    }
+    match changed.get(path) {
+      Some(content) => std::fs::write(&staged_path, content),
+      None => std::fs::copy(path, &staged_path).map(|_| ()),
+    }
+    .with_context(|| format!("Failed to stage {}", relative_path.display()))?;
+    staged_files.push(staged_path);
+  }
+  prepared.root_path = staging.path().to_path_buf();
+  prepared.selected_files = staged_files;
+  prepared._staging = Some(staging);
+
+  Ok(prepared)
+}
+
+// test for redaction rules
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(name: &str, pattern: &str, regex: bool) -> RedactionRule {
+    RedactionRule {
+      name: name.to_string(),
+      pattern: pattern.to_string(),
+      regex,
+      ignore_case: false,
+      replacement: None,
+    }
+  }
+
+  #[test]
+  fn test_redactor() {
+    let mut customer = rule("customer", "Acme Corp", false);
+    customer.ignore_case = true;
+    let mut host = rule("host", r"([a-z0-9-]+)\.corp\.internal", true);
+    host.replacement = Some("$1.example.com".to_string());
+    let redactor = Redactor::new(&[rule("email", r"[\w.+-]+@[\w-]+\.[\w.]+", true), customer, host, rule("price", "$5", false)]).unwrap();
+
+    let (content, counts) = redactor.apply("// ask bob@acme.io or ana@acme.io about ACME corp\nlet url = \"https://db-1.corp.internal\"; // $5\n");
+    assert_eq!(content, "// ask [EMAIL] or [EMAIL] about [CUSTOMER]\nlet url = \"https://db-1.example.com\"; // [PRICE]\n");
+    assert_eq!(counts, BTreeMap::from([("customer".to_string(), 1), ("email".to_string(), 2), ("host".to_string(), 1), ("price".to_string(), 1)]));
+
+    let file = RedactedFile { path: PathBuf::from("a.rs"), counts };
+    assert_eq!(file.total(), 5);
+    assert_eq!(file.summary(), "customer 1, email 2, host 1, price 1");
+
+    assert!(Redactor::new(&[rule("broken", "(", true)]).is_err());
+    assert!(Redactor::new(&[rule("", "x", false)]).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_prepare_stages_changed_files() {
+    use crate::types::{Backend, BackendRequest};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::create_dir(root.join("src")).unwrap();
+    std::fs::write(root.join("src/db.rs"), "const HOST: &str = \"db.corp.internal\";\n").unwrap();
+    std::fs::write(root.join("src/keys.rs"), format!("// AKIA{}\n", "Q3EGUZ7X2RJ5WNLP")).unwrap();
+    std::fs::write(root.join("README.md"), "# readme\n").unwrap();
+    let files = vec![root.join("src/db.rs"), root.join("src/keys.rs"), root.join("README.md")];
+
+    let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+    let request = BackendRequest {
+      backend: Backend::Yek,
+      repomix_options: Default::default(),
+      yek_options: Default::default(),
+      selected_files: files.clone(),
+      roots: SessionRoots::single(root),
+      file_tree_text: None,
+      yek_binary: None,
+      custom_backend: None,
+      secrets: Default::default(),
+      redaction_rules: Vec::new(),
+      request_id: 1,
+      cancellation_token: Default::default(),
+    };
+    let monitor = RunMonitor::new(&request, sender);
+    let rules = [rule("host", "db.corp.internal", false)];
+    let roots = SessionRoots::single(root);
+
+    // blocked by the block policy, nothing is staged
+    let block = SecretsConfig {
+      policy: SecretPolicy::Block,
+      allow: Vec::new(),
+    };
+    let prepared = prepare(&block, &rules, &files, &roots, &monitor).await.unwrap();
+    assert!(prepared.blocked);
+    assert_eq!(prepared.flagged[0].path, root.join("src/keys.rs"));
+
+    // redacted copies are packed from a staging dir, the originals stay as they were
+    let config = SecretsConfig {
+      policy: SecretPolicy::Redact,
+      allow: Vec::new(),
+    };
+    let prepared = prepare(&config, &rules, &files, &roots, &monitor).await.unwrap();
+    assert!(!prepared.blocked);
+    assert_ne!(prepared.root_path, root);
+    assert_eq!(prepared.redacted.len(), 1);
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join("src/db.rs")).unwrap(), "const HOST: &str = \"[HOST]\";\n");
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join("src/keys.rs")).unwrap(), "// [REDACTED AWS access key]\n");
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join("README.md")).unwrap(), "# readme\n");
+    assert!(std::fs::read_to_string(root.join("src/db.rs")).unwrap().contains("db.corp.internal"));
+
+    // the staging dir is removed with the prepared files
+    let staging_root = prepared.root_path.clone();
+    drop(prepared);
+    assert!(!staging_root.exists());
+
+    // Latin-1 sources are packed as UTF-8 even without secrets or rules
+    std::fs::write(root.join("src/legacy.c"), b"/* caf\xe9 */\n").unwrap();
+    let off = SecretsConfig {
+      policy: SecretPolicy::Off,
+      allow: Vec::new(),
+    };
+    let prepared = prepare(&off, &[], &[root.join("src/legacy.c"), root.join("README.md")], &roots, &monitor).await.unwrap();
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join("src/legacy.c")).unwrap(), "/* café */\n");
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join("README.md")).unwrap(), "# readme\n");
+
+    // several roots are always staged, each under its name
+    let other_dir = tempfile::tempdir().unwrap();
+    std::fs::write(other_dir.path().join("api.proto"), "syntax = \"proto3\";\n").unwrap();
+    let roots = SessionRoots::new(&[root.to_path_buf(), other_dir.path().to_path_buf()]).unwrap();
+    let (first, second) = (roots.relative(&root.canonicalize().unwrap()), roots.relative(&other_dir.path().canonicalize().unwrap()));
+    let files = [root.canonicalize().unwrap().join("README.md"), other_dir.path().canonicalize().unwrap().join("api.proto")];
+    let prepared = prepare(&off, &[], &files, &roots, &monitor).await.unwrap();
+    assert_eq!(prepared.selected_files, vec![prepared.root_path.join(&first).join("README.md"), prepared.root_path.join(&second).join("api.proto")]);
+    assert_eq!(std::fs::read_to_string(prepared.root_path.join(second).join("api.proto")).unwrap(), "syntax = \"proto3\";\n");
+  }
+}
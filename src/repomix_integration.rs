@@ -1,20 +1,73 @@
-use crate::types::RepomixOptions;
+use crate::file_utils;
+use crate::process_runner::{self, RunMonitor};
+use crate::types::{BackendOutput, DownloadStatus, RepomixOptions};
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
-/// Download status for repomix.
-#[derive(Debug, Clone)]
-pub enum DownloadStatus {
-  /// Repomix is ready to use
-  Ready,
-  /// Currently downloading repomix
-  Downloading(String),
-  /// Download failed
-  Failed(String),
-  /// Not started yet
-  NotStarted,
+/// Repomix version installed when the config doesn't pin one.
+pub const DEFAULT_REPOMIX_VERSION: &str = "0.3.7";
+
+/// File in each cache dir recording a completed install, used to verify it before running.
+const INSTALL_MANIFEST: &str = "siff-install.json";
+
+/// Where siff gets repomix from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RepomixSource {
+  /// Install the configured version from the npm registry
+  #[default]
+  Npm,
+  /// Install from a local package tarball (offline when its dependencies are bundled or in the npm cache)
+  Tarball(PathBuf),
+  /// Use a repomix already installed on PATH (npm -g, Homebrew), nothing is cached
+  System,
+}
+
+/// Repomix install settings from the config file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RepomixConfig {
+  /// Version installed from npm (pinned for compatibility)
+  pub version: String,
+  /// Where to get repomix from
+  pub source: RepomixSource,
+}
+
+impl Default for RepomixConfig {
+  fn default() -> Self {
+    Self {
+      version: DEFAULT_REPOMIX_VERSION.to_string(),
+      source: RepomixSource::default(),
+    }
+  }
+}
+
+/// Record of a verified install, written after npm install succeeds.
+#[derive(Debug, Serialize, Deserialize)]
+struct InstallManifest {
+  /// What was requested (npm version or tarball hash), a config change means a new install
+  requested: String,
+  /// Version from the installed package.json
+  installed_version: String,
+  /// Entry point relative to the cache dir
+  entry: PathBuf,
+  /// Hash of every file in the installed repomix package
+  sha256: String,
+}
+
+/// A repomix install found in the cache.
+#[derive(Debug)]
+pub struct CachedInstall {
+  /// Cache dir of the install
+  pub path: PathBuf,
+  /// Total size on disk in bytes
+  pub size: u64,
+  /// Installed version if the install verifies, otherwise what's wrong with it
+  pub status: Result<String, String>,
 }
 
 /// Repomix manager that downloads, caches, and runs repomix in complete isolation.
@@ -22,38 +75,78 @@ pub enum DownloadStatus {
 pub struct Repomix {
   /// Path to cached repomix installation
   cache_dir: PathBuf,
-  /// Specific repomix version to use (pinned for compatibility)
+  /// Requested version, or the tarball hash for tarball installs
   version: String,
+  /// Where repomix is installed from
+  source: RepomixSource,
   /// Path to the cached repomix entry point
   repomix_entry: PathBuf,
   /// Current download status
   download_status: DownloadStatus,
+  /// Why an existing install was rejected (shown while it's reinstalled)
+  verification_error: Option<String>,
 }
 
 impl Repomix {
-  /// Creates a new repomix manager instance.
-  /// Sets up cache directory structure for isolated repomix installation.
-  pub fn new() -> Result<Self> {
-    // pin to the v0.3.7 for repomix
-    let version = "0.3.7".to_string();
+  /// Creates a new repomix manager instance for the configured source.
+  /// Cached installs are only used if they match the config and pass verification.
+  pub fn new(config: &RepomixConfig) -> Result<Self> {
+    // use an existing repomix install, no cache needed
+    if config.source == RepomixSource::System {
+      let binary = process_runner::find_executable("repomix").context("Repomix source is \"system\" but no repomix was found on PATH")?;
+      // npm and Homebrew link the bin to the package's node script
+      let repomix_entry = binary.canonicalize().unwrap_or(binary);
+      return Ok(Self {
+        cache_dir: repomix_entry.parent().map(Path::to_path_buf).unwrap_or_default(),
+        version: "system".to_string(),
+        source: config.source.clone(),
+        repomix_entry,
+        download_status: DownloadStatus::Ready,
+        verification_error: None,
+      });
+    }
 
-    // create cache dir: ~/.cache/siff/repomix/0.3.7/
-    let cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join("repomix").join(&version);
+    // create cache dir: ~/.cache/siff/repomix/<version or tarball-hash>/
+    let (cache_dir, version) = Self::cache_dir_for(config)?.context("Repomix source has no cache directory")?;
 
     // repomix entry point will be at node_modules/repomix/bin/repomix.cjs
-    let repomix_entry = cache_dir.join("node_modules").join("repomix").join("bin").join("repomix.cjs");
+    let mut repomix_entry = cache_dir.join("node_modules").join("repomix").join("bin").join("repomix.cjs");
 
-    // check if repomix is already cached
-    let download_status = if repomix_entry.exists() { DownloadStatus::Ready } else { DownloadStatus::NotStarted };
+    // check if a verified install is already cached
+    let (download_status, verification_error) = match verify_install(&cache_dir, Some(&version)) {
+      Ok((entry, _)) => {
+        repomix_entry = entry;
+        (DownloadStatus::Ready, None)
+      }
+      Err(_) if !cache_dir.exists() => (DownloadStatus::NotStarted, None),
+      Err(e) => (DownloadStatus::NotStarted, Some(e.to_string())),
+    };
 
     Ok(Self {
       cache_dir,
       version,
+      source: config.source.clone(),
       repomix_entry,
       download_status,
+      verification_error,
     })
   }
 
+  /// Returns the cache dir and requested version for the configured source (None for system installs).
+  pub fn cache_dir_for(config: &RepomixConfig) -> Result<Option<(PathBuf, String)>> {
+    let requested = match &config.source {
+      RepomixSource::Npm => config.version.clone(),
+      RepomixSource::Tarball(tarball) => {
+        // key tarball installs by content, so a replaced tarball gets a fresh install
+        let bytes = std::fs::read(tarball).with_context(|| format!("Failed to read repomix tarball {}", tarball.display()))?;
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+        format!("tarball-{}", &hash[..12])
+      }
+      RepomixSource::System => return Ok(None),
+    };
+    Ok(Some((cache_root().join(&requested), requested)))
+  }
+
   /// Gets the current download status.
   pub fn download_status(&self) -> &DownloadStatus {
     &self.download_status
@@ -64,7 +157,11 @@ impl Repomix {
   pub async fn start_background_download(&mut self) -> bool {
     match self.download_status {
       DownloadStatus::NotStarted | DownloadStatus::Failed(_) => {
-        self.download_status = DownloadStatus::Downloading("Initializing...".to_string());
+        let message = match self.verification_error.take() {
+          Some(error) => format!("Cached install failed verification ({}), reinstalling...", error),
+          None => "Initializing...".to_string(),
+        };
+        self.download_status = DownloadStatus::Downloading(message);
         true
       }
       // already ready or downloading
@@ -93,6 +190,17 @@ impl Repomix {
     }
   }
 
+  /// Installs repomix if needed and waits for it, for runs without the UI.
+  pub async fn wait_until_ready(&mut self) -> Result<()> {
+    self.start_background_download().await;
+    self.update_background_download().await;
+    match &self.download_status {
+      DownloadStatus::Ready => Ok(()),
+      DownloadStatus::Failed(err) => Err(anyhow::anyhow!("Repomix download failed: {}", err)),
+      _ => Err(anyhow::anyhow!("Repomix download did not finish")),
+    }
+  }
+
   /// Make sure repomix is available in cache, download if needed.
   /// Called before every repomix execution.
   pub async fn ensure_repomix(&mut self) -> Result<PathBuf> {
@@ -100,6 +208,8 @@ impl Repomix {
       DownloadStatus::Ready => {
         if self.repomix_entry.exists() {
           Ok(self.repomix_entry.clone())
+        } else if self.source == RepomixSource::System {
+          Err(anyhow::anyhow!("Repomix was removed from {}", self.repomix_entry.display()))
         } else {
           // cache was deleted, restart download
           self.download_status = DownloadStatus::NotStarted;
@@ -112,40 +222,40 @@ impl Repomix {
     }
   }
 
-  /// Downloads repomix npm package to cache directory.
+  /// Installs the repomix npm package (from the registry or a tarball) to the cache directory.
   /// Runs once per version and creates an isolated repomix installation.
   async fn download_and_cache_repomix(&mut self) -> Result<()> {
     // update status
     self.download_status = DownloadStatus::Downloading("Creating cache directory...".to_string());
 
-    // create cache directory
+    // start from a clean dir, a partial or rejected install is never reused
+    if self.cache_dir.exists() {
+      std::fs::remove_dir_all(&self.cache_dir).context("Failed to remove old repomix install")?;
+    }
     std::fs::create_dir_all(&self.cache_dir).context("Failed to create repomix cache directory")?;
 
     // create package.json for repomix installation
     self.download_status = DownloadStatus::Downloading("Creating package.json...".to_string());
 
-    let package_json = format!(
-      r#"{{
-            "name": "siff-repomix-cache",
-            "version": "1.0.0",
-            "dependencies": {{
-                "repomix": "{}"
-            }}
-        }}"#,
-      self.version
-    );
+    let (dependency, install_args) = match &self.source {
+      RepomixSource::Tarball(tarball) => {
+        let tarball = tarball.canonicalize().with_context(|| format!("Repomix tarball not found: {}", tarball.display()))?;
+        (format!("file:{}", tarball.display()), vec!["install", "--no-audit", "--no-fund", "--silent", "--prefer-offline"])
+      }
+      _ => (self.version.clone(), vec!["install", "--no-audit", "--no-fund", "--silent"]),
+    };
+    let package_json = serde_json::json!({
+      "name": "siff-repomix-cache",
+      "version": "1.0.0",
+      "dependencies": { "repomix": dependency }
+    });
 
-    std::fs::write(self.cache_dir.join("package.json"), package_json)?;
+    std::fs::write(self.cache_dir.join("package.json"), serde_json::to_string_pretty(&package_json)?)?;
 
     // install repomix to cache directory
     self.download_status = DownloadStatus::Downloading(format!("Installing repomix {}...", self.version));
 
-    let npm_install = Command::new("npm")
-      .args(["install", "--no-audit", "--no-fund", "--silent"])
-      .current_dir(&self.cache_dir)
-      .output()
-      .await
-      .context("Failed to run npm install")?;
+    let npm_install = Command::new("npm").args(&install_args).current_dir(&self.cache_dir).output().await.context("Failed to run npm install")?;
 
     if !npm_install.status.success() {
       let stderr = String::from_utf8_lossy(&npm_install.stderr);
@@ -191,12 +301,23 @@ impl Repomix {
       }
     }
 
+    // record the install so later runs can verify it
+    let package_dir = self.cache_dir.join("node_modules").join("repomix");
+    let manifest = InstallManifest {
+      requested: self.version.clone(),
+      installed_version: installed_version(&package_dir)?,
+      entry: self.repomix_entry.strip_prefix(&self.cache_dir).unwrap_or(&self.repomix_entry).to_path_buf(),
+      sha256: hash_directory(&package_dir)?,
+    };
+    std::fs::write(self.cache_dir.join(INSTALL_MANIFEST), serde_json::to_string_pretty(&manifest)?).context("Failed to write repomix install manifest")?;
+
     Ok(())
   }
 
   /// Runs repomix with complete isolation and siff only configuration.
   /// Main entry point that replaces the old repomix runner.
-  pub async fn run_isolated_repomix(&mut self, selected_files: &[PathBuf], options: &RepomixOptions, working_directory: &Path, file_tree: &std::collections::HashMap<PathBuf, crate::types::FileNode>) -> Result<String> {
+  /// Progress is reported through the monitor, and the process group is killed if the run is cancelled.
+  pub async fn run_isolated_repomix(&mut self, selected_files: &[PathBuf], options: &RepomixOptions, working_directory: &Path, file_tree_text: Option<&str>, monitor: &RunMonitor) -> Result<BackendOutput> {
     if selected_files.is_empty() {
       return Err(anyhow::anyhow!("No files selected for processing"));
     }
@@ -204,26 +325,30 @@ impl Repomix {
     // check if repomix is available
     let repomix_path = self.ensure_repomix().await?;
 
+    // intermediate output goes to a private temp dir (never the user's repo), removed however the run ends
+    let temp_dir = tempfile::Builder::new().prefix("siff-repomix-").tempdir().context("Failed to create temporary directory for repomix")?;
+    let temp_file = temp_dir.path().join("repomix-output");
+
     // build isolated command arguments
-    let args = self.build_isolated_args(selected_files, options, working_directory)?;
+    let mut output = BackendOutput::default();
+    let args = self.build_isolated_args(selected_files, options, working_directory, &temp_file, &mut output.warnings)?;
 
     // create isolated environment
-    let env = self.create_isolated_environment()?;
+    let env = self.create_isolated_environment(temp_dir.path())?;
 
     // execute repomix using node with isolated environment
-    let output = Command::new("node")
+    let mut command = Command::new("node");
+    command
       .arg(&repomix_path)
       .args(&args)
       .env_clear() // clear all env vars
       .envs(&env) // only siff controlled env vars
-      .current_dir(working_directory)
-      .output()
-      .await
-      .context("Failed to execute isolated repomix")?;
+      .current_dir(working_directory);
+    let command_output = process_runner::run_monitored(command, monitor, "Packing", None, Some(&temp_file), None).await.context("Failed to execute isolated repomix")?;
 
-    if !output.status.success() {
-      let stderr = String::from_utf8_lossy(&output.stderr);
-      let stdout = String::from_utf8_lossy(&output.stdout);
+    if !command_output.status.success() {
+      let stderr = String::from_utf8_lossy(&command_output.stderr);
+      let stdout = String::from_utf8_lossy(&command_output.stdout);
 
       let error_msg = if !stderr.is_empty() && !stdout.is_empty() {
         format!("stderr: {} | stdout: {}", stderr, stdout)
@@ -235,11 +360,13 @@ impl Repomix {
         "Command failed with no error output".to_string()
       };
 
-      return Err(anyhow::anyhow!("Repomix failed with exit code {}: {}", output.status.code().unwrap_or(-1), error_msg));
+      return Err(anyhow::anyhow!("Repomix failed with exit code {}: {}", command_output.status.code().unwrap_or(-1), error_msg));
     }
 
+    // keep stderr of successful runs for the message log
+    output.stderr = String::from_utf8_lossy(&command_output.stderr).trim().to_string();
+
     // read the output file
-    let temp_file = working_directory.join(format!("siff-repomix-{}.md", std::process::id()));
     if !temp_file.exists() {
       return Err(anyhow::anyhow!("Repomix did not create the expected output file"));
     }
@@ -247,9 +374,7 @@ impl Repomix {
     let mut content = std::fs::read_to_string(&temp_file).context("Failed to read repomix output file")?;
 
     // if file tree is enabled, prepend it to the content
-    if options.file_tree {
-      let file_tree_text = crate::file_utils::generate_file_tree_text(file_tree, working_directory);
-
+    if let Some(file_tree_text) = file_tree_text.filter(|_| options.file_tree) {
       // format the file tree section based on output format
       let formatted_tree = match options.output_format {
         crate::types::OutputFormat::Xml => {
@@ -267,17 +392,21 @@ impl Repomix {
       content = format!("{}{}", formatted_tree, content);
     }
 
-    // copy to clipboard
-    self.copy_to_clipboard(&content).await?;
-
-    // cleanup temp file
-    let _ = std::fs::remove_file(&temp_file);
+    // write the output file if one was requested, siff never picks it up as input
+    if let Some(output_file) = &options.output_file {
+      let output_path = working_directory.join(output_file);
+      std::fs::write(&output_path, &content).with_context(|| format!("Failed to write output file {}", output_path.display()))?;
+      file_utils::register_produced_file(&output_path);
+    }
 
-    Ok(format!("{} files processed and copied to clipboard", selected_files.len()))
+    output.message = format!("{} files processed", selected_files.len());
+    output.content = content;
+    Ok(output)
   }
 
   /// Builds command arguments with complete siff control and no config interference.
-  fn build_isolated_args(&self, selected_files: &[PathBuf], options: &RepomixOptions, working_directory: &Path) -> Result<Vec<String>> {
+  /// Skipped files are reported through warnings.
+  fn build_isolated_args(&self, selected_files: &[PathBuf], options: &RepomixOptions, working_directory: &Path, output_path: &Path, warnings: &mut Vec<String>) -> Result<Vec<String>> {
     let mut args = vec![
       "--no-gitignore".to_string(),
       "--no-default-patterns".to_string(),
@@ -285,7 +414,7 @@ impl Repomix {
       // Note: repomix runs security-check by default. Since siff is opinionated, keep these check enabled by default.
       // TODO: could add this as option in repomix config
       "--output".to_string(),
-      working_directory.join(format!("siff-repomix-{}.md", std::process::id())).to_string_lossy().to_string(),
+      output_path.to_string_lossy().to_string(),
     ];
 
     // add siff controlled options only
@@ -304,7 +433,7 @@ impl Repomix {
 
     // for extremely large file counts, use directory-based patterns to avoid command line limits
     if selected_files.len() > 1000 {
-      let patterns = self.build_directory_patterns(selected_files, working_directory)?;
+      let patterns = self.build_directory_patterns(selected_files, working_directory, warnings)?;
 
       if !patterns.is_empty() {
         args.push("--include".to_string());
@@ -322,7 +451,7 @@ impl Repomix {
         let relative_path = match file_path.strip_prefix(working_directory) {
           Ok(rel_path) => rel_path,
           Err(_) => {
-            eprintln!("Warning: Skipping file outside working directory: {}", file_path.display());
+            warnings.push(format!("Skipping file outside working directory: {}", file_path.display()));
             continue;
           }
         };
@@ -332,19 +461,19 @@ impl Repomix {
 
         // validation for path that doesn't try to escape the working directory
         if path_str.contains("..") {
-          eprintln!("Warning: Skipping file with path traversal attempt: {}", path_str);
+          warnings.push(format!("Skipping file with path traversal attempt: {}", path_str));
           continue;
         }
 
         // path is not empty and doesn't start with dangerous characters
         if path_str.is_empty() || path_str.starts_with('-') {
-          eprintln!("Warning: Skipping file with invalid path: {}", path_str);
+          warnings.push(format!("Skipping file with invalid path: {}", path_str));
           continue;
         }
 
         // skip files with commas in names to prevent command injection (comma is used as separator in --include)
         if path_str.contains(',') {
-          eprintln!("Warning: Skipping file with comma in filename (security): {}", path_str);
+          warnings.push(format!("Skipping file with comma in filename (security): {}", path_str));
           continue;
         }
 
@@ -369,7 +498,7 @@ impl Repomix {
 
   /// Builds directory-based patterns for large file counts
   /// Groups files by directory and creates glob patterns.
-  fn build_directory_patterns(&self, selected_files: &[PathBuf], working_directory: &Path) -> Result<Vec<String>> {
+  fn build_directory_patterns(&self, selected_files: &[PathBuf], working_directory: &Path, warnings: &mut Vec<String>) -> Result<Vec<String>> {
     use std::collections::HashMap;
 
     // group files by parent directory
@@ -379,7 +508,7 @@ impl Repomix {
       let relative_path = match file_path.strip_prefix(working_directory) {
         Ok(rel_path) => rel_path,
         Err(_) => {
-          eprintln!("Warning: Skipping file outside working directory: {}", file_path.display());
+          warnings.push(format!("Skipping file outside working directory: {}", file_path.display()));
           continue;
         }
       };
@@ -388,7 +517,7 @@ impl Repomix {
 
       // skip invalid paths
       if path_str.contains("..") || path_str.is_empty() || path_str.starts_with('-') {
-        eprintln!("Warning: Skipping invalid file path: {}", path_str);
+        warnings.push(format!("Skipping invalid file path: {}", path_str));
         continue;
       }
 
@@ -421,7 +550,8 @@ impl Repomix {
   }
 
   /// Creates an isolated environment for repomix execution.
-  fn create_isolated_environment(&self) -> Result<HashMap<String, String>> {
+  /// The run's private temp dir doubles as home and temp dir, so nothing is written elsewhere.
+  fn create_isolated_environment(&self, temp_dir: &Path) -> Result<HashMap<String, String>> {
     let mut env = HashMap::new();
 
     // essential env variables only
@@ -429,9 +559,9 @@ impl Repomix {
     env.insert("NO_UPDATE_NOTIFIER".to_string(), "1".to_string());
     env.insert("NO_COLOR".to_string(), "1".to_string());
 
-    // create a fake home dir to avoid global config
-    let temp_home = std::env::temp_dir().join("siff-fake-home");
-    std::fs::create_dir_all(&temp_home).ok(); // ignore errors
+    // use a fake home dir to avoid global config
+    let temp_home = temp_dir.join("home");
+    std::fs::create_dir_all(&temp_home).context("Failed to create isolated home directory")?;
     env.insert("HOME".to_string(), temp_home.to_string_lossy().to_string());
     env.insert("USERPROFILE".to_string(), temp_home.to_string_lossy().to_string());
 
@@ -439,6 +569,11 @@ impl Repomix {
     env.insert("XDG_CONFIG_HOME".to_string(), temp_home.to_string_lossy().to_string());
     env.insert("APPDATA".to_string(), temp_home.to_string_lossy().to_string());
 
+    // keep any temp files node creates inside the run's temp dir
+    for name in ["TMPDIR", "TEMP", "TMP"] {
+      env.insert(name.to_string(), temp_dir.to_string_lossy().to_string());
+    }
+
     // minimal path for security (but include node)
     let node_path = self.get_node_path();
     env.insert("PATH".to_string(), node_path);
@@ -464,74 +599,140 @@ impl Repomix {
     common_node_paths.join(if cfg!(windows) { ";" } else { ":" })
   }
 
-  /// Copies content to clipboard using platform-specific commands.
-  async fn copy_to_clipboard(&self, content: &str) -> Result<()> {
-    let clipboard_cmd = if cfg!(target_os = "macos") {
-      vec!["pbcopy"]
-    } else if cfg!(target_os = "linux") {
-      // try xclip first, then xsel as fallback (for linux)
-      if Command::new("which").arg("xclip").output().await.is_ok() {
-        vec!["xclip", "-selection", "clipboard"]
-      } else if Command::new("which").arg("xsel").output().await.is_ok() {
-        vec!["xsel", "--clipboard", "--input"]
-      } else {
-        return Err(anyhow::anyhow!("No clipboard utility found. Please install xclip or xsel"));
-      }
-    } else if cfg!(target_os = "windows") {
-      vec!["clip"]
-    } else {
-      return Err(anyhow::anyhow!("Unsupported platform for clipboard operations"));
-    };
+  /// Checks if node and npm are available for downloading and running repomix.
+  /// Npm isn't needed when using a repomix install from PATH.
+  pub async fn check_build_dependencies(config: &RepomixConfig) -> Result<()> {
+    // check node
+    let node_check = Command::new("node").arg("--version").output().await;
 
-    let mut cmd = Command::new(clipboard_cmd[0]);
-    for arg in &clipboard_cmd[1..] {
-      cmd.arg(arg);
+    if node_check.is_err() {
+      return Err(anyhow::anyhow!("Node.js not found. Please install Node.js to use repomix integration."));
     }
 
-    let mut child = cmd
-      .stdin(std::process::Stdio::piped())
-      .stdout(std::process::Stdio::piped())
-      .stderr(std::process::Stdio::piped())
-      .spawn()
-      .context("Failed to spawn clipboard command")?;
-
-    // write content to stdin
-    if let Some(stdin) = child.stdin.take() {
-      use tokio::io::AsyncWriteExt;
-      let mut stdin = stdin;
-      stdin.write_all(content.as_bytes()).await.context("Failed to write to clipboard command stdin")?;
-      stdin.shutdown().await.context("Failed to close clipboard command stdin")?;
+    if config.source == RepomixSource::System {
+      return Ok(());
     }
 
-    // wait for command to complete
-    let output = child.wait_with_output().await.context("Failed to wait for clipboard command")?;
+    // check npm
+    let npm_check = Command::new("npm").arg("--version").output().await;
 
-    if !output.status.success() {
-      let stderr = String::from_utf8_lossy(&output.stderr);
-      return Err(anyhow::anyhow!("Clipboard command failed: {}", stderr));
+    if npm_check.is_err() {
+      return Err(anyhow::anyhow!("Npm not found. Please install Node.js and npm to use repomix integration."));
     }
 
     Ok(())
   }
+}
 
-  /// Checks if node and npm are available for downloading and running repomix.
-  pub async fn check_build_dependencies() -> Result<()> {
-    // check node
-    let node_check = Command::new("node").arg("--version").output().await;
+/// Returns the root of the repomix cache: ~/.cache/siff/repomix/
+fn cache_root() -> PathBuf {
+  dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join("repomix")
+}
 
-    if node_check.is_err() {
-      return Err(anyhow::anyhow!("Node.js not found. Please install Node.js to use repomix integration."));
+/// Checks a cached install against its manifest and returns the entry point and installed version.
+/// With a requested version, an install of anything else is rejected too.
+fn verify_install(cache_dir: &Path, requested: Option<&str>) -> Result<(PathBuf, String)> {
+  let manifest_content = std::fs::read_to_string(cache_dir.join(INSTALL_MANIFEST)).context("install is incomplete")?;
+  let manifest: InstallManifest = serde_json::from_str(&manifest_content).context("install manifest is corrupted")?;
+
+  if requested.is_some_and(|requested| requested != manifest.requested) {
+    return Err(anyhow::anyhow!("install is for {}", manifest.requested));
+  }
+
+  let entry = cache_dir.join(&manifest.entry);
+  if !entry.is_file() {
+    return Err(anyhow::anyhow!("entry point {} is missing", manifest.entry.display()));
+  }
+
+  let package_dir = cache_dir.join("node_modules").join("repomix");
+  if hash_directory(&package_dir)? != manifest.sha256 {
+    return Err(anyhow::anyhow!("installed files were modified"));
+  }
+
+  Ok((entry, manifest.installed_version))
+}
+
+/// Reads the version from an installed package's package.json.
+fn installed_version(package_dir: &Path) -> Result<String> {
+  let content = std::fs::read_to_string(package_dir.join("package.json")).context("Failed to read installed repomix package.json")?;
+  let package: serde_json::Value = serde_json::from_str(&content).context("Failed to parse installed repomix package.json")?;
+  package["version"].as_str().map(str::to_string).context("Installed repomix package.json has no version")
+}
+
+/// Hashes every file in a directory (relative paths and contents, in a stable order).
+fn hash_directory(dir: &Path) -> Result<String> {
+  let mut files = Vec::new();
+  let mut pending = vec![dir.to_path_buf()];
+  while let Some(current) = pending.pop() {
+    for entry in std::fs::read_dir(&current).with_context(|| format!("Failed to read {}", current.display()))? {
+      let path = entry?.path();
+      if path.is_dir() {
+        pending.push(path);
+      } else {
+        files.push(path);
+      }
     }
+  }
+  files.sort();
 
-    // check npm
-    let npm_check = Command::new("npm").arg("--version").output().await;
+  let mut hasher = Sha256::new();
+  for file in files {
+    hasher.update(file.strip_prefix(dir).unwrap_or(&file).to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(std::fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?);
+  }
+  Ok(format!("{:x}", hasher.finalize()))
+}
 
-    if npm_check.is_err() {
-      return Err(anyhow::anyhow!("Npm not found. Please install Node.js and npm to use repomix integration."));
+/// Returns the total size of a directory in bytes.
+fn directory_size(dir: &Path) -> u64 {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return 0;
+  };
+  entries
+    .flatten()
+    .map(|entry| match entry.metadata() {
+      Ok(metadata) if metadata.is_dir() => directory_size(&entry.path()),
+      Ok(metadata) => metadata.len(),
+      Err(_) => 0,
+    })
+    .sum()
+}
+
+/// Lists the repomix installs in the cache, verifying each one.
+pub fn cached_installs() -> Result<Vec<CachedInstall>> {
+  let root = cache_root();
+  if !root.exists() {
+    return Ok(Vec::new());
+  }
+
+  let mut installs = Vec::new();
+  for entry in std::fs::read_dir(&root).with_context(|| format!("Failed to read repomix cache {}", root.display()))? {
+    let path = entry?.path();
+    if !path.is_dir() {
+      continue;
     }
+    installs.push(CachedInstall {
+      size: directory_size(&path),
+      status: verify_install(&path, None).map(|(_, version)| version).map_err(|e| e.to_string()),
+      path,
+    });
+  }
+  installs.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(installs)
+}
 
-    Ok(())
+/// Removes cached repomix installs, except the one at keep. Returns what was removed.
+pub fn prune_cache(keep: Option<&Path>) -> Result<Vec<CachedInstall>> {
+  let mut removed = Vec::new();
+  for install in cached_installs()? {
+    if Some(install.path.as_path()) == keep {
+      continue;
+    }
+    std::fs::remove_dir_all(&install.path).with_context(|| format!("Failed to remove {}", install.path.display()))?;
+    removed.push(install);
   }
+  Ok(removed)
 }
 
 /// Validates repomix options for the isolated execution.
@@ -549,3 +750,41 @@ pub fn validate_isolated_repomix_options(_options: &RepomixOptions, selected_fil
 
   warnings
 }
+
+// test for cached install verification
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_verify_install_detects_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache_dir = temp_dir.path();
+    let package_dir = cache_dir.join("node_modules").join("repomix");
+    std::fs::create_dir_all(package_dir.join("bin")).unwrap();
+    std::fs::write(package_dir.join("package.json"), r#"{"name":"repomix","version":"0.3.7"}"#).unwrap();
+    std::fs::write(package_dir.join("bin").join("repomix.cjs"), "console.log('repomix')").unwrap();
+
+    // no manifest means the install never finished
+    assert!(verify_install(cache_dir, None).is_err());
+
+    let manifest = InstallManifest {
+      requested: "0.3.7".to_string(),
+      installed_version: installed_version(&package_dir).unwrap(),
+      entry: PathBuf::from("node_modules/repomix/bin/repomix.cjs"),
+      sha256: hash_directory(&package_dir).unwrap(),
+    };
+    std::fs::write(cache_dir.join(INSTALL_MANIFEST), serde_json::to_string(&manifest).unwrap()).unwrap();
+
+    let (entry, version) = verify_install(cache_dir, Some("0.3.7")).unwrap();
+    assert_eq!(entry, package_dir.join("bin").join("repomix.cjs"));
+    assert_eq!(version, "0.3.7");
+
+    // a different configured version or modified files reject the install
+    assert!(verify_install(cache_dir, Some("0.4.0")).is_err());
+    std::fs::write(package_dir.join("bin").join("repomix.cjs"), "console.log('tampered')").unwrap();
+    assert!(verify_install(cache_dir, None).is_err());
+  }
+}
@@ -0,0 +1,185 @@
+use crate::file_utils;
+use crate::types::FileNode;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+
+/// Maximum number of selection operations kept for undo.
+const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// Maximum number of file paths stored across all history entries.
+/// Keeps memory bounded when select all/unselect all runs on huge trees.
+const MAX_HISTORY_PATHS: usize = 500_000;
+
+/// Tells that an operation changed too many files to be undone, which clears the history.
+pub fn not_recorded_message(label: &str, changed_files: usize) -> String {
+  format!("Warning: {} changed {} files, too many to undo, undo history cleared", label, changed_files)
+}
+
+/// A single recorded selection operation.
+/// Stores only the files whose selection flipped, so replaying the flip undoes or redoes it.
+#[derive(Debug)]
+struct SelectionChange {
+  /// Short description of the operation (shown in status messages)
+  label: String,
+  /// Files whose selection state flipped
+  flipped_files: Vec<PathBuf>,
+}
+
+/// Undo/redo history for selection changes.
+#[derive(Debug)]
+pub struct SelectionHistory {
+  /// Operations that can be undone (oldest first)
+  undo_stack: VecDeque<SelectionChange>,
+  /// Operations that can be redone (most recent last)
+  redo_stack: Vec<SelectionChange>,
+  /// Number of paths stored across both stacks
+  stored_paths: usize,
+  /// Maximum number of operations kept for undo
+  max_entries: usize,
+  /// Maximum number of paths stored across both stacks
+  max_paths: usize,
+}
+
+impl Default for SelectionHistory {
+  fn default() -> Self {
+    Self::new(MAX_HISTORY_ENTRIES, MAX_HISTORY_PATHS)
+  }
+}
+
+impl SelectionHistory {
+  /// Creates an empty history with the given limits.
+  pub fn new(max_entries: usize, max_paths: usize) -> Self {
+    Self {
+      undo_stack: VecDeque::new(),
+      redo_stack: Vec::new(),
+      stored_paths: 0,
+      max_entries,
+      max_paths,
+    }
+  }
+
+  /// Records a selection operation, dropping the redo history.
+  /// Returns false for operations larger than the path budget, they aren't recorded and the
+  /// whole history is cleared, since replaying earlier flips would build a selection that never existed.
+  pub fn record(&mut self, label: &str, flipped_files: Vec<PathBuf>) -> bool {
+    if flipped_files.is_empty() {
+      return true;
+    }
+
+    if flipped_files.len() > self.max_paths {
+      self.undo_stack.clear();
+      self.redo_stack.clear();
+      self.stored_paths = 0;
+      return false;
+    }
+
+    // any new operation invalidates the redo history
+    self.stored_paths -= self.redo_stack.iter().map(|change| change.flipped_files.len()).sum::<usize>();
+    self.redo_stack.clear();
+
+    // evict oldest operations until the new one fits
+    while self.undo_stack.len() >= self.max_entries || self.stored_paths + flipped_files.len() > self.max_paths {
+      match self.undo_stack.pop_front() {
+        Some(oldest) => self.stored_paths -= oldest.flipped_files.len(),
+        None => break,
+      }
+    }
+
+    self.stored_paths += flipped_files.len();
+    self.undo_stack.push_back(SelectionChange { label: label.to_string(), flipped_files });
+    true
+  }
+
+  /// Undoes the most recent operation, returns its label and flipped files if there was one.
+  pub fn undo(&mut self, file_tree: &mut HashMap<PathBuf, FileNode>) -> Option<(String, Vec<PathBuf>)> {
+    let change = self.undo_stack.pop_back()?;
+    file_utils::replay_file_selections(file_tree, &change.flipped_files);
+    let replayed = (change.label.clone(), change.flipped_files.clone());
+    self.redo_stack.push(change);
+    Some(replayed)
+  }
+
+  /// Redoes the most recently undone operation, returns its label and flipped files if there was one.
+  pub fn redo(&mut self, file_tree: &mut HashMap<PathBuf, FileNode>) -> Option<(String, Vec<PathBuf>)> {
+    let change = self.redo_stack.pop()?;
+    file_utils::replay_file_selections(file_tree, &change.flipped_files);
+    let replayed = (change.label.clone(), change.flipped_files.clone());
+    self.undo_stack.push_back(change);
+    Some(replayed)
+  }
+}
+
+// test for undo/redo and history limits
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_undo_redo_select_all() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.rs"), "a").unwrap();
+    fs::write(root.join("b.rs"), "b").unwrap();
+
+    let mut file_tree = file_utils::scan_directory(root).unwrap();
+    let mut history = SelectionHistory::default();
+
+    // select a single file, then select everything
+    let changed = file_utils::toggle_selection_recursive(&mut file_tree, &root.join("a.rs")).unwrap();
+    history.record("Toggle selection", changed);
+    let changed = file_utils::select_all_visible_files(&mut file_tree, &[root.join("a.rs"), root.join("b.rs")]).unwrap();
+    assert_eq!(changed, vec![root.join("b.rs")]);
+    history.record("Select all", changed);
+    assert_eq!(file_tree[root].selected_files, 2);
+
+    // undo restores the hand-built selection
+    assert_eq!(history.undo(&mut file_tree).map(|(label, _)| label).as_deref(), Some("Select all"));
+    assert!(file_tree[&root.join("a.rs")].is_selected);
+    assert!(!file_tree[&root.join("b.rs")].is_selected);
+    assert_eq!(file_tree[root].selected_files, 1);
+
+    // redo applies it again
+    assert_eq!(history.redo(&mut file_tree).map(|(label, _)| label).as_deref(), Some("Select all"));
+    assert_eq!(file_tree[root].selected_files, 2);
+  }
+
+  #[test]
+  fn test_history_is_bounded() {
+    let mut history = SelectionHistory::new(2, 3);
+    history.record("one", vec![PathBuf::from("a")]);
+    history.record("two", vec![PathBuf::from("b")]);
+    history.record("three", vec![PathBuf::from("c"), PathBuf::from("d")]);
+    assert_eq!(history.undo_stack.len(), 2);
+    assert_eq!(history.stored_paths, 3);
+
+    // operations over the path budget aren't recorded and clear the history
+    assert!(!history.record("huge", vec![PathBuf::from("e"); 4]));
+    assert!(history.undo_stack.is_empty());
+    assert_eq!(history.stored_paths, 0);
+  }
+
+  #[test]
+  fn test_oversized_operation_clears_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.rs"), "a").unwrap();
+    fs::write(root.join("b.rs"), "b").unwrap();
+    fs::write(root.join("c.rs"), "c").unwrap();
+
+    let mut file_tree = file_utils::scan_directory(root).unwrap();
+    let mut history = SelectionHistory::new(10, 1);
+
+    // toggle fits, select all is too large to record
+    let changed = file_utils::toggle_selection_recursive(&mut file_tree, &root.join("a.rs")).unwrap();
+    assert!(history.record("Toggle selection", changed));
+    let changed = file_utils::select_all_visible_files(&mut file_tree, &[root.join("a.rs"), root.join("b.rs"), root.join("c.rs")]).unwrap();
+    assert!(!history.record("Select all", changed));
+
+    // undo can't unselect a.rs while b.rs and c.rs stay selected
+    assert!(history.undo(&mut file_tree).is_none());
+    assert_eq!(file_tree[root].selected_files, 3);
+  }
+}
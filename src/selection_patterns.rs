@@ -0,0 +1,267 @@
+use crate::file_utils::SessionRoots;
+use crate::types::FileNode;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Pause in typing after which the pattern preview is refreshed, walking huge trees on every key would lag.
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Kind of matcher used by a selection pattern.
+#[derive(Debug, Clone)]
+enum PatternKind {
+  /// Glob matched against the relative path (or file name if it has no `/`)
+  Glob { matcher: GlobMatcher, match_file_name: bool },
+  /// One or more file extensions (`ext:py,rs`)
+  Extension(Vec<String>),
+  /// Regular expression matched against the relative path (`re:handler`)
+  Regex(Regex),
+}
+
+/// A single selection pattern typed into the pattern prompt.
+/// Patterns prefixed with `!` remove matching files instead of adding them.
+#[derive(Debug, Clone)]
+pub struct SelectionPattern {
+  /// Whether matching files are removed from the selection
+  negated: bool,
+  /// How files are matched
+  kind: PatternKind,
+}
+
+impl SelectionPattern {
+  /// Parses a single pattern term such as `src/**/*.rs`, `!**/tests/**`, `ext:py` or `re:handler`.
+  pub fn parse(term: &str) -> Result<Self> {
+    let (negated, body) = match term.strip_prefix('!') {
+      Some(rest) => (true, rest),
+      None => (false, term),
+    };
+
+    if body.is_empty() {
+      return Err(anyhow::anyhow!("Empty pattern"));
+    }
+
+    let kind = if let Some(extensions) = body.strip_prefix("ext:") {
+      let extensions: Vec<String> = extensions.split(',').map(|ext| ext.trim().trim_start_matches('.').to_lowercase()).filter(|ext| !ext.is_empty()).collect();
+      if extensions.is_empty() {
+        return Err(anyhow::anyhow!("No extension given in '{}'", term));
+      }
+      PatternKind::Extension(extensions)
+    } else if let Some(expression) = body.strip_prefix("re:") {
+      PatternKind::Regex(Regex::new(expression).with_context(|| format!("Invalid regex '{}'", expression))?)
+    } else {
+      let glob_text = body.trim_end_matches('/');
+      let matcher = GlobBuilder::new(glob_text).literal_separator(true).build().with_context(|| format!("Invalid glob '{}'", body))?.compile_matcher();
+      PatternKind::Glob {
+        matcher,
+        match_file_name: !glob_text.contains('/'),
+      }
+    };
+
+    Ok(Self { negated, kind })
+  }
+
+  /// Returns true if the pattern matches the given path (relative to the root).
+  /// Globs also match files inside a matching directory.
+  pub fn matches(&self, relative_path: &Path) -> bool {
+    match &self.kind {
+      PatternKind::Glob { matcher, match_file_name } => relative_path.ancestors().filter(|ancestor| !ancestor.as_os_str().is_empty()).any(|ancestor| {
+        if *match_file_name {
+          ancestor.file_name().map(|name| matcher.is_match(name)).unwrap_or(false)
+        } else {
+          matcher.is_match(ancestor)
+        }
+      }),
+      PatternKind::Extension(extensions) => relative_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.iter().any(|wanted| ext.eq_ignore_ascii_case(wanted)))
+        .unwrap_or(false),
+      PatternKind::Regex(regex) => regex.is_match(&relative_path.to_string_lossy().replace('\\', "/")),
+    }
+  }
+}
+
+/// Parses whitespace separated pattern terms, applied in order.
+pub fn parse_patterns(input: &str) -> Result<Vec<SelectionPattern>> {
+  input.split_whitespace().map(SelectionPattern::parse).collect()
+}
+
+/// Files whose selection would change if the patterns were applied.
+#[derive(Debug, Clone, Default)]
+pub struct PatternPreview {
+  /// Files that would be added to the selection
+  pub to_select: Vec<PathBuf>,
+  /// Files that would be removed from the selection
+  pub to_deselect: Vec<PathBuf>,
+  /// Files selected after applying the patterns
+  pub resulting_files: Vec<PathBuf>,
+}
+
+impl PatternPreview {
+  /// Returns every file whose selection flips when the preview is applied.
+  pub fn changed_files(&self) -> Vec<PathBuf> {
+    self.to_select.iter().chain(self.to_deselect.iter()).cloned().collect()
+  }
+}
+
+/// Computes which files the patterns would add or remove, without changing the tree.
+/// Later patterns win over earlier ones, so `src/** !**/tests/**` selects src without tests.
+pub fn preview_patterns(file_tree: &HashMap<PathBuf, FileNode>, roots: &SessionRoots, patterns: &[SelectionPattern]) -> PatternPreview {
+  let mut preview = PatternPreview::default();
+
+  let mut stack = vec![roots.root_path()];
+  while let Some(path) = stack.pop() {
+    let Some(node) = file_tree.get(&path) else {
+      continue;
+    };
+
+    if node.is_directory {
+      stack.extend(node.children.iter().cloned());
+      continue;
+    }
+
+    let relative_path = roots.relative(&path);
+    let mut selected = node.is_selected;
+    for pattern in patterns {
+      if pattern.matches(&relative_path) {
+        selected = !pattern.negated;
+      }
+    }
+    // patterns never add binary or unreadable files, forced ones can still be removed
+    selected &= node.is_selected || node.content.is_text();
+
+    match (node.is_selected, selected) {
+      (false, true) => preview.to_select.push(path.clone()),
+      (true, false) => preview.to_deselect.push(path.clone()),
+      _ => {}
+    }
+
+    if selected {
+      preview.resulting_files.push(path);
+    }
+  }
+
+  preview
+}
+
+/// State of the pattern selection prompt while it's open.
+#[derive(Debug, Clone, Default)]
+pub struct PatternPrompt {
+  /// Text typed so far
+  pub input: String,
+  /// Preview for the current input (none if the input is empty or invalid)
+  pub preview: Option<PatternPreview>,
+  /// Parse error for the current input
+  pub error: Option<String>,
+  /// When the input last changed, set until the preview is refreshed
+  pub edited_at: Option<Instant>,
+}
+
+impl PatternPrompt {
+  /// Re-parses the input and refreshes the preview.
+  pub fn refresh(&mut self, file_tree: &HashMap<PathBuf, FileNode>, roots: &SessionRoots) {
+    self.preview = None;
+    self.error = None;
+    self.edited_at = None;
+
+    if self.input.trim().is_empty() {
+      return;
+    }
+
+    match parse_patterns(&self.input) {
+      Ok(patterns) => self.preview = Some(preview_patterns(file_tree, roots, &patterns)),
+      Err(e) => self.error = Some(e.to_string()),
+    }
+  }
+
+  /// Marks the preview as out of date (input or tree changed), it's refreshed once typing pauses.
+  pub fn mark_stale(&mut self) {
+    self.edited_at = Some(Instant::now());
+  }
+
+  /// Returns true if the preview is out of date and typing has paused.
+  pub fn needs_refresh(&self) -> bool {
+    self.edited_at.is_some_and(|edited_at| edited_at.elapsed() >= PREVIEW_DEBOUNCE)
+  }
+}
+
+// test for pattern parsing and matching
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_pattern_matching() {
+    let glob = SelectionPattern::parse("src/**/*.rs").unwrap();
+    assert!(glob.matches(Path::new("src/a/b.rs")));
+    assert!(!glob.matches(Path::new("tests/b.rs")));
+
+    let file_name_glob = SelectionPattern::parse("*.md").unwrap();
+    assert!(file_name_glob.matches(Path::new("docs/guide/intro.md")));
+
+    let dir_glob = SelectionPattern::parse("!**/tests/**").unwrap();
+    assert!(dir_glob.negated);
+    assert!(dir_glob.matches(Path::new("src/tests/a.rs")));
+
+    let ext = SelectionPattern::parse("ext:py,.RS").unwrap();
+    assert!(ext.matches(Path::new("a/b.py")));
+    assert!(ext.matches(Path::new("lib.rs")));
+    assert!(!ext.matches(Path::new("Makefile")));
+
+    let regex = SelectionPattern::parse("re:handler").unwrap();
+    assert!(regex.matches(Path::new("src/http/handler_user.go")));
+
+    assert!(SelectionPattern::parse("re:(").is_err());
+  }
+
+  #[test]
+  fn test_preview_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("src/tests")).unwrap();
+    fs::write(root.join("src/lib.rs"), "").unwrap();
+    fs::write(root.join("src/tests/lib_test.rs"), "").unwrap();
+    fs::write(root.join("main.py"), "").unwrap();
+
+    let file_tree = crate::file_utils::scan_directory(root).unwrap();
+    let patterns = parse_patterns("src/** !**/tests/**").unwrap();
+    let preview = preview_patterns(&file_tree, &SessionRoots::single(root), &patterns);
+
+    assert_eq!(preview.to_select, vec![root.join("src/lib.rs")]);
+    assert!(preview.to_deselect.is_empty());
+    assert_eq!(preview.resulting_files.len(), 1);
+
+    // typing only marks the preview stale, it's refreshed after a pause
+    let mut prompt = PatternPrompt {
+      input: "*.py".to_string(),
+      ..Default::default()
+    };
+    prompt.mark_stale();
+    assert!(!prompt.needs_refresh() && prompt.preview.is_none());
+    prompt.edited_at = Some(Instant::now() - PREVIEW_DEBOUNCE);
+    assert!(prompt.needs_refresh());
+    prompt.refresh(&file_tree, &SessionRoots::single(root));
+    assert!(!prompt.needs_refresh());
+    assert_eq!(prompt.preview.unwrap().to_select, vec![root.join("main.py")]);
+  }
+
+  #[test]
+  fn test_patterns_skip_binary_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR").unwrap();
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+
+    let file_tree = crate::file_utils::scan_directory(root).unwrap();
+    for input in ["*", "ext:png", "re:."] {
+      let preview = preview_patterns(&file_tree, &SessionRoots::single(root), &parse_patterns(input).unwrap());
+      assert!(!preview.to_select.contains(&root.join("logo.png")), "{} selected the png", input);
+    }
+  }
+}
@@ -0,0 +1,292 @@
+use crate::types::StatusLevel;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+/// Token count where the count is shown as medium.
+const TOKEN_MEDIUM_THRESHOLD: usize = 1_000;
+
+/// Token count where the count is shown as high.
+const TOKEN_HIGH_THRESHOLD: usize = 10_000;
+
+/// Built-in color themes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+  /// Colors for dark terminals (the original look)
+  #[default]
+  Dark,
+  /// Colors for light terminals
+  Light,
+  /// Bright colors with bold text and reversed highlight
+  HighContrast,
+  /// Blue/orange palette distinguishable with common color blindness
+  ColorblindSafe,
+  /// No colors, only bold/underline/reverse (used when NO_COLOR is set)
+  Monochrome,
+}
+
+impl ThemeName {
+  /// Returns the display name for the theme.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      ThemeName::Dark => "Dark",
+      ThemeName::Light => "Light",
+      ThemeName::HighContrast => "High contrast",
+      ThemeName::ColorblindSafe => "Colorblind safe",
+      ThemeName::Monochrome => "Monochrome",
+    }
+  }
+
+  /// Returns the next theme (for cycling from the command palette).
+  pub fn next(&self) -> Self {
+    match self {
+      ThemeName::Dark => ThemeName::Light,
+      ThemeName::Light => ThemeName::HighContrast,
+      ThemeName::HighContrast => ThemeName::ColorblindSafe,
+      ThemeName::ColorblindSafe => ThemeName::Monochrome,
+      ThemeName::Monochrome => ThemeName::Dark,
+    }
+  }
+}
+
+/// Styles used across the UI, resolved from a theme name.
+#[derive(Debug, Clone)]
+pub struct Theme {
+  /// Theme the styles came from
+  pub name: ThemeName,
+  /// Borders and titles
+  pub accent: Style,
+  /// Regular text and unselected files
+  pub text: Style,
+  /// Disabled options
+  pub muted: Style,
+  /// Root/selection info line
+  pub info: Style,
+  /// Nav hints and key labels
+  pub hint: Style,
+  /// Total token count and prompt token preview
+  pub tokens: Style,
+  /// Selected files and fully selected directories, enabled options
+  pub selected: Style,
+  /// Partially selected directories
+  pub partial: Style,
+  /// Unselected directories
+  pub directory: Style,
+  /// Highlighted row
+  pub highlight: Style,
+  /// Text on the highlighted row
+  pub highlighted_text: Style,
+  /// Token counts on the highlighted row
+  pub highlighted_tokens: Style,
+  /// Token counts below the medium threshold
+  pub token_low: Style,
+  /// Token counts below the high threshold
+  pub token_medium: Style,
+  /// Token counts above the high threshold
+  pub token_high: Style,
+  /// Neutral status messages
+  pub status_info: Style,
+  /// Status messages for work in progress
+  pub status_progress: Style,
+  /// Status messages for completed work
+  pub status_success: Style,
+  /// Warning status messages
+  pub status_warning: Style,
+  /// Error status messages
+  pub status_error: Style,
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Self::new(ThemeName::default())
+  }
+}
+
+impl Theme {
+  /// Builds the styles for a named theme.
+  pub fn new(name: ThemeName) -> Self {
+    let fg = |color: Color| Style::default().fg(color);
+    let bold = |color: Color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+
+    match name {
+      ThemeName::Dark => Self {
+        name,
+        accent: fg(Color::Green),
+        text: fg(Color::White),
+        muted: fg(Color::Gray),
+        info: fg(Color::Cyan),
+        hint: fg(Color::Yellow),
+        tokens: fg(Color::Yellow),
+        selected: fg(Color::Green),
+        partial: fg(Color::Yellow),
+        directory: fg(Color::Cyan),
+        highlight: Style::default().bg(Color::Blue),
+        highlighted_text: fg(Color::White),
+        highlighted_tokens: fg(Color::LightBlue),
+        token_low: fg(Color::Green),
+        token_medium: fg(Color::Yellow),
+        token_high: fg(Color::Red),
+        status_info: fg(Color::White),
+        status_progress: fg(Color::Cyan),
+        status_success: fg(Color::Green),
+        status_warning: fg(Color::Yellow),
+        status_error: fg(Color::Red),
+      },
+      ThemeName::Light => Self {
+        name,
+        accent: fg(Color::Blue),
+        text: fg(Color::Black),
+        muted: fg(Color::DarkGray),
+        info: fg(Color::Blue),
+        hint: fg(Color::Magenta),
+        tokens: fg(Color::Magenta),
+        selected: fg(Color::Green),
+        partial: fg(Color::Magenta),
+        directory: fg(Color::Blue),
+        highlight: Style::default().bg(Color::LightBlue),
+        highlighted_text: fg(Color::Black),
+        highlighted_tokens: fg(Color::Black),
+        token_low: fg(Color::Green),
+        token_medium: fg(Color::Magenta),
+        token_high: fg(Color::Red),
+        status_info: fg(Color::Black),
+        status_progress: fg(Color::Blue),
+        status_success: fg(Color::Green),
+        status_warning: fg(Color::Magenta),
+        status_error: fg(Color::Red),
+      },
+      ThemeName::HighContrast => Self {
+        name,
+        accent: bold(Color::White),
+        text: fg(Color::White),
+        muted: fg(Color::Gray),
+        info: bold(Color::LightCyan),
+        hint: fg(Color::LightYellow),
+        tokens: bold(Color::LightYellow),
+        selected: bold(Color::LightGreen),
+        partial: bold(Color::LightYellow),
+        directory: bold(Color::LightCyan),
+        highlight: Style::default().add_modifier(Modifier::REVERSED),
+        highlighted_text: Style::default(),
+        highlighted_tokens: Style::default(),
+        token_low: fg(Color::LightGreen),
+        token_medium: fg(Color::LightYellow),
+        token_high: bold(Color::LightRed),
+        status_info: fg(Color::White),
+        status_progress: bold(Color::LightCyan),
+        status_success: bold(Color::LightGreen),
+        status_warning: bold(Color::LightYellow),
+        status_error: bold(Color::LightRed),
+      },
+      ThemeName::ColorblindSafe => {
+        // okabe-ito palette, avoids relying on red vs green
+        let blue = Color::Rgb(0, 114, 178);
+        let sky = Color::Rgb(86, 180, 233);
+        let orange = Color::Rgb(230, 159, 0);
+        let vermillion = Color::Rgb(213, 94, 0);
+        Self {
+          name,
+          accent: fg(sky),
+          text: fg(Color::White),
+          muted: fg(Color::Gray),
+          info: fg(sky),
+          hint: fg(orange),
+          tokens: fg(orange),
+          selected: bold(sky),
+          partial: fg(orange),
+          directory: fg(Color::White),
+          highlight: Style::default().bg(blue),
+          highlighted_text: fg(Color::White),
+          highlighted_tokens: fg(Color::White),
+          token_low: fg(sky),
+          token_medium: fg(orange),
+          token_high: bold(vermillion),
+          status_info: fg(Color::White),
+          status_progress: fg(sky),
+          status_success: bold(sky),
+          status_warning: fg(orange),
+          status_error: bold(vermillion),
+        }
+      }
+      ThemeName::Monochrome => {
+        let plain = Style::default();
+        let strong = Style::default().add_modifier(Modifier::BOLD);
+        Self {
+          name,
+          accent: plain,
+          text: plain,
+          muted: Style::default().add_modifier(Modifier::DIM),
+          info: plain,
+          hint: plain,
+          tokens: strong,
+          selected: strong,
+          partial: Style::default().add_modifier(Modifier::UNDERLINED),
+          directory: plain,
+          highlight: Style::default().add_modifier(Modifier::REVERSED),
+          highlighted_text: plain,
+          highlighted_tokens: plain,
+          token_low: plain,
+          token_medium: plain,
+          token_high: strong,
+          status_info: plain,
+          status_progress: plain,
+          status_success: plain,
+          status_warning: strong,
+          status_error: strong,
+        }
+      }
+    }
+  }
+
+  /// Resolves the configured theme, NO_COLOR (if set and not empty) forces monochrome.
+  pub fn from_config(name: ThemeName) -> Self {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    Self::new(if no_color { ThemeName::Monochrome } else { name })
+  }
+
+  /// Returns the style for a status message level.
+  pub fn status(&self, level: StatusLevel) -> Style {
+    match level {
+      StatusLevel::Info => self.status_info,
+      StatusLevel::Progress => self.status_progress,
+      StatusLevel::Success => self.status_success,
+      StatusLevel::Warning => self.status_warning,
+      StatusLevel::Error => self.status_error,
+    }
+  }
+
+  /// Returns the style for a token count based on a three-tier system.
+  pub fn token_count(&self, token_count: usize) -> Style {
+    if token_count < TOKEN_MEDIUM_THRESHOLD {
+      self.token_low
+    } else if token_count < TOKEN_HIGH_THRESHOLD {
+      self.token_medium
+    } else {
+      self.token_high
+    }
+  }
+}
+
+// test for theme resolution
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_monochrome_has_no_colors() {
+    let theme = Theme::new(ThemeName::Monochrome);
+    for level in [StatusLevel::Info, StatusLevel::Progress, StatusLevel::Success, StatusLevel::Warning, StatusLevel::Error] {
+      assert_eq!(theme.status(level).fg, None);
+    }
+    assert_eq!(theme.token_count(50_000).fg, None);
+    assert_eq!(theme.highlight.bg, None);
+
+    // every theme is reachable by cycling
+    let mut name = ThemeName::Dark;
+    for _ in 0..5 {
+      name = name.next();
+    }
+    assert_eq!(name, ThemeName::Dark);
+  }
+}
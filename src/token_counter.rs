@@ -78,11 +78,11 @@ impl TokenCounter {
       }
     }
 
-    // read file content
-    let content = match tokio::fs::read_to_string(file_path).await {
-      Ok(content) => content,
-      Err(_) => {
-        // if can't read the file (binary or permission issues), cache and return 0
+    // read and decode file content (UTF-16 and Latin-1 sources are converted)
+    let content = match tokio::fs::read(file_path).await.ok().and_then(|bytes| crate::encoding::decode(&bytes)) {
+      Some((content, _)) => content,
+      None => {
+        // binary files and ones that can't be read (permissions) count as 0, they're marked in the tree
         let mut cache = self.file_token_cache.lock().await;
         cache.insert(file_path.to_path_buf(), 0);
         return Ok(0);
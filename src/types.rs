@@ -1,6 +1,17 @@
+use crate::command_palette::CommandPalette;
+use crate::custom_backend::CustomBackendConfig;
+use crate::file_utils::SessionRoots;
+use crate::keymap::Keymap;
+use crate::notifications::NotificationLog;
+use crate::redaction::{RedactedFile, RedactionRule};
+use crate::secrets::{FlaggedFile, SecretsConfig};
+use crate::selection_history::SelectionHistory;
+use crate::selection_patterns::PatternPrompt;
+use crate::theme::Theme;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use tokio_util::sync::CancellationToken;
 
 /// Backend options for file processing.
@@ -9,14 +20,32 @@ pub enum Backend {
   #[default]
   Repomix,
   Yek,
+  /// Command defined in the config file, by name
+  Custom(String),
 }
 
 impl Backend {
   /// Returns the display name for backend.
-  pub fn display_name(&self) -> &'static str {
+  pub fn display_name(&self) -> &str {
     match self {
       Backend::Repomix => "Repomix",
       Backend::Yek => "Yek",
+      Backend::Custom(name) => name,
+    }
+  }
+
+  /// Returns the next backend (for switching with a single key).
+  /// Custom backends come after yek, in the order they're configured.
+  pub fn next(&self, custom_backends: &[String]) -> Self {
+    let position = match self {
+      Backend::Custom(name) => custom_backends.iter().position(|custom| custom == name).map(|index| index + 1),
+      _ => None,
+    };
+    match (self, position) {
+      (Backend::Repomix, _) => Backend::Yek,
+      (Backend::Yek, _) => custom_backends.first().map_or(Backend::Repomix, |name| Backend::Custom(name.clone())),
+      (Backend::Custom(_), Some(index)) if index < custom_backends.len() => Backend::Custom(custom_backends[index].clone()),
+      (Backend::Custom(_), _) => Backend::Repomix,
     }
   }
 }
@@ -50,6 +79,111 @@ impl OutputFormat {
   }
 }
 
+/// Sort modes for ordering children in the file tree.
+/// Dirs always come before files, the mode orders entries of the same type.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SortMode {
+  #[default]
+  Name,
+  Size,
+  Tokens,
+  Modified,
+  SelectedFirst,
+}
+
+impl SortMode {
+  /// Returns the display name for sort mode.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      SortMode::Name => "Name",
+      SortMode::Size => "Size",
+      SortMode::Tokens => "Tokens",
+      SortMode::Modified => "Modified",
+      SortMode::SelectedFirst => "Selected First",
+    }
+  }
+
+  /// Returns the next sort mode (for cycling with a single key).
+  pub fn next(&self) -> Self {
+    match self {
+      SortMode::Name => SortMode::Size,
+      SortMode::Size => SortMode::Tokens,
+      SortMode::Tokens => SortMode::Modified,
+      SortMode::Modified => SortMode::SelectedFirst,
+      SortMode::SelectedFirst => SortMode::Name,
+    }
+  }
+}
+
+/// Severity of a status message, decides its style and how long it stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusLevel {
+  /// Neutral feedback (toggles, navigation)
+  #[default]
+  Info,
+  /// Work in progress (token calculation, backend runs, downloads)
+  Progress,
+  /// Completed work
+  Success,
+  /// Something worked partially or needs attention
+  Warning,
+  /// Something failed
+  Error,
+}
+
+impl StatusLevel {
+  /// Returns the display name for the level (used in the message log).
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      StatusLevel::Info => "info",
+      StatusLevel::Progress => "progress",
+      StatusLevel::Success => "success",
+      StatusLevel::Warning => "warning",
+      StatusLevel::Error => "error",
+    }
+  }
+}
+
+/// Derived selection state of a node.
+/// Files are either selected or not, directories aggregate their descendant files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionState {
+  /// Nothing selected
+  None,
+  /// Some but not all descendant files selected (directories only)
+  Partial,
+  /// Everything selected
+  All,
+}
+
+/// What a file holds, sniffed from its first bytes while scanning unless its extension always holds text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileContent {
+  /// Text in an encoding siff can decode (also used for directories)
+  #[default]
+  Text,
+  /// Binary data, not selected unless forced
+  Binary,
+  /// Couldn't be read (permissions, broken link), not selected unless forced
+  Unreadable,
+}
+
+impl FileContent {
+  /// Returns true if the content can be packed as text.
+  pub fn is_text(&self) -> bool {
+    *self == FileContent::Text
+  }
+
+  /// Returns the name shown next to files that aren't text.
+  pub fn display_name(&self) -> &'static str {
+    match self {
+      FileContent::Text => "text",
+      FileContent::Binary => "binary",
+      FileContent::Unreadable => "unreadable",
+    }
+  }
+}
+
 /// Represents a single file or directory in our file tree.
 /// Holds core data for the file tree.
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +202,24 @@ pub struct FileNode {
   pub children: Vec<PathBuf>,
   /// How deep node is in the tree (0 = root level)
   pub depth: usize,
+  /// For directories: number of descendant files currently selected
+  pub selected_files: usize,
+  /// For directories: total number of descendant files
+  pub total_files: usize,
+  /// For directories: descendant files that are binary or unreadable (skipped when the dir is selected)
+  pub non_text_files: usize,
+  /// For files: whether the content is text, binary or unreadable
+  pub content: FileContent,
+  /// Size in bytes (for directories: total size of descendant files)
+  pub size: u64,
+  /// Last modification time (for directories: most recent among descendant files)
+  pub modified: Option<SystemTime>,
+  /// For symlinks: where the link points, as written in the link
+  pub link_target: Option<PathBuf>,
+  /// Real location of symlinks and of nodes reached through a followed symlink (none if the path is real)
+  pub real_path: Option<PathBuf>,
+  /// Whether a symlink in the tree points at this node
+  pub is_link_target: bool,
 }
 
 /// Configuration options for repomix execution.
@@ -88,6 +240,83 @@ pub struct RepomixOptions {
   pub backend: Backend,
 }
 
+/// Limit on the size of yek's output.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum YekLimit {
+  /// Yek's own default (10MB)
+  #[default]
+  Default,
+  /// Maximum output size in bytes
+  Bytes(u64),
+  /// Maximum output size in tokens (yek's token mode)
+  Tokens(u64),
+}
+
+impl YekLimit {
+  /// Limits cycled through from the UI.
+  const PRESETS: [YekLimit; 6] = [
+    YekLimit::Default,
+    YekLimit::Tokens(32_000),
+    YekLimit::Tokens(128_000),
+    YekLimit::Tokens(200_000),
+    YekLimit::Bytes(1_000_000),
+    YekLimit::Bytes(10_000_000),
+  ];
+
+  /// Returns the next preset, custom limits from the config go back to the first one.
+  pub fn next(&self) -> Self {
+    let index = Self::PRESETS.iter().position(|limit| limit == self).map_or(0, |index| index + 1);
+    Self::PRESETS[index % Self::PRESETS.len()]
+  }
+
+  /// Returns the display name for the limit.
+  pub fn display_name(&self) -> String {
+    match self {
+      YekLimit::Default => "default".to_string(),
+      YekLimit::Bytes(bytes) => format!("{} bytes", format_si(*bytes)),
+      YekLimit::Tokens(tokens) => format!("{} tokens", format_si(*tokens)),
+    }
+  }
+}
+
+/// Formats a number with a decimal K/M suffix when it divides evenly (as yek parses sizes).
+pub fn format_si(value: u64) -> String {
+  if value >= 1_000_000 && value.is_multiple_of(1_000_000) {
+    format!("{}M", value / 1_000_000)
+  } else if value >= 1_000 && value.is_multiple_of(1_000) {
+    format!("{}K", value / 1_000)
+  } else {
+    value.to_string()
+  }
+}
+
+/// Yek priority rule, files matching the pattern (a regex on the relative path) are ordered by score.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct YekPriorityRule {
+  /// Regex matched against the relative file path
+  pub pattern: String,
+  /// Higher scores are placed later in the output (closer to the prompt)
+  pub score: i32,
+}
+
+/// Configuration options for yek execution.
+/// These mirror the settings that yek accepts on the command line and in its config file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct YekOptions {
+  /// Maximum output size in bytes or tokens
+  pub limit: YekLimit,
+  /// Whether to put a directory tree at the top of the output
+  pub tree_header: bool,
+  /// Template for each file, must contain FILE_PATH and FILE_CONTENT (yek's default if not set)
+  pub output_template: Option<String>,
+  /// Rules deciding the order of files in the output
+  pub priority_rules: Vec<YekPriorityRule>,
+  /// Glob patterns yek skips even when selected
+  pub ignore_patterns: Vec<String>,
+}
+
 /// Represents which UI component currently has focus.
 /// TODO: remove old tab ui compoents
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -100,8 +329,10 @@ pub enum Focus {
 /// central state that gets passed around to different components.
 #[derive(Debug)]
 pub struct AppState {
-  /// The root directory being scanned
+  /// Key of the top node in the tree (the root, or a virtual root above several roots)
   pub root_path: PathBuf,
+  /// Scanned roots, top-level nodes when there are several
+  pub roots: SessionRoots,
   /// Hierarchical file tree structure for navigation
   pub file_tree: HashMap<PathBuf, FileNode>,
   /// Flat list of currently visible paths in the tree view
@@ -110,21 +341,60 @@ pub struct AppState {
   pub selected_index: usize,
   /// Configuration options for repomix execution
   pub repomix_options: RepomixOptions,
+  /// Configuration options for yek execution
+  pub yek_options: YekOptions,
+  /// Backends defined in the config file
+  pub custom_backends: Vec<CustomBackendConfig>,
+  /// Files the last secret scan flagged, with what was found
+  pub secret_files: HashMap<PathBuf, String>,
   /// Individual token counts for each file and directory
   pub individual_token_counts: HashMap<PathBuf, Option<usize>>,
-  /// Current status message to display to user
-  pub status_message: String,
+  /// Notification history, the latest one is shown as a toast
+  pub notifications: NotificationLog,
+  /// Whether the message log panel is shown
+  pub show_log: bool,
+  /// Lines scrolled from the top of the message log
+  pub log_scroll: usize,
   /// Whether the app is currently processing files
   pub is_processing: bool,
   /// Total token count for selected files
   pub token_count: usize,
   /// Which UI component currently has focus
   pub focus: Focus,
+  /// Undo/redo history for selection changes
+  pub selection_history: SelectionHistory,
+  /// Pattern selection prompt, if open
+  pub pattern_prompt: Option<PatternPrompt>,
+  /// Token counts of every file counted so far, selected or not
+  pub file_token_cache: HashMap<PathBuf, usize>,
+  /// How children are ordered in the tree view and generated file tree
+  pub sort_mode: SortMode,
+  /// Whether the background directory scan is still running
+  pub is_scanning: bool,
+  /// Whether dotfiles are shown in the tree
+  pub show_hidden: bool,
+  /// Active keybindings (used for input and generated hints)
+  pub keymap: Keymap,
+  /// Active color theme
+  pub theme: Theme,
+  /// Whether the help overlay is shown
+  pub show_help: bool,
+  /// Lines scrolled from the top of the help overlay
+  pub help_scroll: usize,
+  /// Command palette, if it's open
+  pub command_palette: Option<CommandPalette>,
 }
 
-/// Result type for file scanning operations.
-/// Handles errors gracefully when reading the file system.
-pub type ScanResult = anyhow::Result<HashMap<PathBuf, FileNode>>;
+/// Events streamed by the background directory scanner.
+#[derive(Debug)]
+pub enum ScanEvent {
+  /// A batch of newly discovered nodes (parents always arrive before their children)
+  Nodes(Vec<FileNode>),
+  /// A problem that didn't stop the scan, like an invalid `.siff.json`
+  Warning(String),
+  /// Scan finished, with the number of dirs that couldn't be read
+  Finished { unreadable_dirs: usize },
+}
 
 impl Default for RepomixOptions {
   /// Provides sensible default values for repomix options.
@@ -154,9 +424,39 @@ impl FileNode {
       is_expanded: false,
       children: Vec::new(),
       depth,
+      selected_files: 0,
+      total_files: 0,
+      non_text_files: 0,
+      content: FileContent::Text,
+      size: 0,
+      modified: None,
+      link_target: None,
+      real_path: None,
+      is_link_target: false,
+    }
+  }
+
+  /// Returns the derived selection state of the node.
+  /// Directories are fully selected only when every descendant text file is selected.
+  pub fn selection_state(&self) -> SelectionState {
+    if !self.is_directory {
+      return if self.is_selected { SelectionState::All } else { SelectionState::None };
+    }
+
+    if self.selected_files == 0 {
+      SelectionState::None
+    } else if self.selected_files >= self.total_files.saturating_sub(self.non_text_files) {
+      SelectionState::All
+    } else {
+      SelectionState::Partial
     }
   }
 
+  /// Returns true if the node or any of its descendants is selected.
+  pub fn has_selection(&self) -> bool {
+    self.selection_state() != SelectionState::None
+  }
+
   /// Toggles the expanded state of a directory.
   /// Only applies to directories, files cannot be expanded.
   pub fn toggle_expansion(&mut self) {
@@ -173,12 +473,22 @@ pub struct BackendRequest {
   pub backend: Backend,
   /// Repomix options for configuration
   pub repomix_options: RepomixOptions,
+  /// Yek options for configuration
+  pub yek_options: YekOptions,
   /// List of selected files to process
   pub selected_files: Vec<PathBuf>,
-  /// Root directory path
-  pub root_path: PathBuf,
-  /// Complete file tree for generating directory structure
-  pub file_tree: HashMap<PathBuf, FileNode>,
+  /// Scanned roots, file paths are packed relative to them
+  pub roots: SessionRoots,
+  /// Pre-rendered directory structure (only when the file tree option is enabled)
+  pub file_tree_text: Option<String>,
+  /// Verified yek binary (only for the yek backend)
+  pub yek_binary: Option<PathBuf>,
+  /// Command to run (only for custom backends)
+  pub custom_backend: Option<CustomBackendConfig>,
+  /// How selected files with secrets are handled
+  pub secrets: SecretsConfig,
+  /// Rules masking text in the packed files
+  pub redaction_rules: Vec<RedactionRule>,
   /// Unique request id for cancellation
   pub request_id: u64,
   /// Cancellation token to immediately stop the process
@@ -196,6 +506,124 @@ pub struct BackendResult {
   pub output_file: Option<PathBuf>,
   /// Optional error message if failed
   pub error: Option<String>,
+  /// Warnings collected during the run (skipped files, large file counts)
+  pub warnings: Vec<String>,
+  /// Everything the backend wrote to stderr
+  pub stderr: String,
+  /// Selected files the secret scan flagged
+  pub flagged: Vec<FlaggedFile>,
+  /// Files changed by redaction rules, with the substitutions made
+  pub redacted: Vec<RedactedFile>,
+  /// Packed content, kept for the API even if copying it failed
+  pub content: Option<String>,
   /// Request id that result corresponds to
   pub request_id: u64,
+  /// Backend that produced the result
+  pub backend: Backend,
+}
+
+impl BackendResult {
+  /// Creates a result for a successful run.
+  pub fn succeeded(request_id: u64, backend: Backend, output: BackendOutput, output_file: Option<PathBuf>) -> Self {
+    Self {
+      success: true,
+      message: output.message,
+      output_file,
+      error: None,
+      warnings: output.warnings,
+      stderr: output.stderr,
+      flagged: Vec::new(),
+      redacted: Vec::new(),
+      content: None,
+      request_id,
+      backend,
+    }
+  }
+
+  /// Creates a result for a failed run.
+  pub fn failed(request_id: u64, backend: Backend, error: String) -> Self {
+    Self {
+      success: false,
+      message: String::new(),
+      output_file: None,
+      error: Some(error),
+      warnings: Vec::new(),
+      stderr: String::new(),
+      flagged: Vec::new(),
+      redacted: Vec::new(),
+      content: None,
+      request_id,
+      backend,
+    }
+  }
+}
+
+/// Download status for backends installed on first use (repomix, yek).
+#[derive(Debug, Clone)]
+pub enum DownloadStatus {
+  /// Backend is ready to use
+  Ready,
+  /// Currently downloading/installing, with the current step
+  Downloading(String),
+  /// Download failed
+  Failed(String),
+  /// Not started yet
+  NotStarted,
+}
+
+/// Progress of a running backend, streamed to the UI while the process runs.
+#[derive(Debug, Clone)]
+pub struct BackendProgress {
+  /// Request id the progress belongs to
+  pub request_id: u64,
+  /// Backend that's running
+  pub backend: Backend,
+  /// What the backend is doing right now
+  pub stage: String,
+  /// Files written to the output so far (only when the output shows it)
+  pub files_processed: Option<usize>,
+  /// Number of files sent to the backend
+  pub files_total: usize,
+  /// Bytes of output produced so far
+  pub bytes: u64,
+  /// Time since the run started
+  pub elapsed: Duration,
+}
+
+impl BackendProgress {
+  /// Returns a one line summary for the status toast.
+  pub fn summary(&self) -> String {
+    let files = match self.files_processed {
+      Some(processed) => format!("{}/{} files", processed.min(self.files_total), self.files_total),
+      None => format!("{} files", self.files_total),
+    };
+    let bytes = if self.bytes >= 1024 * 1024 {
+      format!("{:.1} MB", self.bytes as f64 / (1024.0 * 1024.0))
+    } else {
+      format!("{:.1} KB", self.bytes as f64 / 1024.0)
+    };
+    format!("{} • {} • {} • {:.1}s", self.stage, files, bytes, self.elapsed.as_secs_f64())
+  }
+}
+
+/// Events sent from the backend task to the UI.
+#[derive(Debug, Clone)]
+pub enum BackendEvent {
+  /// The running backend made progress
+  Progress(BackendProgress),
+  /// The run finished, failed or was cancelled
+  Finished(BackendResult),
+}
+
+/// Output of a successful backend run.
+#[derive(Debug, Clone, Default)]
+pub struct BackendOutput {
+  /// Summary message shown to the user
+  pub message: String,
+  /// Warnings collected while preparing the run (skipped files, large file counts)
+  pub warnings: Vec<String>,
+  /// Everything the backend wrote to stderr
+  pub stderr: String,
+  /// Packed content, copied to the clipboard by the UI
+  pub content: String,
 }
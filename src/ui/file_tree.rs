@@ -1,7 +1,8 @@
-use crate::types::{AppState, FileNode};
+use crate::keymap::Action;
+use crate::theme::Theme;
+use crate::types::{AppState, FileContent, FileNode, SelectionState, StatusLevel};
 use ratatui::{
   layout::{Constraint, Direction, Layout, Rect},
-  style::{Color, Style},
   text::{Line, Span},
   widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
   Frame,
@@ -11,99 +12,156 @@ use std::path::PathBuf;
 
 /// Renders the combined file tree and options component.
 /// which displays the configuration at top and file tree below.
-pub fn render_file_tree_with_options(terminal_frame: &mut Frame, terminal_frame_area: Rect, app_state: &AppState, file_tree_list_state: &mut ListState, token_count: usize, status_message: &str) {
-  match app_state.repomix_options.backend {
-    crate::types::Backend::Repomix => {
-      // for repomix backend, show both config and file tree
-      let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-          // config box
-          Constraint::Length(3),
-          // file tree box
-          Constraint::Min(0),
-        ])
-        .split(terminal_frame_area);
-
-      // render config section
-      render_configuration_section(terminal_frame, chunks[0], app_state);
-
-      // render file tree section with hints and status
-      render_file_tree_section_with_hints(terminal_frame, chunks[1], app_state, file_tree_list_state, token_count, status_message);
-    }
-    crate::types::Backend::Yek => {
-      // for yek backend, show only file tree
-      render_file_tree_section_with_hints(terminal_frame, terminal_frame_area, app_state, file_tree_list_state, token_count, status_message);
-    }
+pub fn render_file_tree_with_options(terminal_frame: &mut Frame, terminal_frame_area: Rect, app_state: &AppState, file_tree_list_state: &mut ListState, token_count: usize) {
+  // show both config and file tree, the config bar follows the backend
+  let chunks = Layout::default()
+    .direction(Direction::Vertical)
+    .constraints([
+      // config box
+      Constraint::Length(3),
+      // file tree box
+      Constraint::Min(0),
+    ])
+    .split(terminal_frame_area);
+
+  // render config section
+  match &app_state.repomix_options.backend {
+    crate::types::Backend::Repomix => render_configuration_section(terminal_frame, chunks[0], app_state),
+    crate::types::Backend::Yek => render_yek_configuration_section(terminal_frame, chunks[0], app_state),
+    crate::types::Backend::Custom(name) => render_custom_configuration_section(terminal_frame, chunks[0], app_state, name),
   }
+
+  // render file tree section with hints and status
+  render_file_tree_section_with_hints(terminal_frame, chunks[1], app_state, file_tree_list_state, token_count);
 }
 
 /// Renders config section at the top.
 fn render_configuration_section(frame: &mut Frame, area: Rect, app_state: &AppState) {
   // create options content (only for repomix)
   let options = &app_state.repomix_options;
+  let theme = &app_state.theme;
 
-  // create colored spans for options
+  // create styled spans for options
   let compress_symbol = if options.compress { "●" } else { "○" };
-  let compress_color = if options.compress { Color::Green } else { Color::Gray };
+  let compress_style = if options.compress { theme.selected } else { theme.muted };
 
   let remove_comments_symbol = if options.remove_comments { "●" } else { "○" };
-  let remove_comments_color = if options.remove_comments { Color::Green } else { Color::Gray };
+  let remove_comments_style = if options.remove_comments { theme.selected } else { theme.muted };
 
   let file_tree_symbol = if options.file_tree { "●" } else { "○" };
-  let file_tree_color = if options.file_tree { Color::Green } else { Color::Gray };
+  let file_tree_style = if options.file_tree { theme.selected } else { theme.muted };
+
+  // show the bound key next to each option (nothing if unbound)
+  let key = |action: Action| app_state.keymap.first_key(action).map(|key| format!(" ({})", key)).unwrap_or_default();
 
   let options_content = vec![
     Span::raw("Options: "),
-    Span::styled(file_tree_symbol, Style::default().fg(file_tree_color)),
-    Span::raw(" File Tree (t) │ "),
-    Span::styled(compress_symbol, Style::default().fg(compress_color)),
-    Span::raw(" Compress (c) │ "),
-    Span::styled(remove_comments_symbol, Style::default().fg(remove_comments_color)),
-    Span::raw(" Remove Comments (m) │ Format: "),
+    Span::styled(file_tree_symbol, file_tree_style),
+    Span::raw(format!(" File Tree{} │ ", key(Action::ToggleFileTree))),
+    Span::styled(compress_symbol, compress_style),
+    Span::raw(format!(" Compress{} │ ", key(Action::ToggleCompress))),
+    Span::styled(remove_comments_symbol, remove_comments_style),
+    Span::raw(format!(" Remove Comments{} │ Format: ", key(Action::ToggleRemoveComments))),
     Span::styled(
       options.output_format.display_name(),
       // will display format (XML, Markdown, Plain Text)
-      Style::default().fg(Color::Green),
+      theme.selected,
     ),
-    Span::raw(" (f)"),
+    Span::raw(key(Action::CycleOutputFormat)),
   ];
 
   // style config block
-  let config_style = Style::default().fg(Color::Green);
+  let config_style = theme.accent;
 
   // create config block
   let config_block = Block::default().borders(Borders::ALL).title("Configuration").style(config_style);
 
   // create options paragraph
-  let options_paragraph = Paragraph::new(Line::from(options_content)).block(config_block).style(Style::default().fg(Color::White));
+  let options_paragraph = Paragraph::new(Line::from(options_content)).block(config_block).style(theme.text);
+
+  frame.render_widget(options_paragraph, area);
+}
+
+/// Renders the yek config section at the top.
+fn render_yek_configuration_section(frame: &mut Frame, area: Rect, app_state: &AppState) {
+  let options = &app_state.yek_options;
+  let theme = &app_state.theme;
+
+  let tree_header_symbol = if options.tree_header { "●" } else { "○" };
+  let tree_header_style = if options.tree_header { theme.selected } else { theme.muted };
+
+  // show the bound key next to each option (nothing if unbound)
+  let key = |action: Action| app_state.keymap.first_key(action).map(|key| format!(" ({})", key)).unwrap_or_default();
+
+  // template and rules are only set in the config file
+  let template = if options.output_template.is_some() { "custom" } else { "default" };
+  let options_content = vec![
+    Span::raw("Options: "),
+    Span::styled(tree_header_symbol, tree_header_style),
+    Span::raw(format!(" Tree Header{} │ Limit: ", key(Action::ToggleFileTree))),
+    Span::styled(options.limit.display_name(), theme.selected),
+    Span::raw(format!("{} │ Template: ", key(Action::CycleSizeLimit))),
+    Span::styled(template, theme.selected),
+    Span::raw(format!(" │ Rules: {} priority, {} ignore", options.priority_rules.len(), options.ignore_patterns.len())),
+  ];
+
+  let config_block = Block::default().borders(Borders::ALL).title("Configuration").style(theme.accent);
+  let options_paragraph = Paragraph::new(Line::from(options_content)).block(config_block).style(theme.text);
+
+  frame.render_widget(options_paragraph, area);
+}
+
+/// Renders the command of a custom backend at the top, its options are only set in the config file.
+fn render_custom_configuration_section(frame: &mut Frame, area: Rect, app_state: &AppState, name: &str) {
+  let theme = &app_state.theme;
+
+  let options_content = match crate::custom_backend::find(&app_state.custom_backends, name) {
+    Some(custom_backend) => vec![
+      Span::raw("Command: "),
+      Span::styled(custom_backend.command.join(" "), theme.selected),
+      Span::raw(" │ Files: "),
+      Span::styled(custom_backend.files.display_name(), theme.selected),
+    ],
+    None => vec![Span::styled(format!("{} is not defined in the config", name), theme.status_error)],
+  };
+
+  let config_block = Block::default().borders(Borders::ALL).title("Configuration").style(theme.accent);
+  let options_paragraph = Paragraph::new(Line::from(options_content)).block(config_block).style(theme.text);
 
   frame.render_widget(options_paragraph, area);
 }
 
 /// Renders file tree section with hints and status.
-fn render_file_tree_section_with_hints(terminal_frame: &mut Frame, terminal_frame_area: Rect, app_state: &AppState, file_tree_list_state: &mut ListState, token_count: usize, status_message: &str) {
-  // get selected count
-  let selected_count = app_state.file_tree.values().filter(|node| node.is_selected && !node.is_directory).count();
+fn render_file_tree_section_with_hints(terminal_frame: &mut Frame, terminal_frame_area: Rect, app_state: &AppState, file_tree_list_state: &mut ListState, token_count: usize) {
+  // get selected count (maintained incrementally on the root node)
+  let selected_count = app_state.file_tree.get(&app_state.root_path).map(|node| node.selected_files).unwrap_or(0);
 
   // get directory name from the root path
-  let root_name = app_state.root_path.file_name().and_then(|name| name.to_str()).unwrap_or(".");
+  let root_name = match app_state.roots.len() {
+    1 => app_state.root_path.file_name().and_then(|name| name.to_str()).unwrap_or(".").to_string(),
+    roots => format!("{} roots", roots),
+  };
 
   // create title text based on backend
-  let title_text = match app_state.repomix_options.backend {
+  let title_text = match &app_state.repomix_options.backend {
     crate::types::Backend::Repomix => "File Tree (repomix)".to_string(),
     crate::types::Backend::Yek => "File Tree (yek)".to_string(),
+    crate::types::Backend::Custom(name) => format!("File Tree ({})", name),
   };
 
   // style title based on whether component has focus
-  let title_style = Style::default().fg(Color::Green);
+  let theme = &app_state.theme;
+  let title_style = theme.accent;
 
-  // determine layout constraints based on status message
-  let constraints = if !status_message.is_empty() {
+  // latest notification, kept visible while a backend is running
+  let toast = app_state.notifications.toast(app_state.is_processing);
+
+  // determine layout constraints based on the toast
+  let constraints = if toast.is_some() {
     vec![
       Constraint::Length(1), // root directory and selected count
       Constraint::Min(0),    // file list
-      Constraint::Length(3), // status message with padding
+      Constraint::Length(3), // toast with padding
       Constraint::Length(2), // nav hints
     ]
   } else {
@@ -123,8 +181,14 @@ fn render_file_tree_section_with_hints(terminal_frame: &mut Frame, terminal_fram
   terminal_frame.render_widget(file_tree_block, terminal_frame_area);
 
   // render root directory name and selected count
-  let info_text = format!("{}  •  Selected: {} items", root_name, selected_count);
-  let info_paragraph = Paragraph::new(info_text).style(Style::default().fg(Color::Cyan));
+  let mut info_text = format!("{}  •  Selected: {} items  •  Sort: {}", root_name, selected_count, app_state.sort_mode.display_name());
+
+  // show scan progress while the background scan is still streaming in
+  if app_state.is_scanning {
+    let scanned_files = app_state.file_tree.get(&app_state.root_path).map(|node| node.total_files).unwrap_or(0);
+    info_text.push_str(&format!("  •  Scanning… {} files", scanned_files));
+  }
+  let info_paragraph = Paragraph::new(info_text).style(theme.info);
 
   // create layout for inner content
   let inner_chunks = Layout::default().direction(Direction::Vertical).constraints(constraints).split(inner_area);
@@ -142,78 +206,54 @@ fn render_file_tree_section_with_hints(terminal_frame: &mut Frame, terminal_fram
 
   // render token count in top-right with right alignment
   let token_text = format!("Tokens: {}", crate::token_counter::format_token_count(token_count));
-  let token_paragraph = Paragraph::new(token_text).style(Style::default().fg(Color::Yellow)).alignment(ratatui::layout::Alignment::Right);
+  let token_paragraph = Paragraph::new(token_text).style(theme.tokens).alignment(ratatui::layout::Alignment::Right);
   terminal_frame.render_widget(token_paragraph, top_chunks[1]);
 
   // render the file list
   render_file_list_inner(terminal_frame, inner_chunks[1], app_state, file_tree_list_state);
 
-  // render status message if present (above keyboard nav menu)
-  let hints_index = if !status_message.is_empty() {
-    // render status message with appropriate styling and padding
-    let status_style = if status_message.contains("Success") || status_message.contains("Copied to clipboard") {
-      Style::default().fg(Color::Green)
-    } else if status_message.contains("Error") || status_message.contains("Failed") {
-      Style::default().fg(Color::Red)
-    } else if status_message.contains("Warning") {
-      Style::default().fg(Color::Yellow)
-    } else if status_message.contains("Running") || status_message.contains("Processing...") {
-      Style::default().fg(Color::Cyan)
-    } else {
-      Style::default().fg(Color::White)
+  // render toast if present (above keyboard nav menu)
+  let hints_index = if let Some(toast) = toast {
+    // point to the log when there's more output than fits in the toast
+    let log_hint = match (&toast.details, app_state.keymap.first_key(Action::ShowLog)) {
+      (Some(_), Some(key)) => format!(" ({} for details)", key),
+      _ => String::new(),
+    };
+    // show how to stop a running backend
+    let cancel_hint = match app_state.keymap.first_key(Action::CancelRun) {
+      Some(key) if app_state.is_processing && toast.level == StatusLevel::Progress => format!(" ({} to cancel)", key),
+      _ => String::new(),
     };
 
-    // create status content with padding
-    let status_content = format!("\n{}", status_message);
-    let status_paragraph = Paragraph::new(status_content).style(status_style);
-    terminal_frame.render_widget(status_paragraph, inner_chunks[2]);
+    // render toast styled by its level, with padding
+    let toast_line = Line::from(vec![
+      Span::styled(format!("[{}] ", toast.source.display_name()), theme.muted),
+      Span::styled(format!("{}{}{}", toast.message, log_hint, cancel_hint), theme.status(toast.level)),
+    ]);
+    let toast_paragraph = Paragraph::new(vec![Line::raw(""), toast_line]);
+    terminal_frame.render_widget(toast_paragraph, inner_chunks[2]);
 
-    3 // nav hints are at index 3 when status is present
+    3 // nav hints are at index 3 when a toast is shown
   } else {
-    2 // nav hints are at index 2 when no status
-  };
-
-  // render nav hints at bottom
-  let hints_text = match app_state.repomix_options.backend {
-    crate::types::Backend::Repomix => "↑/↓ navigate • ←/→ collapse/expand dirs • Space select files • E expand all • C collapse all • A select all • U unselect all • r run • q quit",
-    crate::types::Backend::Yek => "↑/↓ navigate • ←/→ collapse/expand dirs • Space select files • E expand all • C collapse all • A select all • U unselect all • r run • q quit",
+    2 // nav hints are at index 2 when no toast
   };
-  let hints_paragraph = Paragraph::new(hints_text).style(Style::default().fg(Color::Yellow));
-
-  terminal_frame.render_widget(hints_paragraph, inner_chunks[hints_index]);
-}
 
-/// Builds a map of directories that have selected descendants.
-fn build_directories_with_descendants_map(file_tree: &HashMap<PathBuf, FileNode>) -> HashMap<PathBuf, bool> {
-  let mut dir_map = HashMap::new();
-
-  // initialize all directories as false
-  for (path, node) in file_tree {
-    if node.is_directory {
-      dir_map.insert(path.clone(), false);
-    }
+  // render pattern prompt in place of the nav hints while it's open
+  if let Some(prompt) = &app_state.pattern_prompt {
+    super::prompt::render_pattern_prompt(terminal_frame, inner_chunks[hints_index], prompt, &app_state.file_token_cache, theme);
+    return;
   }
 
-  // mark directories that have selected descendants
-  for (path, node) in file_tree {
-    if node.is_selected {
-      // mark all parent directories as having selected descendants
-      let mut current_path = path.parent();
-      while let Some(parent_path) = current_path {
-        if let Some(has_descendants) = dir_map.get_mut(parent_path) {
-          *has_descendants = true;
-        }
-        current_path = parent_path.parent();
-      }
-    }
-  }
+  // render nav hints at bottom, generated from the active keymap
+  let hints_text = app_state.keymap.hints(&app_state.repomix_options.backend);
+  let hints_paragraph = Paragraph::new(hints_text).style(theme.hint);
 
-  dir_map
+  terminal_frame.render_widget(hints_paragraph, inner_chunks[hints_index]);
 }
 
 /// Creates a formatted list item for a single file or directory.
 /// Handles indentation, icons, selection indicators, and token counts with color coding.
-fn create_list_item(path: &PathBuf, file_tree: &HashMap<PathBuf, FileNode>, individual_token_counts: &HashMap<PathBuf, Option<usize>>, dir_descendants_map: &HashMap<PathBuf, bool>, is_highlighted: bool) -> ListItem<'static> {
+fn create_list_item(path: &PathBuf, file_tree: &HashMap<PathBuf, FileNode>, individual_token_counts: &HashMap<PathBuf, Option<usize>>, theme: &Theme, is_highlighted: bool, has_secrets: bool) -> ListItem<'static> {
   // get node from file tree
   let node = file_tree.get(path).unwrap();
 
@@ -223,44 +263,51 @@ fn create_list_item(path: &PathBuf, file_tree: &HashMap<PathBuf, FileNode>, indi
   // create indentation based on adjusted depth (2 spaces per level)
   let indent = "  ".repeat(display_depth);
 
-  // choose appropriate icon and color based on file type and state
+  // derived selection state (dirs aggregate their descendant files)
+  let selection_state = node.selection_state();
+  let selection_icon = match (selection_state, node.content) {
+    (SelectionState::All, _) => "●",
+    (SelectionState::Partial, _) => "◐",
+    // files that selecting skips
+    (SelectionState::None, FileContent::Binary) => "⊘",
+    (SelectionState::None, FileContent::Unreadable) => "✗",
+    (SelectionState::None, FileContent::Text) => "○",
+  };
+
+  // choose appropriate icon and style based on file type and state
   let (icon, base_style) = if node.is_directory {
     let expansion_icon = if node.is_expanded { "[-]" } else { "[+]" };
 
-    // determine directory color based on selection state
-    let color = if is_highlighted {
-      // when highlighted (blue background), use white text for contrast
-      Color::White
-    } else if node.is_selected {
-      Color::Green // fully selected directory
-    } else if display_depth > 0 && *dir_descendants_map.get(path).unwrap_or(&false) {
-      Color::Yellow // directory with some selected children
+    // determine directory style based on selection state
+    let style = if is_highlighted {
+      // when highlighted, use the highlight text style for contrast
+      theme.highlighted_text
     } else {
-      Color::Cyan // unselected directory
+      match selection_state {
+        SelectionState::All => theme.selected,    // fully selected directory
+        SelectionState::Partial => theme.partial, // directory with some selected children
+        SelectionState::None => theme.directory,  // unselected directory
+      }
     };
 
-    (expansion_icon, Style::default().fg(color))
+    (format!("{} {}", expansion_icon, selection_icon), style)
   } else {
-    let selection_icon = if node.is_selected { "●" } else { "○" };
-    let color = if is_highlighted {
-      // when highlighted (blue background), use white text for contrast
-      Color::White
+    let style = if is_highlighted {
+      // when highlighted, use the highlight text style for contrast
+      theme.highlighted_text
     } else if node.is_selected {
-      Color::Green
+      theme.selected
+    } else if !node.content.is_text() {
+      theme.muted
     } else {
-      Color::White
+      theme.text
     };
-    (selection_icon, Style::default().fg(color))
+    (selection_icon.to_string(), style)
   };
 
   // get token count for item
   // show for selected items or directories with selected descendants
-  let should_show_tokens = if node.is_directory {
-    // show token counts for all directories that are selected or have selected descendants
-    node.is_selected || *dir_descendants_map.get(path).unwrap_or(&false)
-  } else {
-    node.is_selected
-  };
+  let should_show_tokens = node.has_selection();
 
   let token_count_opt = if should_show_tokens { individual_token_counts.get(path).and_then(|opt| *opt) } else { None };
 
@@ -274,40 +321,43 @@ fn create_list_item(path: &PathBuf, file_tree: &HashMap<PathBuf, FileNode>, indi
   if should_show_tokens {
     if let Some(token_count) = token_count_opt {
       // show actual token count (even if 0)
-      let token_color = if is_highlighted {
-        // when highlighted, use light blue for token counts contrast
-        Color::LightBlue
+      let token_style = if is_highlighted {
+        // when highlighted, use the highlight token style for contrast
+        theme.highlighted_tokens
       } else {
-        get_token_count_color(token_count)
+        theme.token_count(token_count)
       };
       let token_text = format!(" ({})", crate::token_counter::format_token_count(token_count));
-      spans.push(Span::styled(token_text, Style::default().fg(token_color)));
+      spans.push(Span::styled(token_text, token_style));
     }
   }
 
-  ListItem::new(Line::from(spans))
-}
+  // symlinks show where they point, their targets that other paths reach them
+  if let Some(link_target) = &node.link_target {
+    spans.push(Span::styled(format!(" → {}", link_target.display()), if is_highlighted { theme.highlighted_text } else { theme.hint }));
+  } else if node.is_link_target {
+    spans.push(Span::styled(" ← linked", if is_highlighted { theme.highlighted_text } else { theme.hint }));
+  }
 
-/// Determines the color for token count display based on a three-tier system.
-/// Provides visual feedback about token density.
-fn get_token_count_color(token_count: usize) -> Color {
-  if token_count < 1_000 {
-    Color::Green // low token count - green
-  } else if token_count < 10_000 {
-    Color::Yellow // medium token count - yellow
-  } else {
-    Color::Red // high token count - red
+  // binary and unreadable files are only packed when forced
+  if !node.content.is_text() {
+    spans.push(Span::styled(format!(" {}", node.content.display_name()), if is_highlighted { theme.highlighted_text } else { theme.muted }));
+  }
+
+  // flag files the last secret scan found something in
+  if has_secrets {
+    spans.push(Span::styled(" ⚠ secrets", if is_highlighted { theme.highlighted_text } else { theme.status_warning }));
   }
-}
 
-/// Handles keyboard input for file tree.
-/// Returns true if input was handled, false otherwise.
-pub fn handle_file_tree_input(app_state: &mut AppState, key: crossterm::event::KeyEvent) -> bool {
-  use crossterm::event::KeyCode;
+  ListItem::new(Line::from(spans))
+}
 
-  match key.code {
-    // nav keys with wrapping (up/down or k/j)
-    KeyCode::Up | KeyCode::Char('k') => {
+/// Handles a navigation or selection action for the file tree.
+/// Returns true if the action was handled, false otherwise.
+pub fn handle_file_tree_action(app_state: &mut AppState, action: Action) -> bool {
+  match action {
+    // nav with wrapping
+    Action::MoveUp => {
       if app_state.visible_paths.is_empty() {
         // no files to navigate
       } else if app_state.selected_index == 0 {
@@ -318,7 +368,7 @@ pub fn handle_file_tree_input(app_state: &mut AppState, key: crossterm::event::K
       }
       true
     }
-    KeyCode::Down | KeyCode::Char('j') => {
+    Action::MoveDown => {
       if app_state.visible_paths.is_empty() {
         // no files to navigate
       } else if app_state.selected_index >= app_state.visible_paths.len() - 1 {
@@ -330,16 +380,8 @@ pub fn handle_file_tree_input(app_state: &mut AppState, key: crossterm::event::K
       true
     }
 
-    // selection (space only, removed enter)
-    KeyCode::Char(' ') => {
-      if let Some(selected_path) = app_state.visible_paths.get(app_state.selected_index) {
-        handle_selection_key(app_state, selected_path.clone());
-      }
-      true
-    }
-
-    // expansion/collapse (h/l and left/right arrows)
-    KeyCode::Char('h') | KeyCode::Left => {
+    // expansion/collapse
+    Action::Collapse => {
       // collapse directory
       if let Some(selected_path) = app_state.visible_paths.get(app_state.selected_index) {
         if let Some(node) = app_state.file_tree.get_mut(selected_path) {
@@ -352,7 +394,7 @@ pub fn handle_file_tree_input(app_state: &mut AppState, key: crossterm::event::K
       true
     }
 
-    KeyCode::Char('l') | KeyCode::Right => {
+    Action::Expand => {
       // expand directory
       if let Some(selected_path) = app_state.visible_paths.get(app_state.selected_index) {
         if let Some(node) = app_state.file_tree.get_mut(selected_path) {
@@ -365,27 +407,10 @@ pub fn handle_file_tree_input(app_state: &mut AppState, key: crossterm::event::K
       true
     }
 
-    // quit (q)
-    // !!!
-    KeyCode::Char('q') | KeyCode::Esc => {
-      // Note: quit handling is now in App struct
-      false // let app handle quit (q or esc)
-    }
-
     _ => false,
   }
 }
 
-/// Handles selection key press for file and directory selection.
-fn handle_selection_key(app_state: &mut AppState, selected_path: PathBuf) {
-  if let Some(_node) = app_state.file_tree.get(&selected_path) {
-    // toggle selection for both files and directories
-    if crate::file_utils::toggle_selection_recursive(&mut app_state.file_tree, &selected_path).is_err() {
-      // silently handle errors - don't block UI ops
-    }
-  }
-}
-
 /// Updates the visible files list based on current expansion states.
 /// rebuilds the flattened tree view that gets displayed.
 fn update_visible_files(app_state: &mut AppState) {
@@ -399,9 +424,6 @@ fn update_visible_files(app_state: &mut AppState) {
 
 /// Renders the file list without borders (for use inside other blocks).
 fn render_file_list_inner(frame: &mut Frame, area: Rect, app_state: &AppState, list_state: &mut ListState) {
-  // build map of directories with selected descendants
-  let dir_descendants_map = build_directories_with_descendants_map(&app_state.file_tree);
-
   // get the currently highlighted index
   let highlighted_index = if !app_state.visible_paths.is_empty() {
     Some(app_state.selected_index.min(app_state.visible_paths.len() - 1))
@@ -416,11 +438,12 @@ fn render_file_list_inner(frame: &mut Frame, area: Rect, app_state: &AppState, l
     .enumerate()
     .map(|(index, path)| {
       let is_highlighted = highlighted_index == Some(index);
-      create_list_item(path, &app_state.file_tree, &app_state.individual_token_counts, &dir_descendants_map, is_highlighted)
+      let has_secrets = app_state.secret_files.contains_key(path);
+      create_list_item(path, &app_state.file_tree, &app_state.individual_token_counts, &app_state.theme, is_highlighted, has_secrets)
     })
     .collect();
 
-  let files_list = List::new(items).highlight_style(Style::default().bg(Color::Blue)).highlight_symbol("► ");
+  let files_list = List::new(items).highlight_style(app_state.theme.highlight).highlight_symbol("► ");
 
   // make sure selected index is within bounds
   if !app_state.visible_paths.is_empty() {
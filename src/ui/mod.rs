@@ -1,5 +1,8 @@
 pub mod file_tree;
+pub mod overlay;
+pub mod prompt;
 
+use crate::keymap::Action;
 use crate::types::{AppState, Focus};
 use ratatui::{widgets::ListState, Frame};
 
@@ -13,21 +16,24 @@ pub struct UIState {
 /// Main entry point for all UI rendering.
 pub fn render_app(terminal_frame: &mut Frame, app_state: &AppState, ui_state: &mut UIState) {
   // use the original integrated layout that shows config and file tree
-  file_tree::render_file_tree_with_options(terminal_frame, terminal_frame.size(), app_state, &mut ui_state.file_tree_list_state, app_state.token_count, &app_state.status_message);
-}
+  file_tree::render_file_tree_with_options(terminal_frame, terminal_frame.size(), app_state, &mut ui_state.file_tree_list_state, app_state.token_count);
 
-/// Handles keyboard input for the entire app.
-/// Routes input to the appropriate component based on current focus.
-pub fn handle_input(app_state: &mut AppState, _ui_state: &mut UIState, key: crossterm::event::KeyEvent) -> bool {
-  // global shortcuts that work regardless of focus (r)
-  if let crossterm::event::KeyCode::Char('r') = key.code {
-    // run repomixm, handled by the main app loop
-    return false;
+  // overlays are drawn on top of the main layout
+  if let Some(palette) = &app_state.command_palette {
+    overlay::render_command_palette(terminal_frame, app_state, palette);
+  } else if app_state.show_help {
+    overlay::render_help(terminal_frame, app_state);
+  } else if app_state.show_log {
+    overlay::render_log(terminal_frame, app_state);
   }
+}
 
+/// Handles a keymap action for the entire app.
+/// Routes the action to the appropriate component based on current focus.
+pub fn handle_input(app_state: &mut AppState, _ui_state: &mut UIState, action: Action) -> bool {
   // route input based on current focus (file tree)
   match app_state.focus {
-    Focus::FileTree => file_tree::handle_file_tree_input(app_state, key),
+    Focus::FileTree => file_tree::handle_file_tree_action(app_state, action),
   }
 }
 
@@ -0,0 +1,159 @@
+use crate::command_palette::CommandPalette;
+use crate::keymap::{Action, ActionCategory, Keymap};
+use crate::types::{AppState, Backend};
+use ratatui::{
+  layout::Rect,
+  style::Modifier,
+  text::{Line, Span},
+  widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+  Frame,
+};
+
+/// Returns a rect centered in the area, clamped to the area size.
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+  let width = width.min(area.width);
+  let height = height.min(area.height);
+  Rect {
+    x: area.x + (area.width - width) / 2,
+    y: area.y + (area.height - height) / 2,
+    width,
+    height,
+  }
+}
+
+/// Returns all keys bound to an action, joined for display.
+fn keys_text(keymap: &Keymap, action: Action) -> String {
+  let keys = keymap.keys_for(action);
+  if keys.is_empty() {
+    "—".to_string()
+  } else {
+    keys.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+  }
+}
+
+/// Returns the current state of toggles and modes, shown next to the action.
+fn action_state(action: Action, app_state: &AppState) -> Option<String> {
+  let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+  let options = &app_state.repomix_options;
+  match action {
+    Action::ToggleCompress => Some(on_off(options.compress)),
+    Action::ToggleRemoveComments => Some(on_off(options.remove_comments)),
+    Action::ToggleFileTree => match options.backend {
+      Backend::Repomix => Some(on_off(options.file_tree)),
+      Backend::Yek => Some(on_off(app_state.yek_options.tree_header)),
+      Backend::Custom(_) => None,
+    },
+    Action::CycleSizeLimit => Some(app_state.yek_options.limit.display_name()),
+    Action::CycleOutputFormat => Some(options.output_format.display_name().to_string()),
+    Action::CycleSort => Some(app_state.sort_mode.display_name().to_string()),
+    Action::ToggleHidden => Some(if app_state.show_hidden { "shown" } else { "hidden" }.to_string()),
+    Action::SwitchBackend => Some(options.backend.display_name().to_string()),
+    Action::CycleTheme => Some(app_state.theme.name.display_name().to_string()),
+    _ => None,
+  }
+}
+
+/// Returns how far the help overlay scrolls on a terminal with the given rows.
+/// The overlay has a title per category, a blank line between them and a line per action.
+pub fn help_max_scroll(rows: u16) -> usize {
+  let line_count = ActionCategory::ALL.len() * 2 - 1 + Action::ALL.len();
+  line_count.saturating_sub(rows.saturating_sub(2) as usize)
+}
+
+/// Renders the help overlay, scrolled when it's taller than the terminal.
+pub fn render_help(frame: &mut Frame, app_state: &AppState) {
+  let theme = &app_state.theme;
+  let mut lines = Vec::new();
+  for category in ActionCategory::ALL {
+    if !lines.is_empty() {
+      lines.push(Line::raw(""));
+    }
+    lines.push(Line::from(Span::styled(category.display_name(), theme.info.add_modifier(Modifier::BOLD))));
+
+    for action in Action::ALL.iter().filter(|action| action.category() == category) {
+      // dim actions that don't apply to the active backend
+      let style = if action.is_available(&app_state.repomix_options.backend) { theme.text } else { theme.muted };
+      let mut spans = vec![Span::styled(format!("  {:<14}", keys_text(&app_state.keymap, *action)), theme.hint), Span::styled(action.description(), style)];
+      if let Some(state) = action_state(*action, app_state) {
+        spans.push(Span::styled(format!(" ({})", state), theme.selected));
+      }
+      lines.push(Line::from(spans));
+    }
+  }
+
+  let area = centered_rect(frame.size(), 60, lines.len() as u16 + 2);
+  let max_scroll = help_max_scroll(frame.size().height);
+  let scroll = app_state.help_scroll.min(max_scroll);
+  let title = if max_scroll > 0 { "Help (↑/↓ scroll • any other key to close)" } else { "Help (any key to close)" };
+  let block = Block::default().borders(Borders::ALL).title(title).style(theme.accent);
+
+  frame.render_widget(Clear, area);
+  frame.render_widget(Paragraph::new(lines).block(block).scroll((scroll as u16, 0)), area);
+}
+
+/// Renders the command palette with the fuzzy matched actions.
+pub fn render_command_palette(frame: &mut Frame, app_state: &AppState, palette: &CommandPalette) {
+  let theme = &app_state.theme;
+  let area = centered_rect(frame.size(), 60, 16);
+  let block = Block::default().borders(Borders::ALL).title("Commands (Enter run • Esc close)").style(theme.accent);
+  let inner = block.inner(area);
+
+  frame.render_widget(Clear, area);
+  frame.render_widget(block, area);
+  if inner.height < 2 {
+    return;
+  }
+
+  let input_line = Line::from(vec![Span::styled(": ", theme.info), Span::styled(format!("{}█", palette.input), theme.text)]);
+  frame.render_widget(Paragraph::new(input_line), Rect { height: 1, ..inner });
+
+  let items: Vec<ListItem> = palette
+    .matches
+    .iter()
+    .map(|action| {
+      let mut spans = vec![Span::styled(action.description(), theme.text)];
+      if let Some(state) = action_state(*action, app_state) {
+        spans.push(Span::styled(format!(" ({})", state), theme.selected));
+      }
+      spans.push(Span::styled(format!("  {}", keys_text(&app_state.keymap, *action)), theme.hint));
+      ListItem::new(Line::from(spans))
+    })
+    .collect();
+
+  let list = List::new(items).highlight_style(theme.highlight);
+  let mut list_state = ListState::default();
+  list_state.select(Some(palette.selected));
+  let list_area = Rect {
+    y: inner.y + 1,
+    height: inner.height - 1,
+    ..inner
+  };
+  frame.render_stateful_widget(list, list_area, &mut list_state);
+}
+
+/// Renders the message log, newest first, with full details such as backend stderr.
+pub fn render_log(frame: &mut Frame, app_state: &AppState) {
+  let theme = &app_state.theme;
+  let mut lines = Vec::new();
+  for notification in app_state.notifications.entries().rev() {
+    lines.push(Line::from(vec![
+      Span::styled(format!("{:>8} ", notification.age_text()), theme.muted),
+      Span::styled(format!("{:<8} ", notification.level.display_name()), theme.status(notification.level)),
+      Span::styled(format!("[{}] ", notification.source.display_name()), theme.info),
+      Span::styled(notification.message.clone(), theme.text),
+    ]));
+    if let Some(details) = &notification.details {
+      lines.extend(details.lines().map(|line| Line::styled(format!("           {}", line), theme.muted)));
+    }
+  }
+  if lines.is_empty() {
+    lines.push(Line::styled("No messages yet", theme.muted));
+  }
+
+  let size = frame.size();
+  let area = centered_rect(size, size.width.saturating_sub(4), size.height.saturating_sub(2));
+  let block = Block::default().borders(Borders::ALL).title("Messages (↑/↓ scroll • Esc close)").style(theme.accent);
+
+  frame.render_widget(Clear, area);
+  frame.render_widget(Paragraph::new(lines).block(block).scroll((app_state.log_scroll.min(u16::MAX as usize) as u16, 0)), area);
+}
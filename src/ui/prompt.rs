@@ -0,0 +1,46 @@
+use crate::selection_patterns::PatternPrompt;
+use crate::theme::Theme;
+use ratatui::{
+  layout::Rect,
+  text::{Line, Span},
+  widgets::Paragraph,
+  Frame,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Renders the pattern selection prompt with a live preview of the result.
+/// Takes the place of the nav hints while the prompt is open.
+pub fn render_pattern_prompt(frame: &mut Frame, area: Rect, prompt: &PatternPrompt, file_token_cache: &HashMap<PathBuf, usize>, theme: &Theme) {
+  let input_line = Line::from(vec![Span::styled("Select: ", theme.info), Span::styled(format!("{}█", prompt.input), theme.text)]);
+
+  // show preview, parse error or usage hints below the input
+  let preview_line = if let Some(error) = &prompt.error {
+    Line::from(Span::styled(error.clone(), theme.status_error))
+  } else if let Some(preview) = &prompt.preview {
+    // sum known token counts, the rest are still being calculated
+    let mut total_tokens = 0;
+    let mut pending = 0;
+    for path in &preview.resulting_files {
+      match file_token_cache.get(path) {
+        Some(count) => total_tokens += count,
+        None => pending += 1,
+      }
+    }
+
+    let pending_text = if pending > 0 { format!(" ({} pending)", pending) } else { String::new() };
+
+    Line::from(vec![
+      Span::styled(format!("+{}", preview.to_select.len()), theme.status_success),
+      Span::raw(" "),
+      Span::styled(format!("-{}", preview.to_deselect.len()), theme.status_error),
+      Span::raw(format!(" → {} selected • ", preview.resulting_files.len())),
+      Span::styled(format!("{} tokens{}", crate::token_counter::format_token_count(total_tokens), pending_text), theme.tokens),
+      Span::raw(" • Enter apply • Esc cancel"),
+    ])
+  } else {
+    Line::from(Span::styled("src/**/*.rs • !**/tests/** • ext:py • re:handler • Enter apply • Esc cancel", theme.hint))
+  };
+
+  frame.render_widget(Paragraph::new(vec![input_line, preview_line]), area);
+}
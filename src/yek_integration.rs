@@ -1,88 +1,304 @@
+use crate::process_runner::{self, RunMonitor};
+use crate::types::{format_si, BackendOutput, DownloadStatus, YekLimit, YekOptions};
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::process::Command;
+use tokio::sync::watch;
 
-/// Yek integration module with runtime download.
-pub struct Yek {
-  /// Path to the yek binary (downloaded on first use).
-  yek_binary_path: PathBuf,
+/// How long `yek --version` may take before the binary is considered broken.
+const VERSION_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Placeholders every yek output template needs.
+const TEMPLATE_PLACEHOLDERS: [&str; 2] = ["FILE_PATH", "FILE_CONTENT"];
+
+/// Header yek puts before each file with its default template.
+const DEFAULT_FILE_MARKER: &str = ">>>> ";
+
+/// Yek install settings from the config file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct YekConfig {
+  /// Required yek version, binaries reporting anything else are skipped (any version if not set)
+  pub version: Option<String>,
+  /// Yek binary to use instead of searching PATH and the cache
+  pub path: Option<PathBuf>,
 }
 
-impl Yek {
-  /// Creates a new yek instance.
-  /// Downloads yek binary on first use if not already available.
-  pub fn new() -> Result<Self> {
-    // determine where to store the yek binary
-    let yek_binary_path = Self::get_yek_binary_path()?;
+/// Provisioning progress shared with the background task.
+#[derive(Debug, Clone)]
+struct ProvisionState {
+  /// Current step
+  status: DownloadStatus,
+  /// Verified binary once ready
+  binary: Option<PathBuf>,
+}
 
-    Ok(Self { yek_binary_path })
+/// Finds or installs a verified yek binary in the background.
+/// Order: configured path, PATH, siff's cache, then `cargo install` into the cache.
+pub struct YekProvisioner {
+  /// Version pin and configured path
+  config: YekConfig,
+  /// Last status seen from the background task
+  status: DownloadStatus,
+  /// Verified binary once ready
+  binary: Option<PathBuf>,
+  /// Updates from the background task while it runs
+  receiver: Option<watch::Receiver<ProvisionState>>,
+}
+
+impl YekProvisioner {
+  /// Creates a provisioner, nothing runs until it's started.
+  pub fn new(config: &YekConfig) -> Self {
+    Self {
+      config: config.clone(),
+      status: DownloadStatus::NotStarted,
+      binary: None,
+      receiver: None,
+    }
   }
 
-  /// Gets the path where yek binary should be stored and downloads it if needed.
-  fn get_yek_binary_path() -> Result<PathBuf> {
-    // create a runtime directory for the binary
-    let runtime_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join("bin");
+  /// Checks, without running anything, that a yek binary exists or can be installed.
+  pub fn check_available(config: &YekConfig) -> Result<()> {
+    if let Some(path) = &config.path {
+      return if path.is_file() { Ok(()) } else { Err(anyhow::anyhow!("Configured yek binary not found: {}", path.display())) };
+    }
+    if process_runner::find_executable("yek").is_some() || cached_binary_path(config.version.as_deref()).is_file() || process_runner::find_executable("cargo").is_some() {
+      Ok(())
+    } else {
+      Err(anyhow::anyhow!("Yek was not found on PATH and cargo is not available to install it"))
+    }
+  }
 
-    std::fs::create_dir_all(&runtime_dir).context("Failed to create runtime directory")?;
+  /// Gets the current provisioning status.
+  pub fn download_status(&self) -> &DownloadStatus {
+    &self.status
+  }
 
-    let binary_name = if cfg!(windows) { "yek.exe" } else { "yek" };
-    let runtime_binary_path = runtime_dir.join(binary_name);
+  /// Returns the verified binary once provisioning is done.
+  pub fn binary_path(&self) -> Option<&Path> {
+    self.binary.as_deref()
+  }
 
-    // if binary doesn't exist, download it
-    if !runtime_binary_path.exists() {
-      Self::download_yek_binary(&runtime_binary_path)?;
+  /// Starts provisioning in the background if it's not running or done.
+  /// Returns true if it was started.
+  pub fn start(&mut self) -> bool {
+    if !matches!(self.status, DownloadStatus::NotStarted | DownloadStatus::Failed(_)) {
+      return false;
     }
 
-    Ok(runtime_binary_path)
+    self.status = DownloadStatus::Downloading("Looking for yek...".to_string());
+    let (sender, receiver) = watch::channel(ProvisionState { status: self.status.clone(), binary: None });
+    self.receiver = Some(receiver);
+
+    let config = self.config.clone();
+    tokio::spawn(async move {
+      let final_state = match provision(&config, &sender).await {
+        Ok(binary) => ProvisionState {
+          status: DownloadStatus::Ready,
+          binary: Some(binary),
+        },
+        Err(e) => ProvisionState {
+          status: DownloadStatus::Failed(e.to_string()),
+          binary: None,
+        },
+      };
+      let _ = sender.send(final_state);
+    });
+    true
   }
 
-  /// Downloads yek binary from crates.io using cargo install.
-  fn download_yek_binary(target_path: &Path) -> Result<()> {
-    use std::process::Command as StdCommand;
+  /// Picks up progress from the background task.
+  /// Returns true if the status changed.
+  pub fn update(&mut self) -> bool {
+    let Some(receiver) = self.receiver.as_mut() else {
+      return false;
+    };
+    if !receiver.has_changed().unwrap_or(false) {
+      return false;
+    }
 
-    // create a temporary directory for cargo install
-    let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+    let state = receiver.borrow_and_update().clone();
+    self.apply(state);
+    true
+  }
 
-    let temp_bin_dir = temp_dir.path().join("bin");
-    std::fs::create_dir_all(&temp_bin_dir).context("Failed to create temporary bin directory")?;
+  /// Starts provisioning if needed and waits for it to finish, for runs without the UI.
+  /// Returns the verified binary.
+  pub async fn wait_until_ready(&mut self) -> Result<PathBuf> {
+    self.start();
+    while let Some(receiver) = self.receiver.as_mut() {
+      if receiver.changed().await.is_err() {
+        break;
+      }
+      let state = receiver.borrow_and_update().clone();
+      self.apply(state);
+    }
 
-    // install yek to temporary directory
-    let output = StdCommand::new("cargo")
-      .args(["install", "yek", "--root", temp_dir.path().to_str().unwrap(), "--quiet"])
-      .output()
-      .context("Failed to execute cargo install yek")?;
+    match (&self.status, &self.binary) {
+      (DownloadStatus::Ready, Some(binary)) => Ok(binary.clone()),
+      (DownloadStatus::Failed(e), _) => Err(anyhow::anyhow!("Yek setup failed: {}", e)),
+      _ => Err(anyhow::anyhow!("Yek setup stopped before it finished")),
+    }
+  }
 
-    if !output.status.success() {
-      let stderr = String::from_utf8_lossy(&output.stderr);
-      return Err(anyhow::anyhow!("Failed to install yek: {}", stderr));
+  /// Takes over a state sent by the background task.
+  fn apply(&mut self, state: ProvisionState) {
+    if matches!(state.status, DownloadStatus::Ready | DownloadStatus::Failed(_)) {
+      self.receiver = None;
     }
+    self.status = state.status;
+    self.binary = state.binary;
+  }
+}
 
-    // find the installed yek binary
-    let binary_name = if cfg!(windows) { "yek.exe" } else { "yek" };
-    let temp_yek_path = temp_bin_dir.join(binary_name);
+/// Runs the provisioning steps, reporting each one, and returns the verified binary.
+async fn provision(config: &YekConfig, sender: &watch::Sender<ProvisionState>) -> Result<PathBuf> {
+  let report = |message: String| {
+    let _ = sender.send(ProvisionState {
+      status: DownloadStatus::Downloading(message),
+      binary: None,
+    });
+  };
+  let pinned = config.version.as_deref();
+
+  // a configured binary is used as is, no fallback to something else
+  if let Some(path) = &config.path {
+    report(format!("Verifying {}...", path.display()));
+    verify_binary(path, pinned).await.with_context(|| format!("Configured yek binary {} is not usable", path.display()))?;
+    return Ok(path.clone());
+  }
 
-    if !temp_yek_path.exists() {
-      return Err(anyhow::anyhow!("Yek binary not found after installation"));
+  // an existing install on PATH
+  if let Some(path) = process_runner::find_executable("yek") {
+    report(format!("Verifying {}...", path.display()));
+    if verify_binary(&path, pinned).await.is_ok() {
+      return Ok(path);
     }
+  }
 
-    // copy to final location
-    std::fs::copy(&temp_yek_path, target_path).context("Failed to copy yek binary to final location")?;
-
-    // make executable on unix systems
-    #[cfg(unix)]
-    {
-      use std::os::unix::fs::PermissionsExt;
-      let mut perms = std::fs::metadata(target_path)?.permissions();
-      perms.set_mode(0o755);
-      std::fs::set_permissions(target_path, perms)?;
+  // siff's own cached install, removed if it doesn't verify
+  let cached = cached_binary_path(pinned);
+  if cached.is_file() {
+    report("Verifying cached yek...".to_string());
+    if verify_binary(&cached, pinned).await.is_ok() {
+      return Ok(cached);
     }
+    let _ = std::fs::remove_file(&cached);
+  }
+
+  // install from older siff versions, used as long as it verifies
+  let binary_name = if cfg!(windows) { "yek.exe" } else { "yek" };
+  let legacy = dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join("bin").join(binary_name);
+  if legacy.is_file() && verify_binary(&legacy, pinned).await.is_ok() {
+    return Ok(legacy);
+  }
+
+  // build it with cargo
+  if process_runner::find_executable("cargo").is_none() {
+    return Err(anyhow::anyhow!("Yek was not found on PATH and cargo is not available to install it"));
+  }
+  report(format!("Installing yek {} with cargo (can take a few minutes)...", pinned.unwrap_or("(latest)")));
+  install_yek(pinned, &cached).await?;
+
+  report("Verifying installed yek...".to_string());
+  verify_binary(&cached, pinned).await.context("Installed yek binary doesn't work")?;
+  Ok(cached)
+}
+
+/// Returns where siff caches yek: ~/.cache/siff/yek/<version or latest>/yek
+fn cached_binary_path(version: Option<&str>) -> PathBuf {
+  let binary_name = if cfg!(windows) { "yek.exe" } else { "yek" };
+  dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".")).join("siff").join("yek").join(version.unwrap_or("latest")).join(binary_name)
+}
+
+/// Runs `yek --version` and returns the reported version.
+/// Fails if the binary doesn't run, or reports a different version than the pinned one.
+async fn verify_binary(path: &Path, pinned: Option<&str>) -> Result<String> {
+  let output = tokio::time::timeout(VERSION_CHECK_TIMEOUT, Command::new(path).arg("--version").kill_on_drop(true).output())
+    .await
+    .context("yek --version timed out")?
+    .context("Failed to run yek --version")?;
+  if !output.status.success() {
+    return Err(anyhow::anyhow!("yek --version exited with {}", output.status));
+  }
+
+  let version = parse_version(&String::from_utf8_lossy(&output.stdout)).context("yek --version printed no version")?;
+  match pinned.map(|pinned| pinned.trim_start_matches('v')) {
+    Some(pinned) if pinned != version => Err(anyhow::anyhow!("found yek {}, but version {} is pinned", version, pinned)),
+    _ => Ok(version),
+  }
+}
+
+/// Extracts the version from `--version` output like "yek 0.21.0".
+fn parse_version(output: &str) -> Option<String> {
+  output
+    .split_whitespace()
+    .map(|word| word.trim_start_matches('v'))
+    .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+    .map(str::to_string)
+}
+
+/// Installs yek from crates.io with cargo install into the cache.
+async fn install_yek(version: Option<&str>, target_path: &Path) -> Result<()> {
+  // create a temporary directory for cargo install
+  let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+
+  // install yek to temporary directory
+  let mut command = Command::new("cargo");
+  command.args(["install", "yek", "--locked", "--quiet", "--root"]).arg(temp_dir.path());
+  if let Some(version) = version {
+    command.arg("--version").arg(format!("={}", version.trim_start_matches('v')));
+  }
+  let output = command.kill_on_drop(true).output().await.context("Failed to execute cargo install yek")?;
+
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow::anyhow!("Failed to install yek: {}", stderr));
+  }
+
+  // find the installed yek binary
+  let binary_name = if cfg!(windows) { "yek.exe" } else { "yek" };
+  let temp_yek_path = temp_dir.path().join("bin").join(binary_name);
+
+  if !temp_yek_path.exists() {
+    return Err(anyhow::anyhow!("Yek binary not found after installation"));
+  }
 
-    Ok(())
+  // copy to final location
+  if let Some(parent) = target_path.parent() {
+    std::fs::create_dir_all(parent).context("Failed to create yek cache directory")?;
+  }
+  std::fs::copy(&temp_yek_path, target_path).context("Failed to copy yek binary to final location")?;
+
+  // make executable on unix systems
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = std::fs::metadata(target_path)?.permissions();
+    perms.set_mode(0o755);
+    std::fs::set_permissions(target_path, perms)?;
+  }
+
+  Ok(())
+}
+
+/// Yek runner for a provisioned binary.
+pub struct Yek {
+  /// Path to the verified yek binary
+  yek_binary_path: PathBuf,
+}
+
+impl Yek {
+  /// Creates a runner for a binary found by the provisioner.
+  pub fn new(yek_binary_path: PathBuf) -> Self {
+    Self { yek_binary_path }
   }
 
   /// Processes selected files using yek binary.
-  /// Returns the serialized content as a string.
-  pub async fn process_files(&self, selected_files: &[PathBuf], root_path: &Path) -> Result<String> {
+  /// Returns the serialized content as a string, warnings and stderr are added to the output.
+  pub async fn process_files(&self, selected_files: &[PathBuf], options: &YekOptions, root_path: &Path, monitor: &RunMonitor, output: &mut BackendOutput) -> Result<String> {
     if selected_files.is_empty() {
       return Err(anyhow::anyhow!("Error: No files selected for processing"));
     }
@@ -102,7 +318,7 @@ impl Yek {
         Ok(rel_path) => rel_path,
         Err(_) => {
           // skip files outside root directory
-          eprintln!("Warning: Skipping file outside root directory: {}", file_path.display());
+          output.warnings.push(format!("Skipping file outside root directory: {}", file_path.display()));
           continue;
         }
       };
@@ -112,13 +328,13 @@ impl Yek {
 
       // skip paths that try to escape the root directory
       if path_str.contains("..") {
-        eprintln!("Warning: Skipping file with path traversal attempt: {}", path_str);
+        output.warnings.push(format!("Skipping file with path traversal attempt: {}", path_str));
         continue;
       }
 
       // skip empty or dangerous paths
       if path_str.is_empty() || path_str.starts_with('-') {
-        eprintln!("Warning: Skipping file with invalid path: {}", path_str);
+        output.warnings.push(format!("Skipping file with invalid path: {}", path_str));
         continue;
       }
 
@@ -132,95 +348,124 @@ impl Yek {
 
     // warn about large file counts
     if yek_args.len() > 1000 {
-      eprintln!("Warning: Large file count ({}), processing may take some time", yek_args.len());
+      output.warnings.push(format!("Large file count ({}), processing may take some time", yek_args.len()));
     }
 
     // execute yek with the selected files
-    let output = Command::new(&self.yek_binary_path).args(&yek_args).current_dir(root_path).output().await.context("Failed to execute yek binary")?;
-
-    if output.status.success() {
-      let content = String::from_utf8_lossy(&output.stdout);
+    // each file header is counted for progress when the template has a recognizable one
+    // anything yek writes besides stdout goes to a private temp dir, removed when the run ends
+    let temp_dir = tempfile::Builder::new().prefix("siff-yek-").tempdir().context("Failed to create temporary directory for yek")?;
+    let option_args = build_option_args(options, temp_dir.path())?;
+    let file_marker = file_marker(options);
+    let mut command = Command::new(&self.yek_binary_path);
+    command
+      .args(&option_args)
+      .arg("--")
+      .args(&yek_args)
+      .current_dir(root_path)
+      .env("TMPDIR", temp_dir.path())
+      .env("TEMP", temp_dir.path())
+      .env("TMP", temp_dir.path());
+    let command_output = process_runner::run_monitored(command, monitor, "Packing", file_marker.as_deref(), None, None).await.context("Failed to execute yek binary")?;
+
+    if command_output.status.success() {
+      // keep stderr of successful runs for the message log
+      output.stderr = String::from_utf8_lossy(&command_output.stderr).trim().to_string();
+      let content = String::from_utf8_lossy(&command_output.stdout);
       Ok(content.to_string())
     } else {
-      let stderr = String::from_utf8_lossy(&output.stderr);
-      Err(anyhow::anyhow!("Error: Yek failed with exit code {}: {}", output.status.code().unwrap_or(-1), stderr))
-    }
-  }
-
-  /// Copies content to clipboard using platform specific commands.
-  pub async fn copy_to_clipboard(&self, content: &str) -> Result<String> {
-    use tokio::process::Command;
-
-    // determine the clipboard command based on the platform
-    let clipboard_cmd = if cfg!(target_os = "macos") {
-      vec!["pbcopy"]
-    } else if cfg!(target_os = "linux") {
-      // try xclip first, then xsel as fallback
-      if Command::new("which").arg("xclip").output().await.is_ok() {
-        vec!["xclip", "-selection", "clipboard"]
-      } else if Command::new("which").arg("xsel").output().await.is_ok() {
-        vec!["xsel", "--clipboard", "--input"]
-      } else {
-        return Err(anyhow::anyhow!(
-          "No clipboard utility found. Please install xclip or xsel:\n\
-                     sudo apt-get install xclip  # or\n\
-                     sudo apt-get install xsel"
-        ));
-      }
-    } else if cfg!(target_os = "windows") {
-      vec!["clip"]
-    } else {
-      return Err(anyhow::anyhow!("Unsupported platform for clipboard operations"));
-    };
-
-    // execute clipboard command
-    let mut cmd = Command::new(clipboard_cmd[0]);
-    for arg in &clipboard_cmd[1..] {
-      cmd.arg(arg);
+      let stderr = String::from_utf8_lossy(&command_output.stderr);
+      Err(anyhow::anyhow!("Error: Yek failed with exit code {}: {}", command_output.status.code().unwrap_or(-1), stderr))
     }
+  }
 
-    let mut child = cmd
-      .stdin(std::process::Stdio::piped())
-      .stdout(std::process::Stdio::piped())
-      .stderr(std::process::Stdio::piped())
-      .spawn()
-      .context("Failed to spawn clipboard command")?;
+  /// Processes files and returns the packed content with the run's summary.
+  /// Main entry point that replaces run_yek function.
+  pub async fn run_yek_integrated(&self, selected_files: &[PathBuf], options: &YekOptions, root_path: &Path, monitor: &RunMonitor) -> Result<BackendOutput> {
+    // process files using yek library
+    let mut output = BackendOutput::default();
+    output.content = self.process_files(selected_files, options, root_path, monitor, &mut output).await?;
+    output.message = format!("{} files processed", selected_files.len());
+    Ok(output)
+  }
+}
 
-    // write content to stdin
-    if let Some(stdin) = child.stdin.take() {
-      use tokio::io::AsyncWriteExt;
-      let mut stdin = stdin;
-      stdin.write_all(content.as_bytes()).await.context("Failed to write to clipboard command stdin")?;
-      stdin.shutdown().await.context("Failed to close clipboard command stdin")?;
-    }
+/// Builds the yek arguments for the options.
+/// Ignore patterns and priority rules only exist in yek's config file, so one is written to the temp dir.
+fn build_option_args(options: &YekOptions, temp_dir: &Path) -> Result<Vec<String>> {
+  let mut args = Vec::new();
 
-    // wait for command to complete
-    let output = child.wait_with_output().await.context("Failed to wait for clipboard command")?;
+  match options.limit {
+    YekLimit::Default => {}
+    YekLimit::Tokens(tokens) => args.extend(["--tokens".to_string(), tokens.to_string()]),
+    YekLimit::Bytes(bytes) => args.extend(["--max-size".to_string(), format_size(bytes)]),
+  }
 
-    if output.status.success() {
-      Ok("Content copied to clipboard".to_string())
-    } else {
-      let stderr = String::from_utf8_lossy(&output.stderr);
-      Err(anyhow::anyhow!("Clipboard command failed: {}", stderr))
-    }
+  if options.tree_header {
+    args.push("--tree-header".to_string());
   }
 
-  /// Processes files and copies to clipboard in one operation.
-  /// Main entry point that replaces run_yek function.
-  pub async fn run_yek_integrated(&self, selected_files: &[PathBuf], root_path: &Path) -> Result<String> {
-    // process files using yek library
-    let content = self.process_files(selected_files, root_path).await?;
+  if let Some(template) = &options.output_template {
+    args.extend(["--output-template".to_string(), template.clone()]);
+  }
 
-    // copy to clipboard
-    self.copy_to_clipboard(&content).await?;
+  if !options.ignore_patterns.is_empty() || !options.priority_rules.is_empty() {
+    let config_path = temp_dir.join("yek.json");
+    let config = serde_json::json!({
+      "ignore_patterns": options.ignore_patterns,
+      "priority_rules": options.priority_rules,
+    });
+    std::fs::write(&config_path, serde_json::to_string_pretty(&config)?).context("Failed to write yek config file")?;
+    args.extend(["--config-file".to_string(), config_path.to_string_lossy().to_string()]);
+  }
+
+  Ok(args)
+}
 
-    Ok(format!("{} files processed and copied to clipboard", selected_files.len()))
+/// Formats a byte count the way yek's --max-size parses it ("2MB", "500KB" or plain bytes).
+fn format_size(bytes: u64) -> String {
+  let size = format_si(bytes);
+  if size.ends_with(|character: char| character.is_ascii_digit()) {
+    size
+  } else {
+    format!("{}B", size)
   }
 }
 
+/// Returns the prefix of each file header in the output, used to count processed files.
+/// Templates that start with the file content have no usable prefix.
+fn file_marker(options: &YekOptions) -> Option<String> {
+  let Some(template) = &options.output_template else {
+    return Some(DEFAULT_FILE_MARKER.to_string());
+  };
+  let prefix = &template[..template.find("FILE_PATH")?];
+  (!prefix.is_empty() && !prefix.contains('\n') && !prefix.contains("FILE_CONTENT")).then(|| prefix.to_string())
+}
+
 /// Validates yek options and selected files.
-/// Returns a list of warnings if any issues are found.
-pub fn validate_yek_options(selected_files: &[PathBuf]) -> Vec<String> {
+/// Returns an error for options yek would reject, and a list of warnings if any other issues are found.
+pub fn validate_yek_options(options: &YekOptions, selected_files: &[PathBuf]) -> Result<Vec<String>> {
+  match options.limit {
+    YekLimit::Bytes(0) | YekLimit::Tokens(0) => anyhow::bail!("Size limit must be greater than zero"),
+    _ => {}
+  }
+
+  if let Some(template) = &options.output_template {
+    for placeholder in TEMPLATE_PLACEHOLDERS {
+      if !template.contains(placeholder) {
+        anyhow::bail!("Output template must contain {}", placeholder);
+      }
+    }
+  }
+
+  for rule in &options.priority_rules {
+    regex::Regex::new(&rule.pattern).with_context(|| format!("Invalid priority rule pattern '{}'", rule.pattern))?;
+  }
+
+  for pattern in &options.ignore_patterns {
+    globset::Glob::new(pattern).with_context(|| format!("Invalid ignore pattern '{}'", pattern))?;
+  }
+
   let mut warnings = Vec::new();
 
   if selected_files.is_empty() {
@@ -232,5 +477,80 @@ pub fn validate_yek_options(selected_files: &[PathBuf]) -> Vec<String> {
     warnings.push("Large number of files selected, May take a moment to process".to_string());
   }
 
-  warnings
+  Ok(warnings)
+}
+
+// test for yek binary verification
+// TODO: move tests to main testing file
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_version() {
+    assert_eq!(parse_version("yek 0.21.0\n").as_deref(), Some("0.21.0"));
+    assert_eq!(parse_version("yek v0.13.1").as_deref(), Some("0.13.1"));
+    assert_eq!(parse_version("yek"), None);
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn test_verify_binary_checks_pinned_version() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let binary = temp_dir.path().join("yek");
+    std::fs::write(&binary, "#!/bin/sh\necho 'yek 0.21.0'\n").unwrap();
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert_eq!(verify_binary(&binary, None).await.unwrap(), "0.21.0");
+    assert!(verify_binary(&binary, Some("v0.21.0")).await.is_ok());
+    assert!(verify_binary(&binary, Some("0.20.0")).await.is_err());
+    assert!(verify_binary(&temp_dir.path().join("missing"), None).await.is_err());
+  }
+
+  #[test]
+  fn test_yek_options() {
+    use crate::types::YekPriorityRule;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let files = [PathBuf::from("src/main.rs")];
+    let mut options = YekOptions {
+      limit: YekLimit::Bytes(2_000_000),
+      tree_header: true,
+      ..YekOptions::default()
+    };
+    assert!(validate_yek_options(&options, &files).unwrap().is_empty());
+    assert_eq!(build_option_args(&options, temp_dir.path()).unwrap(), ["--max-size", "2MB", "--tree-header"]);
+    assert_eq!(file_marker(&options).as_deref(), Some(">>>> "));
+
+    // templates need both placeholders
+    options.output_template = Some("== FILE_PATH ==\nFILE_CONTENT".to_string());
+    assert!(validate_yek_options(&options, &files).is_ok());
+    assert_eq!(file_marker(&options).as_deref(), Some("== "));
+    options.output_template = Some("FILE_CONTENT".to_string());
+    assert!(validate_yek_options(&options, &files).is_err());
+    options.output_template = None;
+
+    // rules go into a generated config file
+    options.priority_rules.push(YekPriorityRule { pattern: "^src/".to_string(), score: 10 });
+    let args = build_option_args(&options, temp_dir.path()).unwrap();
+    assert_eq!(args[args.len() - 2], "--config-file");
+    assert!(std::fs::read_to_string(&args[args.len() - 1]).unwrap().contains("^src/"));
+
+    options.priority_rules[0].pattern = "(".to_string();
+    assert!(validate_yek_options(&options, &files).is_err());
+    options.priority_rules.clear();
+    options.ignore_patterns.push("[".to_string());
+    assert!(validate_yek_options(&options, &files).is_err());
+    options.ignore_patterns.clear();
+    options.limit = YekLimit::Tokens(0);
+    assert!(validate_yek_options(&options, &files).is_err());
+
+    // cycling from a custom limit starts at the presets again
+    assert_eq!(YekLimit::Tokens(5).next(), YekLimit::Default);
+    assert_eq!(YekLimit::Default.next(), YekLimit::Tokens(32_000));
+    assert_eq!(YekLimit::Bytes(10_000_000).next(), YekLimit::Default);
+    assert_eq!(YekLimit::Tokens(128_000).display_name(), "128K tokens");
+  }
 }